@@ -22,9 +22,10 @@ impl AppConductor for PanelsApp {
     fn init_app(&mut self, renderers: &mut Vec<TextureRenderer>, state: &mut State, programs: &mut Vec<Box<dyn ProgramHook<Message = Self::Message>>>) -> EventLoopAction<Self::Message> {
         // now we can add renderers and programs to our application
         // this renderer will render to the surface and render each panel
-        panels::create_program_and_renderer("manager", (state, renderers, programs), TextureIndex::Surface, Box::new(SurfaceManager::new(vec![
-            RenderObject::new_placeholder_rect()
-        ])));
+        let panel_renderobjs = vec![
+            RenderObject::new_placeholder_rect(state)
+        ];
+        panels::create_program_and_renderer("manager", (state, renderers, programs), TextureIndex::Surface, Box::new(SurfaceManager::new(panel_renderobjs)));
         state.set_fps(None);
         EventLoopAction::None
     }
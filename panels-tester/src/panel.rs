@@ -1,41 +1,43 @@
 use std::time::Instant;
 use panels::appmgmt::EventLoopAction;
 use panels::event::WindowEvent;
-use panels::rect::{WorldPoint, WorldRectangle};
+use panels::rect::{LogicalPoint, LogicalRectangle, WorldRectangle};
 use panels::texturerenderer::TextureRenderer;
 use panels::timing::Timing;
 use panels::wgpustate::State;
 use panels::wgpu;
 use panels::bytemuck;
 use panels::programhook::ProgramHook;
+use panels::spriteanim::SpriteAnimator;
 
 /// The logical side of the Panel, containing position data and rendering state.
 /// * Note: a Panel's RenderState/Object is only it's Panel canvas texture.
 pub struct Panel {
-    /// The Rect of the Panel on the screen.
+    /// The Rect of the Panel on the screen, in logical pixels so it looks the same on any monitor.
     /// Optionally, use this to check if the mouse is in this `Panel`, and direct `WindowEvents` to it.
-    world_rect: WorldRectangle,
+    rect: LogicalRectangle,
 
     /// The `TextureRenderer` index on the State's `texture_renderers` list.
     renderer_id: usize,
 
-    /// The bindgroup locations for animations on this renderer
-    animation_bindgroups: Vec<usize>
+    /// Sprite animations played on the Panel's canvas object.
+    animation: SpriteAnimator
 }
 
 impl Panel {
     /// Creates a new panel that can be attatched to a TextureRenderer
     pub fn new() -> Self {
         Self {
-            world_rect: WorldRectangle {
-                pos: WorldPoint::new(0f32, 0f32, 0f32),
-                width: 0.0,
-                height: 0.0,
-            },
+            rect: LogicalRectangle::new(LogicalPoint::new(0.0, 0.0), 0.0, 0.0),
             renderer_id: 0,
-            animation_bindgroups: vec![]
+            animation: SpriteAnimator::new()
         }
     }
+
+    /// The Panel's rect in world units, for the current window size and scale factor.
+    pub fn world_rect(&self, state: &State) -> WorldRectangle {
+        state.logical_to_world(&self.rect)
+    }
 }
 
 impl ProgramHook for Panel {
@@ -59,9 +61,8 @@ impl ProgramHook for Panel {
         render_pass: &mut wgpu::RenderPass<'a>
     ) {
 
-        for obj in &renderer.my_objects {
-            obj.render_this(_state, render_pass);
-        }
+        renderer.render_queue.submit_all(&renderer.my_objects);
+        renderer.render_queue.flush(_state, render_pass);
 
         // render any subprograms to thier own textures if they are ready (check Timing)
 
@@ -74,12 +75,9 @@ impl ProgramHook for Panel {
     }
 
     fn update(&mut self, renderer: &mut TextureRenderer, _state: &mut State) -> EventLoopAction<Self::Message> {
-        // let obj = &renderer.my_objects[0];
-        // _state.queue.write_buffer(
-        //     &_state.models[obj.model].offset_buffer,
-        //     0,
-        //     bytemuck::cast_slice(&[obj.position]),
-        // );
+        if let Some(canvas) = renderer.my_objects.get_mut(0) {
+            self.animation.update(&renderer.updatef_status, _state, canvas);
+        }
         EventLoopAction::None
     }
 
@@ -28,13 +28,8 @@ impl SurfaceManager {
         }
     }
 
-    pub fn move_panel(&self, state: &State, panel_id: usize, worldpoint: WorldPoint) {
-        let obj = &self.panel_renderobjs[panel_id];
-        state.queue.write_buffer(
-            &state.models[obj.model].offset_buffer,
-            0,
-            panels::bytemuck::cast_slice(&[worldpoint]),
-        );
+    pub fn move_panel(&mut self, state: &State, panel_id: usize, worldpoint: WorldPoint) {
+        self.panel_renderobjs[panel_id].set_position(state, worldpoint);
     }
 }
 
@@ -74,14 +69,13 @@ impl ProgramHook for SurfaceManager {
     }
 
     fn update(&mut self, renderer: &mut TextureRenderer, state: &mut State) -> EventLoopAction<Self::Message> {
-        self.move_panel(state, 0usize, self.lastpos*WorldPoint::new(1.0,-1.0,1.0));
+        self.move_panel(state, 0usize, self.lastpos);
         EventLoopAction::None
     }
 
     fn render<'a>(&self, renderer: &mut TextureRenderer, state: &'a mut State, render_pass: &mut RenderPass<'a>) {
         // render all panels
-        for obj in &self.panel_renderobjs {
-            obj.render_this(state, render_pass);
-        }
+        renderer.render_queue.submit_all(&self.panel_renderobjs);
+        renderer.render_queue.flush(state, render_pass);
     }
 }
\ No newline at end of file
@@ -0,0 +1,423 @@
+use std::collections::HashMap;
+use anyhow::*;
+use image::RgbaImage;
+use crate::sampler::SamplerOptions;
+use crate::wgpustate::State;
+
+/// Packs rectangles into rows ("shelves") of similar height.
+pub struct ShelfPacker {
+    width: u32,
+    height: u32,
+    /// (y, height, next free x) of every shelf.
+    shelves: Vec<(u32, u32, u32)>,
+    next_y: u32,
+}
+
+impl ShelfPacker {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self { width, height, shelves: vec![], next_y: 0 }
+    }
+
+    /// Finds space for a rectangle and returns it's top-left corner, or `None` if it doesn't fit.
+    pub fn allocate(&mut self, w: u32, h: u32) -> Option<(u32, u32)> {
+        if w > self.width {
+            return None;
+        }
+        // the lowest shelf that fits wastes the least space
+        let best = self.shelves.iter_mut()
+            .filter(|s| h <= s.1 && s.2 + w <= self.width)
+            .min_by_key(|s| s.1);
+        if let Some(shelf) = best {
+            let x = shelf.2;
+            shelf.2 += w;
+            return Some((x, shelf.0));
+        }
+        if self.next_y + h > self.height {
+            return None;
+        }
+        let y = self.next_y;
+        self.shelves.push((y, h, w));
+        self.next_y += h;
+        Some((0, y))
+    }
+
+    /// Existing allocations keep their positions.
+    pub fn grow(&mut self, width: u32, height: u32) {
+        self.width = width;
+        self.height = height;
+    }
+
+    pub fn clear(&mut self) {
+        self.shelves.clear();
+        self.next_y = 0;
+    }
+}
+
+/// Where a sprite was placed in a `TextureAtlas`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AtlasRegion {
+    /// The atlas page (texture) holding the sprite.
+    pub page: usize,
+    /// The sprite's pixels in the page, `[x, y, width, height]`.
+    pub pixels: [u32; 4],
+    /// Normalized texture coordinates of the top-left corner.
+    pub uv_min: [f32; 2],
+    /// Normalized texture coordinates of the bottom-right corner.
+    pub uv_max: [f32; 2],
+}
+
+struct AtlasPage {
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    packer: ShelfPacker,
+    /// Index into `State::bind_groups`.
+    bind_group: usize,
+}
+
+/// Many small images packed into a few large textures, so that sprites on the same page share a bind group.
+/// * The pages' bind groups live in `State::bind_groups` and use the `default:pipe:textured` layout,
+///   so they can be used by a `RenderObject` or a `SpriteBuilder`.
+/// * Images are kept in memory, so the atlas can be repacked when a new image doesn't fit.
+///   Regions may move when that happens; `generation` changes whenever they do.
+pub struct TextureAtlas {
+    page_size: u32,
+    /// Empty pixels left around every sprite, so filtering doesn't bleed between neighbours.
+    pub padding: u32,
+    pub filter: wgpu::FilterMode,
+    pages: Vec<AtlasPage>,
+    images: Vec<RgbaImage>,
+    regions: Vec<AtlasRegion>,
+    pub sprite_map: HashMap<String, usize>,
+    pub generation: u64,
+    /// Bind groups of pages dropped by a repack, reused before new ones are added to `State::bind_groups`.
+    spare_bind_groups: Vec<usize>,
+}
+
+impl TextureAtlas {
+    /// Creates an empty atlas whose pages are `page_size` pixels square.
+    pub fn new(page_size: u32) -> Self {
+        Self {
+            page_size,
+            padding: 2,
+            filter: wgpu::FilterMode::Linear,
+            pages: vec![],
+            images: vec![],
+            regions: vec![],
+            sprite_map: HashMap::new(),
+            generation: 0,
+            spare_bind_groups: vec![],
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.images.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.images.is_empty()
+    }
+
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    /// The sprite id registered under a name.
+    pub fn id(&self, name: &str) -> Option<usize> {
+        self.sprite_map.get(name).copied()
+    }
+
+    pub fn region(&self, sprite: usize) -> &AtlasRegion {
+        &self.regions[sprite]
+    }
+
+    /// The index in `State::bind_groups` of the page's bind group.
+    /// * A repack keeps the indices of the pages it keeps, but sprites may move to another page,
+    ///   so read it again from the sprite's region when `generation` changes.
+    pub fn page_bind_group(&self, page: usize) -> usize {
+        self.pages[page].bind_group
+    }
+
+    /// Decode an image and add it, see `TextureAtlas::add`.
+    pub fn add_bytes(&mut self, state: &mut State, name: &str, bytes: &[u8]) -> Result<usize> {
+        let img = image::load_from_memory(bytes)?;
+        self.add(state, name, img.to_rgba8())
+    }
+
+    /// Add an image and return it's sprite id. An empty `name` isn't registered.
+    /// * Repacks every image when there is no room left, adding pages as needed.
+    pub fn add(&mut self, state: &mut State, name: &str, image: RgbaImage) -> Result<usize> {
+        let (w, h) = image.dimensions();
+        self.check_fits(w, h)?;
+
+        let id = self.images.len();
+        self.images.push(image);
+        self.regions.push(AtlasRegion { page: 0, pixels: [0; 4], uv_min: [0.0; 2], uv_max: [0.0; 2] });
+        if !name.is_empty() {
+            self.sprite_map.insert(name.to_string(), id);
+        }
+
+        let slot = self.pages.iter_mut().enumerate()
+            .find_map(|(i, p)| p.packer.allocate(w + self.padding, h + self.padding).map(|pos| (i, pos)));
+        match slot {
+            Some((page, pos)) => {
+                self.place(id, page, pos);
+                self.upload(state, id);
+            }
+            None => self.repack(state),
+        }
+        Ok(id)
+    }
+
+    /// Fails if a `w`x`h` image and it's padding are larger than a page.
+    fn check_fits(&self, w: u32, h: u32) -> Result<()> {
+        if w + self.padding > self.page_size || h + self.padding > self.page_size {
+            bail!("{}x{} image does not fit in a {}px atlas page", w, h, self.page_size);
+        }
+        Ok(())
+    }
+
+    /// The region of a `w`x`h` image in the padded space the packer allocated at `pos`.
+    fn region_at(&self, page: usize, pos: (u32, u32), (w, h): (u32, u32)) -> AtlasRegion {
+        // half of the padding on each side
+        let (x, y) = (pos.0 + self.padding / 2, pos.1 + self.padding / 2);
+        let size = self.page_size as f32;
+        AtlasRegion {
+            page,
+            pixels: [x, y, w, h],
+            uv_min: [x as f32 / size, y as f32 / size],
+            uv_max: [(x + w) as f32 / size, (y + h) as f32 / size],
+        }
+    }
+
+    fn place(&mut self, id: usize, page: usize, pos: (u32, u32)) {
+        self.regions[id] = self.region_at(page, pos, self.images[id].dimensions());
+    }
+
+    fn upload(&self, state: &State, id: usize) {
+        let region = &self.regions[id];
+        let image = &self.images[id];
+        if region.pixels[2] == 0 || region.pixels[3] == 0 {
+            return;
+        }
+        state.queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &self.pages[region.page].texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d { x: region.pixels[0], y: region.pixels[1], z: 0 },
+                aspect: wgpu::TextureAspect::All,
+            },
+            image.as_raw(),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: std::num::NonZeroU32::new(4 * region.pixels[2]),
+                rows_per_image: std::num::NonZeroU32::new(region.pixels[3]),
+            },
+            wgpu::Extent3d { width: region.pixels[2], height: region.pixels[3], depth_or_array_layers: 1 },
+        );
+    }
+
+    fn create_page(&self, state: &mut State, reuse_bind_group: Option<usize>) -> AtlasPage {
+        let texture = state.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("panels:atlas"),
+            size: wgpu::Extent3d { width: self.page_size, height: self.page_size, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = self.create_bind_group(state, &view);
+
+        // keep the same bind group index, so objects using the page see the new texture
+        let bind_group = match reuse_bind_group {
+            Some(i) => {
+                state.bind_groups[i] = bind_group;
+                i
+            }
+            None => {
+                state.bind_groups.push(bind_group);
+                state.bind_groups.len() - 1
+            }
+        };
+
+        AtlasPage {
+            texture,
+            view,
+            packer: ShelfPacker::new(self.page_size, self.page_size),
+            bind_group,
+        }
+    }
+
+    fn create_bind_group(&self, state: &mut State, view: &wgpu::TextureView) -> wgpu::BindGroup {
+        let sampler = state.sampler(&SamplerOptions {
+            mag_filter: self.filter,
+            min_filter: self.filter,
+            ..SamplerOptions::default()
+        });
+
+        let layout = &state.bindgroup_layouts[state.get_pipeline("default:pipe:textured").bindgrouplayout];
+        state.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("panels:atlas"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+        })
+    }
+
+    /// Packs every image again from scratch, tallest first, into fresh page textures.
+    pub fn repack(&mut self, state: &mut State) {
+        let mut order: Vec<usize> = (0..self.images.len()).collect();
+        order.sort_by_key(|i| {
+            let (w, h) = self.images[*i].dimensions();
+            (std::cmp::Reverse(h), std::cmp::Reverse(w))
+        });
+
+        // pages keep their bind group indices in order, then spares left by earlier repacks are used up
+        let mut free_bind_groups: Vec<usize> = self.pages.iter().map(|p| p.bind_group)
+            .chain(self.spare_bind_groups.drain(..))
+            .rev()
+            .collect();
+        self.pages.clear();
+
+        for id in order {
+            let (w, h) = self.images[id].dimensions();
+            let (w, h) = (w + self.padding, h + self.padding);
+            let mut slot = self.pages.iter_mut().enumerate()
+                .find_map(|(i, p)| p.packer.allocate(w, h).map(|pos| (i, pos)));
+            if slot.is_none() {
+                let page = self.create_page(state, free_bind_groups.pop());
+                self.pages.push(page);
+                let last = self.pages.len() - 1;
+                slot = self.pages[last].packer.allocate(w, h).map(|pos| (last, pos));
+            }
+            // `add` checked that every image fits in an empty page
+            let (page, pos) = slot.unwrap();
+            self.place(id, page, pos);
+            self.upload(state, id);
+        }
+
+        // bind groups of dropped pages show the first page instead of a texture nothing updates
+        if let Some(first) = self.pages.first() {
+            for &i in &free_bind_groups {
+                state.bind_groups[i] = self.create_bind_group(state, &first.view);
+            }
+        }
+        self.spare_bind_groups = free_bind_groups.into_iter().rev().collect();
+
+        self.generation += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    /// `[x, y, width, height]` rectangles overlap.
+    fn overlap(a: [u32; 4], b: [u32; 4]) -> bool {
+        a[0] < b[0] + b[2] && b[0] < a[0] + a[2] && a[1] < b[1] + b[3] && b[1] < a[1] + a[3]
+    }
+
+    proptest! {
+        #[test]
+        fn packed_rects_stay_apart(sizes in prop::collection::vec((1u32..80, 1u32..80), 1..60)) {
+            let mut packer = ShelfPacker::new(256, 256);
+            let mut placed: Vec<[u32; 4]> = vec![];
+            for (w, h) in sizes {
+                if let Some((x, y)) = packer.allocate(w, h) {
+                    let rect = [x, y, w, h];
+                    prop_assert!(x + w <= 256 && y + h <= 256, "{:?} is outside the page", rect);
+                    for other in &placed {
+                        prop_assert!(!overlap(rect, *other), "{:?} overlaps {:?}", rect, other);
+                    }
+                    placed.push(rect);
+                }
+            }
+        }
+
+        #[test]
+        fn padding_separates_sprites(sizes in prop::collection::vec((1u32..40, 1u32..40), 1..40), padding in 0u32..5) {
+            let mut atlas = TextureAtlas::new(256);
+            atlas.padding = padding;
+            let mut packer = ShelfPacker::new(256, 256);
+            let mut regions: Vec<AtlasRegion> = vec![];
+            for (w, h) in sizes {
+                if let Some(pos) = packer.allocate(w + padding, h + padding) {
+                    regions.push(atlas.region_at(0, pos, (w, h)));
+                }
+            }
+            // growing every sprite by the padding, split between it's two sides, still doesn't overlap
+            let grown = |r: &AtlasRegion| {
+                let [x, y, w, h] = r.pixels;
+                [x - padding / 2, y - padding / 2, w + padding, h + padding]
+            };
+            for (i, a) in regions.iter().enumerate() {
+                let [x, y, w, h] = grown(a);
+                prop_assert!(x + w <= 256 && y + h <= 256, "{:?} is outside the page", a.pixels);
+                for b in &regions[i + 1..] {
+                    prop_assert!(!overlap(grown(a), grown(b)), "{:?} is too close to {:?}", a.pixels, b.pixels);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn shelves_are_reused_by_shorter_rects() {
+        let mut packer = ShelfPacker::new(100, 100);
+        assert_eq!(packer.allocate(60, 30), Some((0, 0)));
+        assert_eq!(packer.allocate(60, 20), Some((0, 30)));
+        // the 20px shelf wastes less space than the 30px one
+        assert_eq!(packer.allocate(30, 15), Some((60, 30)));
+        assert_eq!(packer.allocate(30, 25), Some((60, 0)));
+        // no shelf has room left, so a new one starts below
+        assert_eq!(packer.allocate(20, 10), Some((0, 50)));
+    }
+
+    #[test]
+    fn full_pages_have_no_space() {
+        let mut packer = ShelfPacker::new(64, 64);
+        assert_eq!(packer.allocate(65, 1), None);
+        assert_eq!(packer.allocate(1, 65), None);
+        for i in 0..16 {
+            assert!(packer.allocate(16, 16).is_some(), "cell {} doesn't fit", i);
+        }
+        assert_eq!(packer.allocate(16, 16), None);
+        assert_eq!(packer.allocate(1, 1), None);
+
+        packer.grow(64, 80);
+        assert_eq!(packer.allocate(64, 16), Some((0, 64)));
+        packer.clear();
+        assert_eq!(packer.allocate(64, 64), Some((0, 0)));
+    }
+
+    #[test]
+    fn oversized_images_are_rejected() {
+        let mut atlas = TextureAtlas::new(64);
+        atlas.padding = 2;
+        assert!(atlas.check_fits(62, 62).is_ok());
+        assert!(atlas.check_fits(63, 10).is_err());
+        assert!(atlas.check_fits(10, 63).is_err());
+        atlas.padding = 0;
+        assert!(atlas.check_fits(64, 64).is_ok());
+    }
+
+    #[test]
+    fn regions_are_inside_their_padding() {
+        let mut atlas = TextureAtlas::new(128);
+        atlas.padding = 4;
+        let region = atlas.region_at(1, (32, 64), (16, 8));
+        assert_eq!(region.page, 1);
+        assert_eq!(region.pixels, [34, 66, 16, 8]);
+        assert_eq!(region.uv_min, [34.0 / 128.0, 66.0 / 128.0]);
+        assert_eq!(region.uv_max, [50.0 / 128.0, 74.0 / 128.0]);
+    }
+}
@@ -1,19 +1,284 @@
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, Point3, SquareMatrix, Vector3, Vector4};
 use crate::resourcebytes::OPENGL_TO_WGPU_MATRIX;
 
-struct Camera {
-    eye: cgmath::Point3<f32>,
-    target: cgmath::Point3<f32>,
-    up: cgmath::Vector3<f32>,
-    aspect: f32,
-    fovy: f32,
-    znear: f32,
-    zfar: f32,
+/// How a `Camera` maps view space onto the render target.
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub enum Projection {
+    /// No projection or view transform, world coordinates are clip-space coordinates (-1..1 on both axes).
+    Ndc,
+
+    /// One world unit is one pixel of the viewport, with `(0,0)` at the top-left and Y pointing down.
+    /// * The camera's `eye` X/Y pans the view.
+    OrthographicPixels,
+
+    /// `height` world units are visible vertically, the visible width follows the aspect ratio.
+    Orthographic { height: f32, znear: f32, zfar: f32 },
+
+    /// A perspective projection with a vertical field of view in degrees.
+    Perspective { fovy: f32, znear: f32, zfar: f32 },
+}
+
+/// A camera that produces the view-projection matrix for a renderer.
+#[derive(Debug,Copy,Clone)]
+pub struct Camera {
+    pub eye: Point3<f32>,
+    pub target: Point3<f32>,
+    pub up: Vector3<f32>,
+    pub projection: Projection,
+    /// Viewport size in physical pixels.
+    pub viewport: [f32; 2],
+    /// When set, `State::resize` updates the viewport (and so the aspect ratio) to the surface size.
+    /// * A camera used by a renderer that draws into a texture should clear this,
+    ///   and be given the texture's size with `set_viewport` instead.
+    pub fit_surface: bool,
 }
 
 impl Camera {
-    fn build_view_proj_matrix(&self) -> cgmath::Matrix4<f32> {
-        let view = cgmath::Matrix4::look_at_rh(self.eye, self.target, self.up);
-        let proj = cgmath::perspective(cgmath::Deg(self.fovy), self.aspect, self.znear, self.zfar);
-        return OPENGL_TO_WGPU_MATRIX * proj * view;
+    fn with_projection(projection: Projection, eye: Point3<f32>, target: Point3<f32>) -> Self {
+        Self {
+            eye,
+            target,
+            up: Vector3::unit_y(),
+            projection,
+            viewport: [1.0, 1.0],
+            fit_surface: true,
+        }
+    }
+
+    /// The camera used by default, leaving world coordinates untouched.
+    pub fn ndc() -> Self {
+        Self::with_projection(Projection::Ndc, Point3::new(0.0, 0.0, 1.0), Point3::origin())
+    }
+
+    pub fn orthographic_pixels() -> Self {
+        Self::with_projection(Projection::OrthographicPixels, Point3::new(0.0, 0.0, 1.0), Point3::origin())
+    }
+
+    /// A 2D camera looking down -Z at the origin, showing `height` world units vertically.
+    pub fn orthographic(height: f32) -> Self {
+        Self::with_projection(
+            Projection::Orthographic { height, znear: -1000.0, zfar: 1000.0 },
+            Point3::new(0.0, 0.0, 1.0),
+            Point3::origin(),
+        )
+    }
+
+    pub fn perspective(eye: Point3<f32>, target: Point3<f32>, fovy: f32) -> Self {
+        Self::with_projection(Projection::Perspective { fovy, znear: 0.1, zfar: 1000.0 }, eye, target)
+    }
+
+    pub fn aspect(&self) -> f32 {
+        self.viewport[0] / self.viewport[1]
+    }
+
+    pub fn set_viewport(&mut self, width: u32, height: u32) {
+        self.viewport = [width.max(1) as f32, height.max(1) as f32];
+    }
+
+    /// Moves both the eye and the target, keeping the viewing direction.
+    pub fn pan(&mut self, dx: f32, dy: f32, dz: f32) {
+        let d = Vector3::new(dx, dy, dz);
+        self.eye += d;
+        self.target += d;
+    }
+
+    pub fn build_view_matrix(&self) -> Matrix4<f32> {
+        match self.projection {
+            Projection::Ndc => Matrix4::identity(),
+            Projection::OrthographicPixels => Matrix4::from_translation(Vector3::new(-self.eye.x, -self.eye.y, 0.0)),
+            _ => Matrix4::look_at_rh(self.eye, self.target, self.up),
+        }
+    }
+
+    pub fn build_projection_matrix(&self) -> Matrix4<f32> {
+        match self.projection {
+            Projection::Ndc => Matrix4::identity(),
+            Projection::OrthographicPixels => {
+                OPENGL_TO_WGPU_MATRIX * cgmath::ortho(0.0, self.viewport[0], self.viewport[1], 0.0, -1.0, 1.0)
+            }
+            Projection::Orthographic { height, znear, zfar } => {
+                let half_h = height / 2.0;
+                let half_w = half_h * self.aspect();
+                OPENGL_TO_WGPU_MATRIX * cgmath::ortho(-half_w, half_w, -half_h, half_h, znear, zfar)
+            }
+            Projection::Perspective { fovy, znear, zfar } => {
+                OPENGL_TO_WGPU_MATRIX * cgmath::perspective(cgmath::Deg(fovy), self.aspect(), znear, zfar)
+            }
+        }
+    }
+
+    pub fn build_view_proj_matrix(&self) -> Matrix4<f32> {
+        self.build_projection_matrix() * self.build_view_matrix()
+    }
+
+    /// Maps a clip-space point (X/Y in -1..1, Z in 0..1) back into world space.
+    pub fn unproject(&self, ndc: [f32; 2], depth: f32) -> Option<Point3<f32>> {
+        let inv = self.build_view_proj_matrix().invert()?;
+        let p = inv * Vector4::new(ndc[0], ndc[1], depth, 1.0);
+        if p.w.abs() < f32::EPSILON {
+            return None;
+        }
+        Some(Point3::from_vec(p.truncate() / p.w))
+    }
+
+    /// The world-space ray through a clip-space point, as an origin and a normalized direction.
+    pub fn ray(&self, ndc: [f32; 2]) -> Option<(Point3<f32>, Vector3<f32>)> {
+        let near = self.unproject(ndc, 0.0)?;
+        let far = self.unproject(ndc, 1.0)?;
+        Some((near, (far - near).normalize()))
+    }
+
+    pub fn to_uniform(&self) -> CameraUniform {
+        CameraUniform {
+            view_proj: self.build_view_proj_matrix().into()
+        }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPS: f32 = 1e-3;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() <= EPS * (1.0 + a.abs().max(b.abs()))
+    }
+
+    /// The clip-space position of a world point, after the perspective divide.
+    fn project(camera: &Camera, p: Point3<f32>) -> [f32; 3] {
+        let clip = camera.build_view_proj_matrix() * p.to_homogeneous();
+        [clip.x / clip.w, clip.y / clip.w, clip.z / clip.w]
+    }
+
+    #[test]
+    fn pixel_corners_map_to_ndc() {
+        let mut camera = Camera::orthographic_pixels();
+        camera.set_viewport(800, 600);
+        let maps_to = |camera: &Camera, x: f32, y: f32, ndc: [f32; 2]| {
+            let p = project(camera, Point3::new(x, y, 0.0));
+            assert!(close(p[0], ndc[0]) && close(p[1], ndc[1]), "({}, {}) -> {:?}", x, y, p);
+        };
+        // Y points down in pixels and up in NDC
+        maps_to(&camera, 0.0, 0.0, [-1.0, 1.0]);
+        maps_to(&camera, 800.0, 600.0, [1.0, -1.0]);
+        maps_to(&camera, 800.0, 0.0, [1.0, 1.0]);
+        maps_to(&camera, 400.0, 300.0, [0.0, 0.0]);
+
+        // the eye pans the view
+        camera.pan(100.0, 50.0, 0.0);
+        maps_to(&camera, 100.0, 50.0, [-1.0, 1.0]);
+    }
+
+    #[test]
+    fn aspect_follows_the_viewport() {
+        let mut camera = Camera::perspective(Point3::new(0.0, 0.0, 5.0), Point3::origin(), 45.0);
+        for (w, h) in [(800, 600), (1600, 800), (300, 900)] {
+            camera.set_viewport(w, h);
+            let aspect = w as f32 / h as f32;
+            assert_eq!(camera.aspect(), aspect);
+            let m = camera.build_projection_matrix();
+            assert!(close(m.x.x * aspect, m.y.y), "{}x{}", w, h);
+        }
+
+        // an orthographic camera shows `height` units, and a width to match
+        let mut camera = Camera::orthographic(10.0);
+        camera.set_viewport(1600, 800);
+        let right = project(&camera, Point3::new(10.0, 5.0, 0.0));
+        assert!(close(right[0], 1.0) && close(right[1], 1.0), "{:?}", right);
+        // a zero sized viewport doesn't divide by zero
+        camera.set_viewport(0, 0);
+        assert_eq!(camera.aspect(), 1.0);
+    }
+
+    #[test]
+    fn unproject_round_trips() {
+        let cameras = [
+            Camera::perspective(Point3::new(2.0, 3.0, 6.0), Point3::new(0.0, 1.0, 0.0), 60.0),
+            Camera::orthographic(8.0),
+            Camera::orthographic_pixels(),
+            Camera::ndc(),
+        ];
+        let points = [Point3::new(0.0, 0.0, 0.0), Point3::new(0.5, -0.25, 0.1), Point3::new(-0.8, 0.6, -0.3)];
+        for mut camera in cameras {
+            camera.set_viewport(640, 480);
+            for p in points {
+                let [x, y, z] = project(&camera, p);
+                let back = camera.unproject([x, y], z).unwrap();
+                assert!(close(back.x, p.x) && close(back.y, p.y) && close(back.z, p.z), "{:?}: {:?} -> {:?}", camera.projection, p, back);
+            }
+        }
+    }
+
+    #[test]
+    fn rays_go_through_the_target() {
+        let camera = Camera::perspective(Point3::new(0.0, 0.0, 5.0), Point3::origin(), 45.0);
+        let (origin, dir) = camera.ray([0.0, 0.0]).unwrap();
+        assert!(close(origin.x, 0.0) && close(origin.y, 0.0) && origin.z < 5.0);
+        assert!(close(dir.z, -1.0), "{:?}", dir);
+    }
+}
+
+/// The camera data bound at `group(2), binding(0)` by the default pipelines.
+#[repr(C)]
+#[derive(Debug,Copy,Clone,bytemuck::Pod,bytemuck::Zeroable)]
+pub struct CameraUniform {
+    pub view_proj: [[f32; 4]; 4],
+}
+
+/// A `Camera` stored in the `State`, along with it's GPU resources.
+pub struct CameraBinding {
+    pub camera: Camera,
+    pub buffer: wgpu::Buffer,
+    pub bind_group: wgpu::BindGroup,
+}
+
+impl CameraBinding {
+    pub fn create_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("panels:camera"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<CameraUniform>() as wgpu::BufferAddress),
+                    },
+                    count: None,
+                },
+            ],
+        })
+    }
+
+    pub fn new(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, camera: Camera) -> Self {
+        use wgpu::util::DeviceExt;
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("panels:camera"),
+            contents: bytemuck::cast_slice(&[camera.to_uniform()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("panels:camera"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        Self {
+            camera,
+            buffer,
+            bind_group,
+        }
+    }
+
+    pub fn upload(&self, queue: &wgpu::Queue) {
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[self.camera.to_uniform()]));
+    }
+}
@@ -0,0 +1,590 @@
+use std::time::Instant;
+use cgmath::{InnerSpace, Point3, Vector3};
+use winit::event::{ElementState, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent};
+use crate::camera::{Camera, Projection};
+
+/// Reusable input handling for a `Camera`.
+/// * Call `input` from `ProgramHook::input` and `update` from `ProgramHook::update`, passing `state.camera_mut(renderer.camera)`.
+pub trait CameraController {
+    /// Feed a `WindowEvent` to the controller, returns `true` if the event was used.
+    fn input(&mut self, camera: &Camera, event: &WindowEvent) -> bool;
+
+    /// Move the camera towards the controller's goal, `dt` is the time since the last update in seconds.
+    fn update(&mut self, camera: &mut Camera, dt: f32);
+
+    /// Like `update`, measuring `dt` with the given clock.
+    fn update_now(&mut self, camera: &mut Camera, clock: &mut ControllerClock) {
+        let dt = clock.tick();
+        self.update(camera, dt);
+    }
+}
+
+/// Measures the time between updates for a `CameraController`.
+pub struct ControllerClock {
+    last: Option<Instant>,
+}
+
+impl ControllerClock {
+    /// The longest step a single update will take, so a stalled frame doesn't teleport the camera.
+    const MAX_DT: f32 = 0.1;
+
+    pub fn new() -> Self {
+        Self {
+            last: None
+        }
+    }
+
+    /// Seconds since the last tick, `0.0` on the first call.
+    pub fn tick(&mut self) -> f32 {
+        let now = Instant::now();
+        let dt = match self.last {
+            Some(last) => (now - last).as_secs_f32().min(Self::MAX_DT),
+            None => 0.0,
+        };
+        self.last = Some(now);
+        dt
+    }
+}
+
+impl Default for ControllerClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Exponential smoothing factor for a step of `dt` seconds, `sharpness` of `0.0` disables smoothing.
+fn smoothing(sharpness: f32, dt: f32) -> f32 {
+    if sharpness <= 0.0 {
+        1.0
+    } else {
+        1.0 - (-sharpness * dt).exp()
+    }
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+fn scroll_lines(delta: &MouseScrollDelta) -> f32 {
+    match delta {
+        MouseScrollDelta::LineDelta(_, y) => *y,
+        // roughly one line per 20 pixels of touchpad scrolling
+        MouseScrollDelta::PixelDelta(p) => p.y as f32 / 20.0,
+    }
+}
+
+/// The world units covered by one viewport pixel, for the 2D projections.
+fn world_per_pixel(camera: &Camera, height: f32) -> f32 {
+    match camera.projection {
+        Projection::Orthographic { .. } => height / camera.viewport[1],
+        Projection::Ndc => 2.0 / camera.viewport[1],
+        _ => 1.0,
+    }
+}
+
+/// 2D panning and zoom-to-cursor for `Orthographic` and `OrthographicPixels` cameras.
+/// * Dragging with `pan_button` pans, the mouse wheel zooms around the cursor, arrow keys pan.
+/// * Zoom only applies to `Projection::Orthographic`, as it changes the visible `height`.
+pub struct PanZoomController {
+    pub pan_button: MouseButton,
+    /// Zoom multiplier per wheel line.
+    pub zoom_step: f32,
+    /// Smallest and largest visible height, in world units.
+    pub zoom_limits: (f32, f32),
+    /// Optional min/max corners the view center is clamped to.
+    pub bounds: Option<([f32; 2], [f32; 2])>,
+    /// Arrow key panning speed, in viewport heights per second.
+    pub key_speed: f32,
+    /// How quickly the camera catches up to it's goal, higher is snappier.
+    pub sharpness: f32,
+
+    center: [f32; 2],
+    height: f32,
+    goal_center: [f32; 2],
+    goal_height: f32,
+    dragging: bool,
+    cursor: Option<[f32; 2]>,
+    keys: [bool; 4],
+}
+
+impl PanZoomController {
+    pub fn new(camera: &Camera) -> Self {
+        let height = match camera.projection {
+            Projection::Orthographic { height, .. } => height,
+            _ => camera.viewport[1],
+        };
+        let center = [camera.eye.x, camera.eye.y];
+        Self {
+            pan_button: MouseButton::Left,
+            zoom_step: 1.1,
+            zoom_limits: (0.01, 10000.0),
+            bounds: None,
+            key_speed: 0.5,
+            sharpness: 20.0,
+            center,
+            height,
+            goal_center: center,
+            goal_height: height,
+            dragging: false,
+            cursor: None,
+            keys: [false; 4],
+        }
+    }
+
+    pub fn with_bounds(mut self, min: [f32; 2], max: [f32; 2]) -> Self {
+        self.bounds = Some((min, max));
+        self
+    }
+
+    /// The world-space center the controller is moving towards.
+    pub fn goal_center(&self) -> [f32; 2] {
+        self.goal_center
+    }
+
+    fn clamp_goal(&mut self) {
+        self.goal_height = self.goal_height.clamp(self.zoom_limits.0, self.zoom_limits.1);
+        if let Some((min, max)) = self.bounds {
+            for i in 0..2 {
+                self.goal_center[i] = self.goal_center[i].clamp(min[i], max[i]);
+            }
+        }
+    }
+
+    /// Y points up in world space but down in pixel space, except for the pixel camera.
+    fn y_sign(camera: &Camera) -> f32 {
+        match camera.projection {
+            Projection::OrthographicPixels => 1.0,
+            _ => -1.0,
+        }
+    }
+
+    fn zoom_at_cursor(&mut self, camera: &Camera, factor: f32) {
+        if let Projection::Orthographic { .. } = camera.projection {
+            let new_height = (self.goal_height * factor).clamp(self.zoom_limits.0, self.zoom_limits.1);
+            if let Some([cx, cy]) = self.cursor {
+                // keep the world point under the cursor fixed while the visible extents change
+                let offset_x = cx - camera.viewport[0] / 2.0;
+                let offset_y = (cy - camera.viewport[1] / 2.0) * Self::y_sign(camera);
+                let before = world_per_pixel(camera, self.goal_height);
+                let after = world_per_pixel(camera, new_height);
+                self.goal_center[0] += offset_x * (before - after);
+                self.goal_center[1] += offset_y * (before - after);
+            }
+            self.goal_height = new_height;
+            self.clamp_goal();
+        }
+    }
+}
+
+impl CameraController for PanZoomController {
+    fn input(&mut self, camera: &Camera, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::MouseInput { state, button, .. } if *button == self.pan_button => {
+                self.dragging = *state == ElementState::Pressed;
+                true
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                let pos = [position.x as f32, position.y as f32];
+                if let (true, Some(last)) = (self.dragging, self.cursor) {
+                    let wpp = world_per_pixel(camera, self.goal_height);
+                    self.goal_center[0] -= (pos[0] - last[0]) * wpp;
+                    self.goal_center[1] -= (pos[1] - last[1]) * wpp * Self::y_sign(camera);
+                    self.clamp_goal();
+                }
+                self.cursor = Some(pos);
+                self.dragging
+            }
+            WindowEvent::CursorLeft { .. } => {
+                self.cursor = None;
+                self.dragging = false;
+                false
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                let lines = scroll_lines(delta);
+                self.zoom_at_cursor(camera, self.zoom_step.powf(-lines));
+                true
+            }
+            WindowEvent::KeyboardInput { input, .. } => {
+                let pressed = input.state == ElementState::Pressed;
+                let key = match input.virtual_keycode {
+                    Some(VirtualKeyCode::Left) => 0,
+                    Some(VirtualKeyCode::Right) => 1,
+                    Some(VirtualKeyCode::Up) => 2,
+                    Some(VirtualKeyCode::Down) => 3,
+                    _ => return false,
+                };
+                self.keys[key] = pressed;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn update(&mut self, camera: &mut Camera, dt: f32) {
+        let key_step = self.key_speed * self.goal_height * dt;
+        let dx = (self.keys[1] as i32 - self.keys[0] as i32) as f32;
+        let dy = (self.keys[2] as i32 - self.keys[3] as i32) as f32;
+        if dx != 0.0 || dy != 0.0 {
+            self.goal_center[0] += dx * key_step;
+            self.goal_center[1] -= dy * key_step * Self::y_sign(camera);
+            self.clamp_goal();
+        }
+
+        let t = smoothing(self.sharpness, dt);
+        self.center[0] = lerp(self.center[0], self.goal_center[0], t);
+        self.center[1] = lerp(self.center[1], self.goal_center[1], t);
+        self.height = lerp(self.height, self.goal_height, t);
+
+        camera.eye.x = self.center[0];
+        camera.eye.y = self.center[1];
+        camera.target.x = self.center[0];
+        camera.target.y = self.center[1];
+        if let Projection::Orthographic { height, .. } = &mut camera.projection {
+            *height = self.height;
+        }
+    }
+}
+
+/// Orbits a 3D camera around a target point.
+/// * Dragging with `orbit_button` rotates, dragging with `pan_button` moves the target, the wheel changes the distance.
+pub struct OrbitController {
+    pub orbit_button: MouseButton,
+    pub pan_button: MouseButton,
+    /// Radians per pixel dragged.
+    pub rotate_speed: f32,
+    /// Distance multiplier per wheel line.
+    pub zoom_step: f32,
+    pub distance_limits: (f32, f32),
+    pub sharpness: f32,
+
+    target: Point3<f32>,
+    yaw: f32,
+    pitch: f32,
+    distance: f32,
+    goal_target: Point3<f32>,
+    goal_yaw: f32,
+    goal_pitch: f32,
+    goal_distance: f32,
+    orbiting: bool,
+    panning: bool,
+    cursor: Option<[f32; 2]>,
+}
+
+impl OrbitController {
+    const PITCH_LIMIT: f32 = std::f32::consts::FRAC_PI_2 - 0.01;
+
+    pub fn new(camera: &Camera) -> Self {
+        let offset = camera.eye - camera.target;
+        let distance = offset.magnitude().max(0.001);
+        let yaw = offset.x.atan2(offset.z);
+        let pitch = (offset.y / distance).asin();
+        Self {
+            orbit_button: MouseButton::Left,
+            pan_button: MouseButton::Right,
+            rotate_speed: 0.01,
+            zoom_step: 1.1,
+            distance_limits: (0.1, 10000.0),
+            sharpness: 15.0,
+            target: camera.target,
+            yaw,
+            pitch,
+            distance,
+            goal_target: camera.target,
+            goal_yaw: yaw,
+            goal_pitch: pitch,
+            goal_distance: distance,
+            orbiting: false,
+            panning: false,
+            cursor: None,
+        }
+    }
+
+    fn offset(yaw: f32, pitch: f32, distance: f32) -> Vector3<f32> {
+        Vector3::new(yaw.sin() * pitch.cos(), pitch.sin(), yaw.cos() * pitch.cos()) * distance
+    }
+}
+
+impl CameraController for OrbitController {
+    fn input(&mut self, camera: &Camera, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::MouseInput { state, button, .. } => {
+                let pressed = *state == ElementState::Pressed;
+                if *button == self.orbit_button {
+                    self.orbiting = pressed;
+                } else if *button == self.pan_button {
+                    self.panning = pressed;
+                } else {
+                    return false;
+                }
+                true
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                let pos = [position.x as f32, position.y as f32];
+                if let Some(last) = self.cursor {
+                    let (dx, dy) = (pos[0] - last[0], pos[1] - last[1]);
+                    if self.orbiting {
+                        self.goal_yaw -= dx * self.rotate_speed;
+                        self.goal_pitch = (self.goal_pitch + dy * self.rotate_speed).clamp(-Self::PITCH_LIMIT, Self::PITCH_LIMIT);
+                    } else if self.panning {
+                        // move the target in the view plane, scaled so the point under the cursor follows it
+                        let forward = -Self::offset(self.goal_yaw, self.goal_pitch, 1.0);
+                        let right = forward.cross(camera.up).normalize();
+                        let up = right.cross(forward).normalize();
+                        let scale = 2.0 * self.goal_distance / camera.viewport[1];
+                        self.goal_target += (-right * dx + up * dy) * scale;
+                    }
+                }
+                self.cursor = Some(pos);
+                self.orbiting || self.panning
+            }
+            WindowEvent::CursorLeft { .. } => {
+                self.cursor = None;
+                self.orbiting = false;
+                self.panning = false;
+                false
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                self.goal_distance = (self.goal_distance * self.zoom_step.powf(-scroll_lines(delta))).clamp(self.distance_limits.0, self.distance_limits.1);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn update(&mut self, camera: &mut Camera, dt: f32) {
+        let t = smoothing(self.sharpness, dt);
+        self.yaw = lerp(self.yaw, self.goal_yaw, t);
+        self.pitch = lerp(self.pitch, self.goal_pitch, t);
+        self.distance = lerp(self.distance, self.goal_distance, t);
+        self.target += (self.goal_target - self.target) * t;
+
+        camera.target = self.target;
+        camera.eye = self.target + Self::offset(self.yaw, self.pitch, self.distance);
+    }
+}
+
+/// Free-flying 3D camera.
+/// * WASD moves, Q/E or Space/LShift move down/up, dragging with `look_button` looks around.
+pub struct FlyController {
+    pub look_button: MouseButton,
+    /// World units per second.
+    pub speed: f32,
+    /// Radians per pixel dragged.
+    pub look_speed: f32,
+    pub sharpness: f32,
+
+    position: Point3<f32>,
+    yaw: f32,
+    pitch: f32,
+    velocity: Vector3<f32>,
+    goal_yaw: f32,
+    goal_pitch: f32,
+    looking: bool,
+    cursor: Option<[f32; 2]>,
+    /// forward, back, left, right, up, down
+    keys: [bool; 6],
+}
+
+impl FlyController {
+    const PITCH_LIMIT: f32 = std::f32::consts::FRAC_PI_2 - 0.01;
+
+    pub fn new(camera: &Camera) -> Self {
+        let dir = (camera.target - camera.eye).normalize();
+        let yaw = dir.x.atan2(-dir.z);
+        let pitch = dir.y.asin();
+        Self {
+            look_button: MouseButton::Right,
+            speed: 5.0,
+            look_speed: 0.005,
+            sharpness: 10.0,
+            position: camera.eye,
+            yaw,
+            pitch,
+            velocity: Vector3::new(0.0, 0.0, 0.0),
+            goal_yaw: yaw,
+            goal_pitch: pitch,
+            looking: false,
+            cursor: None,
+            keys: [false; 6],
+        }
+    }
+
+    fn forward(&self) -> Vector3<f32> {
+        Vector3::new(self.yaw.sin() * self.pitch.cos(), self.pitch.sin(), -self.yaw.cos() * self.pitch.cos())
+    }
+}
+
+impl CameraController for FlyController {
+    fn input(&mut self, _camera: &Camera, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::MouseInput { state, button, .. } if *button == self.look_button => {
+                self.looking = *state == ElementState::Pressed;
+                true
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                let pos = [position.x as f32, position.y as f32];
+                if let (true, Some(last)) = (self.looking, self.cursor) {
+                    self.goal_yaw += (pos[0] - last[0]) * self.look_speed;
+                    self.goal_pitch = (self.goal_pitch - (pos[1] - last[1]) * self.look_speed).clamp(-Self::PITCH_LIMIT, Self::PITCH_LIMIT);
+                }
+                self.cursor = Some(pos);
+                self.looking
+            }
+            WindowEvent::Focused(false) => {
+                self.keys = [false; 6];
+                self.looking = false;
+                false
+            }
+            WindowEvent::KeyboardInput { input, .. } => {
+                let pressed = input.state == ElementState::Pressed;
+                let key = match input.virtual_keycode {
+                    Some(VirtualKeyCode::W) => 0,
+                    Some(VirtualKeyCode::S) => 1,
+                    Some(VirtualKeyCode::A) => 2,
+                    Some(VirtualKeyCode::D) => 3,
+                    Some(VirtualKeyCode::E) | Some(VirtualKeyCode::Space) => 4,
+                    Some(VirtualKeyCode::Q) | Some(VirtualKeyCode::LShift) => 5,
+                    _ => return false,
+                };
+                self.keys[key] = pressed;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn update(&mut self, camera: &mut Camera, dt: f32) {
+        let t = smoothing(self.sharpness, dt);
+        self.yaw = lerp(self.yaw, self.goal_yaw, t);
+        self.pitch = lerp(self.pitch, self.goal_pitch, t);
+
+        let forward = self.forward();
+        let right = forward.cross(camera.up).normalize();
+        let axis = |pos: bool, neg: bool| (pos as i32 - neg as i32) as f32;
+        let mut wish = forward * axis(self.keys[0], self.keys[1])
+            + right * axis(self.keys[3], self.keys[2])
+            + camera.up * axis(self.keys[4], self.keys[5]);
+        if wish.magnitude2() > 0.0 {
+            wish = wish.normalize() * self.speed;
+        }
+
+        // ease into and out of movement instead of starting and stopping instantly
+        self.velocity += (wish - self.velocity) * t;
+        self.position += self.velocity * dt;
+
+        camera.eye = self.position;
+        camera.target = self.position + forward;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[allow(deprecated)]
+    fn cursor_moved(x: f64, y: f64) -> WindowEvent<'static> {
+        WindowEvent::CursorMoved {
+            // only compared against other ids, never used to reach a device
+            device_id: unsafe { winit::event::DeviceId::dummy() },
+            position: winit::dpi::PhysicalPosition::new(x, y),
+            modifiers: Default::default(),
+        }
+    }
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() <= 1e-3 * (1.0 + a.abs().max(b.abs()))
+    }
+
+    fn camera_2d() -> Camera {
+        let mut camera = Camera::orthographic(10.0);
+        camera.set_viewport(800, 600);
+        camera.pan(3.0, -2.0, 0.0);
+        camera
+    }
+
+    #[test]
+    fn zoom_keeps_the_point_under_the_cursor() {
+        let mut camera = camera_2d();
+        let mut pan_zoom = PanZoomController::new(&camera);
+        pan_zoom.sharpness = 0.0;
+        pan_zoom.input(&camera, &cursor_moved(600.0, 150.0));
+        // the cursor's pixel in clip space
+        let ndc = [600.0 / 800.0 * 2.0 - 1.0, 1.0 - 150.0 / 600.0 * 2.0];
+
+        for factor in [0.5, 3.0, 1.1] {
+            let before = camera.unproject(ndc, 0.5).unwrap();
+            pan_zoom.zoom_at_cursor(&camera, factor);
+            pan_zoom.update(&mut camera, 0.016);
+            let after = camera.unproject(ndc, 0.5).unwrap();
+            assert!(close(before.x, after.x) && close(before.y, after.y), "{:?} moved to {:?}", before, after);
+        }
+        assert!(matches!(camera.projection, Projection::Orthographic { height, .. } if close(height, 10.0 * 0.5 * 3.0 * 1.1)));
+    }
+
+    #[test]
+    fn pan_and_zoom_stay_in_bounds() {
+        let mut camera = camera_2d();
+        let mut pan_zoom = PanZoomController::new(&camera).with_bounds([-1.0, -1.0], [1.0, 1.0]);
+        pan_zoom.zoom_limits = (1.0, 20.0);
+        pan_zoom.sharpness = 0.0;
+
+        pan_zoom.input(&camera, &cursor_moved(0.0, 0.0));
+        for _ in 0..10 {
+            pan_zoom.zoom_at_cursor(&camera, 4.0);
+        }
+        pan_zoom.update(&mut camera, 0.016);
+        assert!(matches!(camera.projection, Projection::Orthographic { height, .. } if height == 20.0));
+        for _ in 0..10 {
+            pan_zoom.zoom_at_cursor(&camera, 0.1);
+        }
+        assert_eq!(pan_zoom.goal_height, 1.0);
+
+        // dragging far away stops at the bounds
+        pan_zoom.dragging = true;
+        pan_zoom.input(&camera, &cursor_moved(-100000.0, -100000.0));
+        assert_eq!(pan_zoom.goal_center(), [1.0, -1.0]);
+
+        // and so do the arrow keys
+        pan_zoom.dragging = false;
+        pan_zoom.keys = [true, false, true, false];
+        for _ in 0..100 {
+            pan_zoom.update(&mut camera, 0.1);
+        }
+        assert_eq!(pan_zoom.goal_center(), [-1.0, 1.0]);
+        assert_eq!([camera.eye.x, camera.eye.y], [-1.0, 1.0]);
+    }
+
+    #[test]
+    fn orbit_pitch_stops_short_of_the_poles() {
+        let mut camera = Camera::perspective(Point3::new(0.0, 0.0, 5.0), Point3::new(0.0, 0.0, 0.0), 45.0);
+        camera.set_viewport(800, 600);
+        let mut orbit = OrbitController::new(&camera);
+        orbit.sharpness = 0.0;
+        orbit.orbiting = true;
+
+        for y in [1e6, -1e6] {
+            orbit.input(&camera, &cursor_moved(0.0, 0.0));
+            orbit.input(&camera, &cursor_moved(0.0, y));
+            assert_eq!(orbit.goal_pitch.abs(), OrbitController::PITCH_LIMIT);
+            orbit.update(&mut camera, 0.016);
+            // still at the same distance, and never looking straight along `up`
+            let offset = camera.eye - camera.target;
+            assert!(close(offset.magnitude(), 5.0));
+            assert!(offset.normalize().dot(camera.up).abs() < 1.0 - 1e-5);
+            assert!(camera.build_view_matrix().x.x.is_finite());
+        }
+    }
+
+    #[test]
+    fn fly_pitch_stops_short_of_the_poles() {
+        let mut camera = Camera::perspective(Point3::new(0.0, 0.0, 5.0), Point3::new(0.0, 0.0, 0.0), 45.0);
+        let mut fly = FlyController::new(&camera);
+        fly.sharpness = 0.0;
+        fly.looking = true;
+        fly.input(&camera, &cursor_moved(0.0, 0.0));
+        fly.input(&camera, &cursor_moved(0.0, -1e6));
+        assert_eq!(fly.goal_pitch, FlyController::PITCH_LIMIT);
+        fly.update(&mut camera, 0.016);
+        assert!((camera.target - camera.eye).normalize().dot(camera.up) < 1.0 - 1e-5);
+    }
+}
@@ -0,0 +1,98 @@
+/// A region written into a `DynamicBuffer` during the current frame.
+#[derive(Debug, Clone)]
+pub struct DynamicSlice {
+    /// Which of the frame's buffers holds the data, see `DynamicBuffer::slice`.
+    generation: usize,
+    pub range: std::ops::Range<wgpu::BufferAddress>,
+}
+
+impl DynamicSlice {
+    pub fn is_empty(&self) -> bool {
+        self.range.start == self.range.end
+    }
+}
+
+/// A GPU buffer that is filled from the start every frame, for geometry that is rebuilt each frame (text, shapes, sprites).
+/// * Every `push` lands at a new offset, so several passes in the same command encoder can each use their own data.
+/// * When it runs out of space a larger buffer replaces it; the old one is kept until `reset` so earlier slices stay valid.
+pub struct DynamicBuffer {
+    label: &'static str,
+    usage: wgpu::BufferUsages,
+    buffer: wgpu::Buffer,
+    capacity: wgpu::BufferAddress,
+    cursor: wgpu::BufferAddress,
+    retired: Vec<wgpu::Buffer>,
+}
+
+impl DynamicBuffer {
+    pub fn new(device: &wgpu::Device, label: &'static str, usage: wgpu::BufferUsages, capacity: wgpu::BufferAddress) -> Self {
+        let usage = usage | wgpu::BufferUsages::COPY_DST;
+        Self {
+            label,
+            usage,
+            buffer: Self::create(device, label, usage, capacity),
+            capacity,
+            cursor: 0,
+            retired: vec![],
+        }
+    }
+
+    fn create(device: &wgpu::Device, label: &'static str, usage: wgpu::BufferUsages, size: wgpu::BufferAddress) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size,
+            usage,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// Start a new frame, reusing the buffer from the beginning.
+    pub fn reset(&mut self) {
+        self.cursor = 0;
+        self.retired.clear();
+    }
+
+    /// Upload the bytes and return where they were placed.
+    pub fn push(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, bytes: &[u8]) -> DynamicSlice {
+        // copies must start and end on 4 byte boundaries
+        let len = (bytes.len() as wgpu::BufferAddress).div_ceil(wgpu::COPY_BUFFER_ALIGNMENT) * wgpu::COPY_BUFFER_ALIGNMENT;
+
+        if self.cursor + len > self.capacity {
+            self.capacity = (self.capacity * 2).max(len);
+            let old = std::mem::replace(&mut self.buffer, Self::create(device, self.label, self.usage, self.capacity));
+            self.retired.push(old);
+            self.cursor = 0;
+        }
+
+        let start = self.cursor;
+        if !bytes.is_empty() {
+            if len == bytes.len() as wgpu::BufferAddress {
+                queue.write_buffer(&self.buffer, start, bytes);
+            } else {
+                let mut padded = bytes.to_vec();
+                padded.resize(len as usize, 0);
+                queue.write_buffer(&self.buffer, start, &padded);
+            }
+        }
+        self.cursor += len;
+
+        DynamicSlice {
+            generation: self.retired.len(),
+            range: start..start + bytes.len() as wgpu::BufferAddress,
+        }
+    }
+
+    /// The buffer holding the slice's data.
+    pub fn buffer(&self, slice: &DynamicSlice) -> &wgpu::Buffer {
+        if slice.generation < self.retired.len() {
+            &self.retired[slice.generation]
+        } else {
+            &self.buffer
+        }
+    }
+
+    /// A `BufferSlice` to bind the data with, e.g. `set_vertex_buffer`.
+    pub fn slice(&self, slice: &DynamicSlice) -> wgpu::BufferSlice<'_> {
+        self.buffer(slice).slice(slice.range.clone())
+    }
+}
@@ -0,0 +1,111 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+use anyhow::*;
+use crate::wgpustate::State;
+
+/// Builds a render pipeline and it's `group(0)` layout from a compiled shader, for pipelines whose shader can be reloaded.
+pub type ShaderPipelineFn = fn(&State, &wgpu::ShaderModule) -> (wgpu::RenderPipeline, wgpu::BindGroupLayout);
+
+/// Parse and validate WGSL, returning the errors as readable text.
+pub fn validate_wgsl(source: &str) -> Result<naga::Module> {
+    parse_wgsl(source).map(|(module, _)| module)
+}
+
+/// Parse and validate WGSL, keeping the validator's analysis of it, e.g. which globals each entry point uses.
+pub fn parse_wgsl(source: &str) -> Result<(naga::Module, naga::valid::ModuleInfo)> {
+    let module = naga::front::wgsl::parse_str(source).map_err(|e| anyhow!(e.emit_to_string(source)))?;
+    let info = validate_module(&module)?;
+    Ok((module, info))
+}
+
+pub fn validate_module(module: &naga::Module) -> Result<naga::valid::ModuleInfo> {
+    naga::valid::Validator::new(naga::valid::ValidationFlags::all(), naga::valid::Capabilities::all())
+        .validate(module)
+        .map_err(|e| anyhow!("shader validation failed: {}", e))
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// A pipeline rebuilt when it's shader file changes.
+pub struct WatchedShader {
+    /// The name in `State::pipeline_map`.
+    pub pipeline: String,
+    pub path: PathBuf,
+    pub build: ShaderPipelineFn,
+    modified: Option<SystemTime>,
+    /// Files it `#include`s, with their modification times.
+    includes: Vec<(PathBuf, Option<SystemTime>)>,
+}
+
+/// Polls the modification time of shader files.
+/// * `State::poll_shaders` is called before every frame and checks at most once per `interval`.
+pub struct ShaderWatcher {
+    pub shaders: Vec<WatchedShader>,
+    pub interval: Duration,
+    last_poll: Option<Instant>,
+}
+
+impl Default for ShaderWatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ShaderWatcher {
+    pub fn new() -> Self {
+        Self {
+            shaders: vec![],
+            interval: Duration::from_millis(500),
+            last_poll: None,
+        }
+    }
+
+    pub fn watch(&mut self, pipeline: &str, path: PathBuf, build: ShaderPipelineFn) {
+        let modified = modified(&path);
+        self.shaders.retain(|s| s.pipeline != pipeline);
+        self.shaders.push(WatchedShader {
+            pipeline: pipeline.to_string(),
+            path,
+            build,
+            modified,
+            includes: vec![],
+        });
+    }
+
+    /// Also reload a pipeline when one of these files changes, e.g. `ProcessedShader::files`.
+    pub fn set_includes(&mut self, pipeline: &str, files: &[PathBuf]) {
+        if let Some(s) = self.shaders.iter_mut().find(|s| s.pipeline == pipeline) {
+            s.includes = files.iter().filter(|f| **f != s.path).map(|f| (f.clone(), modified(f))).collect();
+        }
+    }
+
+    pub fn unwatch(&mut self, pipeline: &str) {
+        self.shaders.retain(|s| s.pipeline != pipeline);
+    }
+
+    /// Whether `interval` has passed since the last poll.
+    pub fn due(&mut self) -> bool {
+        if self.shaders.is_empty() || self.last_poll.is_some_and(|t| t.elapsed() < self.interval) {
+            return false;
+        }
+        self.last_poll = Some(Instant::now());
+        true
+    }
+
+    /// The indices of shaders whose file, or a file it includes, changed since they were last seen.
+    pub fn changed(&mut self) -> Vec<usize> {
+        self.shaders.iter_mut().enumerate().filter_map(|(i, s)| {
+            let now = modified(&s.path);
+            let mut changed = now.is_some() && now != s.modified;
+            s.modified = now.or(s.modified);
+            for (path, time) in &mut s.includes {
+                let now = modified(path);
+                changed |= now.is_some() && now != *time;
+                *time = now.or(*time);
+            }
+            changed.then_some(i)
+        }).collect()
+    }
+}
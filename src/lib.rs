@@ -11,21 +11,38 @@ mod tests {
 // Made by following this tutorial
 // https://sotrh.github.io/learn-wgpu
 
-mod camera;
+pub mod atlas;
+pub mod camera;
+pub mod cameracontrol;
+pub mod dynbuffer;
+pub mod hotreload;
+pub mod mipmap;
 pub mod modelbuffers;
 mod panel;
 mod panelmgmt;
 pub mod rect;
 mod renderable;
 pub mod renderobj;
+pub mod renderqueue;
 pub mod resourcebytes;
+pub mod sampler;
+pub mod shapes;
+pub mod spriteanim;
+pub mod sprites;
+pub mod streaming;
+pub mod text;
+pub mod texformat;
 pub mod texture;
 pub mod texturerenderer;
 pub mod bindgroupreg;
 pub mod transform2d;
+pub mod transformbuffer;
+pub mod tween;
+pub mod uniform;
 pub mod util;
 pub mod timing;
 pub mod wgpustate;
+pub mod widgets;
 pub mod programhook;
 mod renderablestate;
 mod rendererinit;
@@ -36,6 +53,9 @@ pub mod appmgmt;
 mod schedule;
 // pub mod combos;
 pub mod pipelines;
+pub mod pipelinedesc;
+pub mod preprocess;
+pub mod reflect;
 
 use std::borrow::Borrow;
 use std::sync::{Arc, Condvar, Mutex};
@@ -55,7 +75,7 @@ use crate::apiloop::*;
 use crate::appmgmt::{AppConductor, CloseReqResponse, EventLoopAction};
 use crate::proxyevents::ProxyEvent;
 use std::ops::Add;
-use winit::event::Event;
+use winit::event::{Event, WindowEvent};
 use winit::event_loop::{ControlFlow, EventLoop};
 use winit::window::WindowBuilder;
 pub use bytemuck;
@@ -71,7 +91,7 @@ pub fn create_program_and_renderer<T>(nametag: &str, (state, renderers, programs
 
     // ... and create a corresponding TextureRenderer, attatched with the ProgramHook.
     // in this case we are creating a renderer/program for the main Surface.
-    let mut my_renderer = TextureRenderer::new(nametag,None, target_texture);
+    let mut my_renderer = TextureRenderer::new(state, nametag,None, target_texture);
 
     // get the new IDs for the program and renderer
     let program_id = programs.len();
@@ -104,11 +124,27 @@ fn redraw_if_ready<T>(
 
     let mut surface_accessed = false;
 
+    // camera and uniform changes made during update/input are uploaded once, before any pass is encoded
+    state.poll_shaders();
+    state.begin_frame();
+    state.upload_cameras();
+    state.upload_uniforms();
+
+    let due: Vec<bool> = renderers.iter_mut().map(|r| r.should_call_drawf(false)).collect();
+
+    // compute work is encoded before every render pass, so the passes can use it's output
+    for (tex_rend, _) in renderers.iter_mut().zip(due.iter()).filter(|(_, due)| **due) {
+        let encoder = encoder.get_or_insert_with(|| state
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None }));
+        programs[tex_rend.program_id.unwrap()].compute(tex_rend, state, encoder);
+    }
+
     // note: scope here for renderpass ownership
     {
-        for tex_rend in renderers {
+        for (tex_rend, due) in renderers.iter_mut().zip(due) {
             // print!("Renderer: {} | Status:", tex_rend.name);
-            if tex_rend.should_call_drawf(false) {
+            if due {
 
                 // initialize encoder if needed
                 if match encoder {
@@ -121,6 +157,14 @@ fn redraw_if_ready<T>(
                 }
 
 
+                // attachments and pipelines for this target are made before the pass borrows the state
+                tex_rend.prepare_attachments(state);
+                state.pass.camera = tex_rend.camera;
+                state.pass.target_size = tex_rend.target_size(state);
+                state.pass.target = tex_rend.pipeline_target(state);
+                state.prepare_pipelines();
+                let (msaa_view, depth_view) = tex_rend.attachment_views(state);
+
                 // println!(" Rendering!");
                 // get the correct TextureView to this renderer's Texture
                 let view_ref = match match tex_rend.get_textureview(state){
@@ -153,16 +197,25 @@ fn redraw_if_ready<T>(
                 let mut render_pass = encoder.as_mut().unwrap().begin_render_pass(&wgpu::RenderPassDescriptor {
                     label: None,
                     color_attachments: &[wgpu::RenderPassColorAttachment {
-                        view: view_ref,
-                        resolve_target: None,
+                        // a multisampled pass draws into it's own buffer and resolves into the target
+                        view: msaa_view.as_ref().unwrap_or(view_ref),
+                        resolve_target: msaa_view.as_ref().map(|_| view_ref),
                         ops: wgpu::Operations {
                             load: tex_rend.clear_or_load.clone(),
                             store: true,
                         },
                     }],
-                    depth_stencil_attachment: None,
+                    depth_stencil_attachment: depth_view.as_ref().map(|view| wgpu::RenderPassDepthStencilAttachment {
+                        view,
+                        depth_ops: Some(wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(1.0),
+                            store: true,
+                        }),
+                        stencil_ops: None,
+                    }),
                 });
 
+
                 // mutate the render_pass according to the program
                 programs[tex_rend.program_id.unwrap().clone()].render(
                     tex_rend,
@@ -242,6 +295,13 @@ pub fn start<T:'static>(mut conductor: Box<dyn AppConductor<Message = T>>) {
 
             Event::WindowEvent { window_id, event } =>{
                 if window.id() == window.id() {
+                    // keep the State and any logically sized targets in sync before the conductor sees the event
+                    if let WindowEvent::ScaleFactorChanged { scale_factor, new_inner_size } = &event {
+                        state.set_scale_factor(*scale_factor, **new_inner_size);
+                        for r in &renderers {
+                            r.rescale_target(&mut state);
+                        }
+                    }
                     let e = conductor.event_mgmt(&mut renderers, &mut state, &mut programs, event);
                     match_ela(&mut conductor, (&mut state, &mut renderers, &mut programs), e, &mut skip_frame);
                 }
@@ -0,0 +1,680 @@
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+use anyhow::*;
+use serde::{Deserialize, Serialize};
+use crate::camera::CameraUniform;
+use crate::pipelines::PipelineTarget;
+use crate::transformbuffer::ObjectUniform;
+
+/// A render pipeline described as data, built and cached by `State::add_pipeline_desc`.
+/// * Loadable from RON or JSON with `PipelineDesc::load`, e.g.
+/// ```ron
+/// (
+///     shader: File("sprite.wgsl"),
+///     vertex_buffers: [(attributes: [(location: 0, format: Float32x3), (location: 1, format: Float32x2)])],
+///     bind_groups: [Texture, Transforms, Camera],
+///     blend: Alpha,
+/// )
+/// ```
+/// * Bind group `n` in `bind_groups` is `group(n)` in the shader.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct PipelineDesc {
+    #[serde(default)]
+    pub label: Option<String>,
+    pub shader: ShaderSource,
+    #[serde(default = "main_entry")]
+    pub vertex_entry: String,
+    /// `None` for a pipeline without a fragment stage, e.g. a depth pre-pass.
+    #[serde(default = "fragment_entry")]
+    pub fragment_entry: Option<String>,
+    #[serde(default)]
+    pub vertex_buffers: Vec<VertexBufferDesc>,
+    #[serde(default)]
+    pub bind_groups: Vec<BindGroupDesc>,
+    /// Derive what isn't given from the shader: layouts for the groups after `bind_groups`,
+    /// and one vertex buffer with every input when `vertex_buffers` is empty. See `ShaderReflection`.
+    #[serde(default)]
+    pub reflect: bool,
+    /// Needs `Features::PUSH_CONSTANTS`, which `State` requests when the adapter has it.
+    #[serde(default)]
+    pub push_constants: Vec<PushConstantDesc>,
+    #[serde(default)]
+    pub blend: BlendMode,
+    #[serde(default)]
+    pub topology: Topology,
+    #[serde(default)]
+    pub cull: CullMode,
+    #[serde(default)]
+    pub front_face: FrontFace,
+    /// Depth testing, used when the target has a depth buffer (see `TextureRenderer::set_depth`).
+    #[serde(default)]
+    pub depth: Option<DepthDesc>,
+}
+
+fn main_entry() -> String {
+    String::from("main")
+}
+
+fn fragment_entry() -> Option<String> {
+    Some(main_entry())
+}
+
+impl PipelineDesc {
+    /// A description with `main` entry points and nothing else, to be filled in with struct update syntax.
+    pub fn new(shader: ShaderSource) -> Self {
+        Self {
+            label: None,
+            shader,
+            vertex_entry: main_entry(),
+            fragment_entry: fragment_entry(),
+            vertex_buffers: vec![],
+            bind_groups: vec![],
+            reflect: false,
+            push_constants: vec![],
+            blend: BlendMode::default(),
+            topology: Topology::default(),
+            cull: CullMode::default(),
+            front_face: FrontFace::default(),
+            depth: None,
+        }
+    }
+
+    /// The layout of `default:pipe:textured`: `resourcebytes::Vertex` input, and a texture,
+    /// the per-object transforms and the camera at groups 0, 1 and 2.
+    pub fn textured(shader: ShaderSource) -> Self {
+        Self {
+            vertex_buffers: vec![VertexBufferDesc::new(&[VertexFormat::Float32x3, VertexFormat::Float32x2])],
+            bind_groups: vec![BindGroupDesc::Texture, BindGroupDesc::Transforms, BindGroupDesc::Camera],
+            cull: CullMode::Back,
+            ..Self::new(shader)
+        }
+    }
+
+    pub fn from_ron(text: &str) -> Result<Self> {
+        ron::from_str(text).map_err(|e| anyhow!("invalid pipeline description: {}", e))
+    }
+
+    pub fn from_json(text: &str) -> Result<Self> {
+        serde_json::from_str(text).map_err(|e| anyhow!("invalid pipeline description: {}", e))
+    }
+
+    /// Read a `.ron` or `.json` description. A relative `ShaderSource::File` is resolved from the description's directory.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).with_context(|| format!("could not read {}", path.display()))?;
+        let mut desc = match path.extension().and_then(|e| e.to_str()) {
+            Some("ron") => Self::from_ron(&text),
+            Some("json") => Self::from_json(&text),
+            _ => bail!("{} is not a .ron or .json pipeline description", path.display()),
+        }.with_context(|| path.display().to_string())?;
+        if let (ShaderSource::File(shader), Some(dir)) = (&mut desc.shader, path.parent()) {
+            if shader.is_relative() {
+                *shader = dir.join(&*shader);
+            }
+        }
+        Ok(desc)
+    }
+
+    pub fn to_ron(&self) -> Result<String> {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()).map_err(Error::from)
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).map_err(Error::from)
+    }
+
+    /// The WGSL source as written, read from disk for `ShaderSource::File`. `State` expands it with `State::preprocessor`.
+    pub fn shader_source(&self) -> Result<Cow<'_, str>> {
+        match &self.shader {
+            ShaderSource::Wgsl(source) => Ok(Cow::Borrowed(source)),
+            ShaderSource::File(path) => std::fs::read_to_string(path)
+                .map(Cow::Owned)
+                .with_context(|| format!("could not read shader {}", path.display())),
+        }
+    }
+
+    pub fn primitive_state(&self) -> wgpu::PrimitiveState {
+        let topology = self.topology.to_wgpu();
+        wgpu::PrimitiveState {
+            topology,
+            // `Model` indices are always `u16`
+            strip_index_format: if topology.is_strip() { Some(wgpu::IndexFormat::Uint16) } else { None },
+            front_face: self.front_face.to_wgpu(),
+            cull_mode: self.cull.to_wgpu(),
+            clamp_depth: false,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            conservative: false,
+        }
+    }
+
+    /// A pipeline without `depth` ignores the target's depth buffer.
+    pub fn depth_stencil_state(&self, target: &PipelineTarget) -> Option<wgpu::DepthStencilState> {
+        match &self.depth {
+            Some(d) => target.depth_stencil_state(d.write, d.compare.to_wgpu()),
+            None => target.depth_stencil_state(false, wgpu::CompareFunction::Always),
+        }
+    }
+
+    pub fn push_constant_ranges(&self) -> Vec<wgpu::PushConstantRange> {
+        self.push_constants.iter().map(|p| wgpu::PushConstantRange {
+            stages: stages(&p.stages),
+            range: p.start..p.end,
+        }).collect()
+    }
+}
+
+/// Where a pipeline's WGSL comes from.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ShaderSource {
+    Wgsl(String),
+    File(PathBuf),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ShaderStage {
+    Vertex,
+    Fragment,
+    Compute,
+}
+
+fn stages(list: &[ShaderStage]) -> wgpu::ShaderStages {
+    list.iter().fold(wgpu::ShaderStages::NONE, |all, s| all | match s {
+        ShaderStage::Vertex => wgpu::ShaderStages::VERTEX,
+        ShaderStage::Fragment => wgpu::ShaderStages::FRAGMENT,
+        ShaderStage::Compute => wgpu::ShaderStages::COMPUTE,
+    })
+}
+
+fn vertex_and_fragment() -> Vec<ShaderStage> {
+    vec![ShaderStage::Vertex, ShaderStage::Fragment]
+}
+
+/// One vertex buffer. Attribute offsets and the stride are worked out from the formats when left out.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct VertexBufferDesc {
+    #[serde(default)]
+    pub step: StepMode,
+    #[serde(default)]
+    pub stride: Option<u64>,
+    pub attributes: Vec<VertexAttributeDesc>,
+}
+
+impl VertexBufferDesc {
+    /// Tightly packed per-vertex attributes at locations `0..formats.len()`.
+    pub fn new(formats: &[VertexFormat]) -> Self {
+        Self {
+            step: StepMode::Vertex,
+            stride: None,
+            attributes: formats.iter().enumerate().map(|(i, format)| VertexAttributeDesc {
+                location: i as u32,
+                format: *format,
+                offset: None,
+            }).collect(),
+        }
+    }
+
+    pub fn attributes(&self) -> Vec<wgpu::VertexAttribute> {
+        let mut end = 0;
+        self.attributes.iter().map(|a| {
+            let format = a.format.to_wgpu();
+            let offset = a.offset.unwrap_or(end);
+            end = offset + format.size();
+            wgpu::VertexAttribute {
+                format,
+                offset,
+                shader_location: a.location,
+            }
+        }).collect()
+    }
+
+    pub fn array_stride(&self) -> u64 {
+        self.stride.unwrap_or_else(|| self.attributes().iter().map(|a| a.offset + a.format.size()).max().unwrap_or(0))
+    }
+
+    pub fn step_mode(&self) -> wgpu::VertexStepMode {
+        match self.step {
+            StepMode::Vertex => wgpu::VertexStepMode::Vertex,
+            StepMode::Instance => wgpu::VertexStepMode::Instance,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct VertexAttributeDesc {
+    pub location: u32,
+    pub format: VertexFormat,
+    /// Defaults to the end of the previous attribute.
+    #[serde(default)]
+    pub offset: Option<u64>,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum StepMode {
+    #[default]
+    Vertex,
+    Instance,
+}
+
+/// `wgpu::VertexFormat`, without the 64-bit formats that need an extra feature.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum VertexFormat {
+    Uint8x2, Uint8x4, Sint8x2, Sint8x4, Unorm8x2, Unorm8x4, Snorm8x2, Snorm8x4,
+    Uint16x2, Uint16x4, Sint16x2, Sint16x4, Unorm16x2, Unorm16x4, Snorm16x2, Snorm16x4,
+    Float16x2, Float16x4,
+    Float32, Float32x2, Float32x3, Float32x4,
+    Uint32, Uint32x2, Uint32x3, Uint32x4,
+    Sint32, Sint32x2, Sint32x3, Sint32x4,
+}
+
+impl VertexFormat {
+    pub fn to_wgpu(self) -> wgpu::VertexFormat {
+        use wgpu::VertexFormat as F;
+        match self {
+            VertexFormat::Uint8x2 => F::Uint8x2,
+            VertexFormat::Uint8x4 => F::Uint8x4,
+            VertexFormat::Sint8x2 => F::Sint8x2,
+            VertexFormat::Sint8x4 => F::Sint8x4,
+            VertexFormat::Unorm8x2 => F::Unorm8x2,
+            VertexFormat::Unorm8x4 => F::Unorm8x4,
+            VertexFormat::Snorm8x2 => F::Snorm8x2,
+            VertexFormat::Snorm8x4 => F::Snorm8x4,
+            VertexFormat::Uint16x2 => F::Uint16x2,
+            VertexFormat::Uint16x4 => F::Uint16x4,
+            VertexFormat::Sint16x2 => F::Sint16x2,
+            VertexFormat::Sint16x4 => F::Sint16x4,
+            VertexFormat::Unorm16x2 => F::Unorm16x2,
+            VertexFormat::Unorm16x4 => F::Unorm16x4,
+            VertexFormat::Snorm16x2 => F::Snorm16x2,
+            VertexFormat::Snorm16x4 => F::Snorm16x4,
+            VertexFormat::Float16x2 => F::Float16x2,
+            VertexFormat::Float16x4 => F::Float16x4,
+            VertexFormat::Float32 => F::Float32,
+            VertexFormat::Float32x2 => F::Float32x2,
+            VertexFormat::Float32x3 => F::Float32x3,
+            VertexFormat::Float32x4 => F::Float32x4,
+            VertexFormat::Uint32 => F::Uint32,
+            VertexFormat::Uint32x2 => F::Uint32x2,
+            VertexFormat::Uint32x3 => F::Uint32x3,
+            VertexFormat::Uint32x4 => F::Uint32x4,
+            VertexFormat::Sint32 => F::Sint32,
+            VertexFormat::Sint32x2 => F::Sint32x2,
+            VertexFormat::Sint32x3 => F::Sint32x3,
+            VertexFormat::Sint32x4 => F::Sint32x4,
+        }
+    }
+}
+
+/// The layout of one bind group.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum BindGroupDesc {
+    /// A 2D texture and it's sampler, like `default:pipe:textured`'s `group(0)`; see `State::texture_bind_group`.
+    Texture,
+    /// The `ObjectUniform` of a `RenderObject` from `State::transforms`, with a dynamic offset.
+    Transforms,
+    /// A `CameraUniform` from `State::cameras`.
+    Camera,
+    /// A value from `State::uniforms`.
+    Uniform,
+    Entries(Vec<BindingDesc>),
+}
+
+impl BindGroupDesc {
+    /// Layouts equal to the ones `State` already uses are deduplicated by wgpu,
+    /// so the bind groups of `State::transforms`, `State::cameras` and `State::uniforms` fit them.
+    pub fn layout_entries(&self) -> Vec<wgpu::BindGroupLayoutEntry> {
+        let uniform = |visibility, dynamic, size: usize| vec![wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: dynamic,
+                min_binding_size: wgpu::BufferSize::new(size as u64),
+            },
+            count: None,
+        }];
+        let vertex_fragment = wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT;
+        match self {
+            BindGroupDesc::Texture => vec![
+                BindingDesc {
+                    binding: 0,
+                    visibility: vec![ShaderStage::Fragment],
+                    ty: BindingKind::Texture { sample: SampleKind::Float, dimension: ViewDimension::D2, multisampled: false },
+                }.to_wgpu(),
+                BindingDesc {
+                    binding: 1,
+                    visibility: vec![ShaderStage::Fragment],
+                    ty: BindingKind::Sampler { filtering: true, comparison: false },
+                }.to_wgpu(),
+            ],
+            BindGroupDesc::Transforms => uniform(vertex_fragment, true, std::mem::size_of::<ObjectUniform>()),
+            BindGroupDesc::Camera => uniform(vertex_fragment, false, std::mem::size_of::<CameraUniform>()),
+            BindGroupDesc::Uniform => uniform(vertex_fragment | wgpu::ShaderStages::COMPUTE, false, 0),
+            BindGroupDesc::Entries(entries) => entries.iter().map(BindingDesc::to_wgpu).collect(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct BindingDesc {
+    pub binding: u32,
+    #[serde(default = "vertex_and_fragment")]
+    pub visibility: Vec<ShaderStage>,
+    pub ty: BindingKind,
+}
+
+impl BindingDesc {
+    pub fn to_wgpu(&self) -> wgpu::BindGroupLayoutEntry {
+        let ty = match &self.ty {
+            BindingKind::Uniform { dynamic, min_size } => wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: *dynamic,
+                min_binding_size: min_size.and_then(wgpu::BufferSize::new),
+            },
+            BindingKind::Storage { read_only, dynamic } => wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: *read_only },
+                has_dynamic_offset: *dynamic,
+                min_binding_size: None,
+            },
+            BindingKind::Texture { sample, dimension, multisampled } => wgpu::BindingType::Texture {
+                sample_type: sample.to_wgpu(),
+                view_dimension: dimension.to_wgpu(),
+                multisampled: *multisampled,
+            },
+            BindingKind::Sampler { filtering, comparison } => wgpu::BindingType::Sampler {
+                filtering: *filtering,
+                comparison: *comparison,
+            },
+        };
+        wgpu::BindGroupLayoutEntry {
+            binding: self.binding,
+            visibility: stages(&self.visibility),
+            ty,
+            count: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum BindingKind {
+    Uniform {
+        #[serde(default)]
+        dynamic: bool,
+        #[serde(default)]
+        min_size: Option<u64>,
+    },
+    Storage {
+        #[serde(default)]
+        read_only: bool,
+        #[serde(default)]
+        dynamic: bool,
+    },
+    Texture {
+        #[serde(default)]
+        sample: SampleKind,
+        #[serde(default)]
+        dimension: ViewDimension,
+        #[serde(default)]
+        multisampled: bool,
+    },
+    Sampler {
+        #[serde(default = "enabled")]
+        filtering: bool,
+        #[serde(default)]
+        comparison: bool,
+    },
+}
+
+fn enabled() -> bool {
+    true
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SampleKind {
+    #[default]
+    Float,
+    UnfilterableFloat,
+    Depth,
+    Sint,
+    Uint,
+}
+
+impl SampleKind {
+    pub fn to_wgpu(self) -> wgpu::TextureSampleType {
+        match self {
+            SampleKind::Float => wgpu::TextureSampleType::Float { filterable: true },
+            SampleKind::UnfilterableFloat => wgpu::TextureSampleType::Float { filterable: false },
+            SampleKind::Depth => wgpu::TextureSampleType::Depth,
+            SampleKind::Sint => wgpu::TextureSampleType::Sint,
+            SampleKind::Uint => wgpu::TextureSampleType::Uint,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ViewDimension {
+    D1,
+    #[default]
+    D2,
+    D2Array,
+    Cube,
+    CubeArray,
+    D3,
+}
+
+impl ViewDimension {
+    pub fn to_wgpu(self) -> wgpu::TextureViewDimension {
+        match self {
+            ViewDimension::D1 => wgpu::TextureViewDimension::D1,
+            ViewDimension::D2 => wgpu::TextureViewDimension::D2,
+            ViewDimension::D2Array => wgpu::TextureViewDimension::D2Array,
+            ViewDimension::Cube => wgpu::TextureViewDimension::Cube,
+            ViewDimension::CubeArray => wgpu::TextureViewDimension::CubeArray,
+            ViewDimension::D3 => wgpu::TextureViewDimension::D3,
+        }
+    }
+}
+
+/// A byte range of push constants visible to some stages.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct PushConstantDesc {
+    #[serde(default = "vertex_and_fragment")]
+    pub stages: Vec<ShaderStage>,
+    pub start: u32,
+    pub end: u32,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum BlendMode {
+    /// Overwrite the target.
+    #[default]
+    Replace,
+    Alpha,
+    PremultipliedAlpha,
+    Additive,
+    Multiply,
+}
+
+impl BlendMode {
+    pub fn to_wgpu(self) -> Option<wgpu::BlendState> {
+        let both = |component| wgpu::BlendState { color: component, alpha: component };
+        Some(match self {
+            BlendMode::Replace => wgpu::BlendState::REPLACE,
+            BlendMode::Alpha => wgpu::BlendState::ALPHA_BLENDING,
+            BlendMode::PremultipliedAlpha => wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING,
+            BlendMode::Additive => both(wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::One,
+                dst_factor: wgpu::BlendFactor::One,
+                operation: wgpu::BlendOperation::Add,
+            }),
+            BlendMode::Multiply => both(wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::Dst,
+                dst_factor: wgpu::BlendFactor::Zero,
+                operation: wgpu::BlendOperation::Add,
+            }),
+        })
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Topology {
+    PointList,
+    LineList,
+    LineStrip,
+    #[default]
+    TriangleList,
+    TriangleStrip,
+}
+
+impl Topology {
+    pub fn to_wgpu(self) -> wgpu::PrimitiveTopology {
+        match self {
+            Topology::PointList => wgpu::PrimitiveTopology::PointList,
+            Topology::LineList => wgpu::PrimitiveTopology::LineList,
+            Topology::LineStrip => wgpu::PrimitiveTopology::LineStrip,
+            Topology::TriangleList => wgpu::PrimitiveTopology::TriangleList,
+            Topology::TriangleStrip => wgpu::PrimitiveTopology::TriangleStrip,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum CullMode {
+    #[default]
+    None,
+    Front,
+    Back,
+}
+
+impl CullMode {
+    pub fn to_wgpu(self) -> Option<wgpu::Face> {
+        match self {
+            CullMode::None => None,
+            CullMode::Front => Some(wgpu::Face::Front),
+            CullMode::Back => Some(wgpu::Face::Back),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum FrontFace {
+    #[default]
+    Ccw,
+    Cw,
+}
+
+impl FrontFace {
+    pub fn to_wgpu(self) -> wgpu::FrontFace {
+        match self {
+            FrontFace::Ccw => wgpu::FrontFace::Ccw,
+            FrontFace::Cw => wgpu::FrontFace::Cw,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct DepthDesc {
+    #[serde(default = "enabled")]
+    pub write: bool,
+    #[serde(default)]
+    pub compare: CompareFunction,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum CompareFunction {
+    Never,
+    #[default]
+    Less,
+    Equal,
+    LessEqual,
+    Greater,
+    NotEqual,
+    GreaterEqual,
+    Always,
+}
+
+impl CompareFunction {
+    pub fn to_wgpu(self) -> wgpu::CompareFunction {
+        match self {
+            CompareFunction::Never => wgpu::CompareFunction::Never,
+            CompareFunction::Less => wgpu::CompareFunction::Less,
+            CompareFunction::Equal => wgpu::CompareFunction::Equal,
+            CompareFunction::LessEqual => wgpu::CompareFunction::LessEqual,
+            CompareFunction::Greater => wgpu::CompareFunction::Greater,
+            CompareFunction::NotEqual => wgpu::CompareFunction::NotEqual,
+            CompareFunction::GreaterEqual => wgpu::CompareFunction::GreaterEqual,
+            CompareFunction::Always => wgpu::CompareFunction::Always,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn full() -> PipelineDesc {
+        PipelineDesc {
+            label: Some(String::from("full")),
+            fragment_entry: None,
+            bind_groups: vec![
+                BindGroupDesc::Texture,
+                BindGroupDesc::Entries(vec![
+                    BindingDesc {
+                        binding: 0,
+                        visibility: vec![ShaderStage::Fragment],
+                        ty: BindingKind::Texture { sample: SampleKind::Depth, dimension: ViewDimension::Cube, multisampled: false },
+                    },
+                    BindingDesc {
+                        binding: 1,
+                        visibility: vec![ShaderStage::Vertex, ShaderStage::Compute],
+                        ty: BindingKind::Uniform { dynamic: true, min_size: Some(64) },
+                    },
+                ]),
+            ],
+            reflect: true,
+            push_constants: vec![PushConstantDesc { stages: vec![ShaderStage::Vertex], start: 0, end: 16 }],
+            blend: BlendMode::PremultipliedAlpha,
+            topology: Topology::TriangleStrip,
+            depth: Some(DepthDesc { write: false, compare: CompareFunction::GreaterEqual }),
+            ..PipelineDesc::textured(ShaderSource::File(PathBuf::from("shaders/full.wgsl")))
+        }
+    }
+
+    #[test]
+    fn ron_round_trip() {
+        let desc = full();
+        assert_eq!(PipelineDesc::from_ron(&desc.to_ron().unwrap()).unwrap(), desc);
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let desc = full();
+        assert_eq!(PipelineDesc::from_json(&desc.to_json().unwrap()).unwrap(), desc);
+    }
+
+    #[test]
+    fn missing_fields_use_defaults() {
+        let desc = PipelineDesc::from_ron("(shader: Wgsl(\"\"), blend: Alpha)").unwrap();
+        assert_eq!(desc, PipelineDesc { blend: BlendMode::Alpha, ..PipelineDesc::new(ShaderSource::Wgsl(String::new())) });
+        assert!(PipelineDesc::from_json("{\"blend\": \"Alpha\"}").is_err());
+    }
+
+    #[test]
+    fn load_resolves_the_shader_from_the_description() {
+        let dir = std::env::temp_dir().join(format!("panels-pipelinedesc-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let desc = full();
+
+        std::fs::write(dir.join("full.ron"), desc.to_ron().unwrap()).unwrap();
+        std::fs::write(dir.join("full.json"), desc.to_json().unwrap()).unwrap();
+        std::fs::write(dir.join("full.toml"), "").unwrap();
+        let ron = PipelineDesc::load(dir.join("full.ron"));
+        let json = PipelineDesc::load(dir.join("full.json"));
+        let toml = PipelineDesc::load(dir.join("full.toml"));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let expected = PipelineDesc { shader: ShaderSource::File(dir.join("shaders/full.wgsl")), ..desc };
+        assert_eq!(ron.unwrap(), expected);
+        assert_eq!(json.unwrap(), expected);
+        assert!(toml.is_err());
+    }
+}
@@ -1,10 +1,170 @@
+use crate::hotreload::ShaderPipelineFn;
+use crate::pipelinedesc::PipelineDesc;
+use crate::preprocess::ProcessedShader;
+use crate::reflect::ShaderReflection;
 use crate::renderobj::RenderObject;
 use crate::wgpustate::State;
+use std::collections::HashMap;
+use std::sync::Arc;
+#[derive(Clone, Debug)]
 pub struct Pipeline {
     pub pipeline: usize,
+    /// The layout of `group(0)`, used by `State::create_bindgroup`.
     pub bindgrouplayout: usize,
+    /// The layout of every group in order, indices into `State::bindgroup_layouts`.
+    pub bindgrouplayouts: Vec<usize>,
+    /// What the shader declares, when it's WGSL source is known. Used by `State::create_bindgroup_named`.
+    pub reflection: Option<Arc<ShaderReflection>>,
 }
 
 impl Pipeline {
+    /// A pipeline with a single bind group layout.
+    pub fn new(pipeline: usize, bindgrouplayout: usize) -> Self {
+        Self {
+            pipeline,
+            bindgrouplayout,
+            bindgrouplayouts: vec![bindgrouplayout],
+            reflection: None,
+        }
+    }
+}
+
+/// The attachments of a render pass, which a render pipeline has to be built for.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PipelineTarget {
+    pub color_formats: Vec<wgpu::TextureFormat>,
+    pub depth_format: Option<wgpu::TextureFormat>,
+    pub sample_count: u32,
+}
+
+impl PipelineTarget {
+    /// A single color attachment without depth or multisampling.
+    pub fn new(format: wgpu::TextureFormat) -> Self {
+        Self {
+            color_formats: vec![format],
+            depth_format: None,
+            sample_count: 1,
+        }
+    }
+
+    /// One `ColorTargetState` per color attachment.
+    pub fn color_targets(&self, blend: Option<wgpu::BlendState>) -> Vec<wgpu::ColorTargetState> {
+        self.color_formats.iter().map(|format| wgpu::ColorTargetState {
+            format: *format,
+            blend,
+            write_mask: wgpu::ColorWrites::ALL,
+        }).collect()
+    }
+
+    /// Depth testing with `compare` when the target has a depth buffer.
+    /// A pipeline that doesn't use depth still needs this, with `Always` and no writes.
+    pub fn depth_stencil_state(&self, write: bool, compare: wgpu::CompareFunction) -> Option<wgpu::DepthStencilState> {
+        self.depth_format.map(|format| wgpu::DepthStencilState {
+            format,
+            depth_write_enabled: write,
+            depth_compare: compare,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        })
+    }
+
+    pub fn multisample_state(&self) -> wgpu::MultisampleState {
+        wgpu::MultisampleState {
+            count: self.sample_count,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        }
+    }
+}
 
-}
\ No newline at end of file
+/// How a render pipeline was built, kept so `State` can build it again for other `PipelineTarget`s.
+/// * Builders read the target from `State::pass.target`.
+pub enum PipelineRecipe {
+    /// From `State::add_pipeline`.
+    Fn(fn(&State) -> (wgpu::RenderPipeline, wgpu::BindGroupLayout)),
+    /// From a watched shader file, with it's latest source.
+    Shader { source: String, build: ShaderPipelineFn },
+    /// From `State::add_pipeline_desc`, with it's shader already expanded.
+    Desc { desc: Box<PipelineDesc>, shader: ProcessedShader },
+}
+
+/// A built-in pipeline, like the one `ShapeRenderer` draws with, built again for each `PipelineTarget` it's drawn into.
+/// * An alpha blended triangle list with one vertex buffer and `main` entry points, without depth testing.
+pub struct TargetPipelines {
+    label: &'static str,
+    shader: wgpu::ShaderModule,
+    layout: wgpu::PipelineLayout,
+    vertex: wgpu::VertexBufferLayout<'static>,
+    /// The target it was first built for, used for targets that weren't prepared.
+    first: PipelineTarget,
+    pipelines: HashMap<PipelineTarget, wgpu::RenderPipeline>,
+}
+
+impl TargetPipelines {
+    pub fn new(
+        device: &wgpu::Device,
+        label: &'static str,
+        source: &str,
+        bind_group_layouts: &[&wgpu::BindGroupLayout],
+        vertex: wgpu::VertexBufferLayout<'static>,
+        target: &PipelineTarget,
+    ) -> Self {
+        let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some(label),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(label),
+            bind_group_layouts,
+            push_constant_ranges: &[],
+        });
+        let mut pipelines = Self {
+            label,
+            shader,
+            layout,
+            vertex,
+            first: target.clone(),
+            pipelines: HashMap::new(),
+        };
+        pipelines.prepare(device, target);
+        pipelines
+    }
+
+    /// Build the pipeline for `target` if it isn't built yet.
+    pub fn prepare(&mut self, device: &wgpu::Device, target: &PipelineTarget) {
+        if self.pipelines.contains_key(target) {
+            return;
+        }
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(self.label),
+            layout: Some(&self.layout),
+            vertex: wgpu::VertexState {
+                module: &self.shader,
+                entry_point: "main",
+                buffers: std::slice::from_ref(&self.vertex),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &self.shader,
+                entry_point: "main",
+                targets: &target.color_targets(Some(wgpu::BlendState::ALPHA_BLENDING)),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                clamp_depth: false,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                conservative: false,
+            },
+            depth_stencil: target.depth_stencil_state(false, wgpu::CompareFunction::Always),
+            multisample: target.multisample_state(),
+        });
+        self.pipelines.insert(target.clone(), pipeline);
+    }
+
+    /// The pipeline for `target`, see `prepare`.
+    pub fn get(&self, target: &PipelineTarget) -> &wgpu::RenderPipeline {
+        self.pipelines.get(target).unwrap_or(&self.pipelines[&self.first])
+    }
+}
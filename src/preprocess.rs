@@ -0,0 +1,345 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use anyhow::*;
+use crate::hotreload;
+
+/// Where a line of preprocessed WGSL came from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SourceLocation {
+    /// The path of the file, or the name of a snippet.
+    pub file: String,
+    /// 1-based.
+    pub line: usize,
+}
+
+/// The original file and line of every line of preprocessed WGSL.
+/// * Lines are mapped whole, so columns are off on lines where a constant was substituted.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SourceMap {
+    lines: Vec<SourceLocation>,
+}
+
+impl SourceMap {
+    /// The origin of a 1-based line of the output.
+    pub fn locate(&self, line: usize) -> Option<&SourceLocation> {
+        line.checked_sub(1).and_then(|i| self.lines.get(i))
+    }
+
+    /// Rewrite the `wgsl:line:column` references and the line numbers in the margin of naga's errors
+    /// to the original file and line.
+    pub fn remap(&self, message: &str) -> String {
+        message.lines().map(|l| self.remap_line(l)).collect::<Vec<_>>().join("\n")
+    }
+
+    fn remap_line(&self, text: &str) -> String {
+        // a quoted source line, e.g. `23 │     return nope;`
+        if let Some((number, quoted)) = text.split_once(" │") {
+            if let Some(loc) = number.trim_start().parse().ok().and_then(|line| self.locate(line)) {
+                return format!("{:>width$} │{}", loc.line, quoted, width = number.len());
+            }
+        }
+        let mut out = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(i) = rest.find("wgsl:") {
+            out.push_str(&rest[..i]);
+            let after = &rest[i + 5..];
+            let digits = after.find(|c: char| !c.is_ascii_digit()).unwrap_or(after.len());
+            match after[..digits].parse().ok().and_then(|line| self.locate(line)) {
+                Some(loc) => {
+                    out.push_str(&format!("{}:{}", loc.file, loc.line));
+                    rest = &after[digits..];
+                }
+                None => {
+                    out.push_str("wgsl:");
+                    rest = after;
+                }
+            }
+        }
+        out.push_str(rest);
+        out
+    }
+}
+
+/// WGSL with it's directives expanded.
+#[derive(Clone, Debug)]
+pub struct ProcessedShader {
+    pub source: String,
+    pub map: SourceMap,
+    /// Every file that was included, e.g. to watch them for changes.
+    pub files: Vec<PathBuf>,
+}
+
+impl ProcessedShader {
+    /// Parse and validate the output, with parse errors pointing to the original files.
+    pub fn parse(&self) -> Result<(naga::Module, naga::valid::ModuleInfo)> {
+        let module = naga::front::wgsl::parse_str(&self.source).map_err(|e| {
+            let (line, column) = e.location(&self.source);
+            let at = match self.map.locate(line) {
+                Some(loc) => format!("{}:{}:{}", loc.file, loc.line, column),
+                None => format!("wgsl:{}:{}", line, column),
+            };
+            anyhow!("{}: {}\n{}", at, e, self.map.remap(&e.emit_to_string(&self.source)))
+        })?;
+        let info = hotreload::validate_module(&module)?;
+        Ok((module, info))
+    }
+}
+
+/// Expands a few C-like directives in WGSL, one per line:
+/// * `#include "name"` pastes a snippet from `snippets`, or a file relative to the including file or one of `include_dirs`.
+///   Each is included once, so shared blocks can be included from several places.
+/// * `#define NAME value` and `#undef NAME`. Later uses of `NAME` are replaced by `value`, outside of `//` comments.
+/// * `#ifdef NAME`, `#ifndef NAME`, `#else` and `#endif`, for feature toggles.
+pub struct Preprocessor {
+    /// Defined before a shader is processed, e.g. `defines.insert("USE_FOG".into(), String::new())`.
+    pub defines: HashMap<String, String>,
+    /// Includes by name, checked before files.
+    pub snippets: HashMap<String, String>,
+    /// Searched for includes after the including file's own directory.
+    pub include_dirs: Vec<PathBuf>,
+}
+
+impl Default for Preprocessor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The state of one expansion.
+struct Expansion {
+    out: String,
+    map: SourceMap,
+    files: Vec<PathBuf>,
+    defines: HashMap<String, String>,
+    included: HashSet<String>,
+}
+
+struct Condition {
+    active: bool,
+    seen_else: bool,
+    line: usize,
+}
+
+impl Preprocessor {
+    /// With panels' own snippets:
+    /// * `panels/transforms.wgsl`: `object: ObjectUniform` at `group(1)` and `camera: CameraUniform` at `group(2)`,
+    ///   as bound by `default:pipe:textured`.
+    pub fn new() -> Self {
+        let mut snippets = HashMap::new();
+        snippets.insert(String::from("panels/transforms.wgsl"), String::from(include_str!("transforms.wgsl")));
+        Self {
+            defines: HashMap::new(),
+            snippets,
+            include_dirs: vec![],
+        }
+    }
+
+    pub fn define(&mut self, name: &str, value: &str) {
+        self.defines.insert(name.to_string(), value.to_string());
+    }
+
+    pub fn undefine(&mut self, name: &str) {
+        self.defines.remove(name);
+    }
+
+    /// Process a shader file, resolving relative includes from it's directory.
+    pub fn process_file(&self, path: impl AsRef<Path>) -> Result<ProcessedShader> {
+        let path = path.as_ref();
+        let source = std::fs::read_to_string(path).with_context(|| format!("could not read shader {}", path.display()))?;
+        let mut ex = self.expansion();
+        ex.included.insert(file_key(path));
+        ex.files.push(path.to_path_buf());
+        self.expand(&mut ex, &path.display().to_string(), &source, path.parent())?;
+        Ok(ex.finish())
+    }
+
+    /// Process source that isn't read from a file. `name` is used in errors, and relative includes
+    /// are resolved from `dir` when there is one.
+    pub fn process_str(&self, name: &str, source: &str, dir: Option<&Path>) -> Result<ProcessedShader> {
+        let mut ex = self.expansion();
+        self.expand(&mut ex, name, source, dir)?;
+        Ok(ex.finish())
+    }
+
+    fn expansion(&self) -> Expansion {
+        Expansion {
+            out: String::new(),
+            map: SourceMap::default(),
+            files: vec![],
+            defines: self.defines.clone(),
+            included: HashSet::new(),
+        }
+    }
+
+    fn expand(&self, ex: &mut Expansion, file: &str, source: &str, dir: Option<&Path>) -> Result<()> {
+        let mut conditions: Vec<Condition> = vec![];
+        for (i, text) in source.lines().enumerate() {
+            let line = i + 1;
+            let active = conditions.iter().all(|c| c.active);
+            let directive = match text.trim_start().strip_prefix('#') {
+                Some(d) => d,
+                None => {
+                    if active {
+                        ex.out.push_str(&substitute(text, &ex.defines));
+                        ex.out.push('\n');
+                        ex.map.lines.push(SourceLocation { file: file.to_string(), line });
+                    }
+                    continue;
+                }
+            };
+            let (name, rest) = directive.split_once(char::is_whitespace).unwrap_or((directive, ""));
+            let rest = rest.trim();
+            match name {
+                "ifdef" | "ifndef" => {
+                    let defined = ex.defines.contains_key(identifier(rest, file, line)?);
+                    conditions.push(Condition {
+                        active: defined == (name == "ifdef"),
+                        seen_else: false,
+                        line,
+                    });
+                }
+                "else" => {
+                    let c = conditions.last_mut().ok_or_else(|| anyhow!("{}:{}: #else without #ifdef", file, line))?;
+                    if c.seen_else {
+                        bail!("{}:{}: second #else for the #ifdef on line {}", file, line, c.line);
+                    }
+                    c.active = !c.active;
+                    c.seen_else = true;
+                }
+                "endif" => {
+                    conditions.pop().ok_or_else(|| anyhow!("{}:{}: #endif without #ifdef", file, line))?;
+                }
+                _ if !active => {}
+                "define" => {
+                    let (id, value) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                    let id = identifier(id, file, line)?;
+                    ex.defines.insert(id.to_string(), value.trim().to_string());
+                }
+                "undef" => {
+                    ex.defines.remove(identifier(rest, file, line)?);
+                }
+                "include" => {
+                    let target = rest.strip_prefix('"').and_then(|r| r.strip_suffix('"'))
+                        .ok_or_else(|| anyhow!("{}:{}: expected #include \"name\"", file, line))?;
+                    self.include(ex, target, dir).with_context(|| format!("{}:{}: in #include \"{}\"", file, line, target))?;
+                }
+                _ => bail!("{}:{}: unknown directive #{}", file, line, name),
+            }
+        }
+        if let Some(c) = conditions.last() {
+            bail!("{}:{}: #ifdef without #endif", file, c.line);
+        }
+        Ok(())
+    }
+
+    fn include(&self, ex: &mut Expansion, target: &str, dir: Option<&Path>) -> Result<()> {
+        if let Some(source) = self.snippets.get(target) {
+            if ex.included.insert(target.to_string()) {
+                self.expand(ex, target, source, None)?;
+            }
+            return Ok(());
+        }
+        let path = dir.into_iter().chain(self.include_dirs.iter().map(|d| d.as_path()))
+            .map(|d| d.join(target))
+            .find(|p| p.is_file())
+            .ok_or_else(|| anyhow!("no snippet or file named \"{}\"", target))?;
+        if ex.included.insert(file_key(&path)) {
+            let source = std::fs::read_to_string(&path).with_context(|| format!("could not read {}", path.display()))?;
+            ex.files.push(path.clone());
+            self.expand(ex, &path.display().to_string(), &source, path.parent())?;
+        }
+        Ok(())
+    }
+}
+
+impl Expansion {
+    fn finish(self) -> ProcessedShader {
+        ProcessedShader {
+            source: self.out,
+            map: self.map,
+            files: self.files,
+        }
+    }
+}
+
+/// Identifies a file however it was reached, so it's only included once.
+fn file_key(path: &Path) -> String {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf()).display().to_string()
+}
+
+fn identifier<'a>(text: &'a str, file: &str, line: usize) -> Result<&'a str> {
+    let valid = text.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && text.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid {
+        bail!("{}:{}: expected a name, found \"{}\"", file, line, text);
+    }
+    Ok(text)
+}
+
+/// Replace defined names that have a value, leaving `//` comments alone.
+fn substitute(text: &str, defines: &HashMap<String, String>) -> String {
+    if defines.values().all(|v| v.is_empty()) {
+        return text.to_string();
+    }
+    let (code, comment) = match text.find("//") {
+        Some(i) => text.split_at(i),
+        None => (text, ""),
+    };
+    let mut out = String::with_capacity(text.len());
+    let mut word = String::new();
+    for c in code.chars().chain(std::iter::once('\0')) {
+        if c.is_ascii_alphanumeric() || c == '_' {
+            word.push(c);
+            continue;
+        }
+        match defines.get(&word) {
+            // a leading digit makes it a number, not a name
+            Some(value) if !value.is_empty() && !word.starts_with(|d: char| d.is_ascii_digit()) => out.push_str(value),
+            _ => out.push_str(&word),
+        }
+        word.clear();
+        if c != '\0' {
+            out.push(c);
+        }
+    }
+    out.push_str(comment);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn includes_snippets_once() {
+        let mut p = Preprocessor::new();
+        p.snippets.insert("a.wgsl".into(), "#include \"panels/transforms.wgsl\"\nlet a: f32 = 1.0;".into());
+        let out = p.process_str("main.wgsl", "#include \"a.wgsl\"\n#include \"panels/transforms.wgsl\"\nlet b: f32 = a;", None).unwrap();
+        assert_eq!(out.source.matches("struct CameraUniform").count(), 1);
+        assert!(out.source.ends_with("let a: f32 = 1.0;\nlet b: f32 = a;\n"));
+        let last = out.source.lines().count();
+        assert_eq!(out.map.locate(last), Some(&SourceLocation { file: "main.wgsl".into(), line: 3 }));
+        assert_eq!(out.map.locate(last - 1), Some(&SourceLocation { file: "a.wgsl".into(), line: 2 }));
+    }
+
+    #[test]
+    fn ifdef_and_constants() {
+        let mut p = Preprocessor::new();
+        p.define("FOG", "");
+        let src = "#define SIZE 4\n#ifdef FOG\nfog SIZE; // SIZE\n#else\nclear\n#endif\n#ifndef FOG\nno_fog\n#endif\nSIZE_2 SIZE";
+        let out = p.process_str("s", src, None).unwrap();
+        assert_eq!(out.source, "fog 4; // SIZE\nSIZE_2 4\n");
+        assert_eq!(out.map.locate(2).unwrap().line, 10);
+    }
+
+    #[test]
+    fn errors_point_to_the_original_line() {
+        let p = Preprocessor::new();
+        assert!(p.process_str("s", "#ifdef A\n", None).unwrap_err().to_string().contains("s:1"));
+        assert!(p.process_str("s", "#include \"missing.wgsl\"", None).is_err());
+
+        let src = "#include \"panels/transforms.wgsl\"\n\nfn f() -> f32 {\n    return nope;\n}\n";
+        let err = p.process_str("bad.wgsl", src, None).unwrap().parse().unwrap_err().to_string();
+        assert!(err.starts_with("bad.wgsl:4:"), "{}", err);
+    }
+}
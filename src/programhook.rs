@@ -22,6 +22,10 @@ pub trait ProgramHook {
         println!("[WARN] ProgramHook has no hook_renderer() function.");
     }
 
+    /// Called before any render pass of the frame when the program is due to render, with the frame's encoder.
+    /// Here you can dispatch compute passes (see `State::dispatch`) whose output `render` then draws.
+    fn compute(&self, renderer: &mut TextureRenderer, state: &mut State, encoder: &mut wgpu::CommandEncoder) {}
+
     /// The program is passed a reference to a `RenderPass` targetting it's `TextureRenderer` texture.
     /// Here you can render resources stored in the `State`.
     fn render<'a>(&self, renderer: &mut TextureRenderer, state: &'a mut State, render_pass: &mut wgpu::RenderPass<'a>) {}
@@ -2,14 +2,20 @@ use winit::dpi::PhysicalSize;
 use bytemuck::*;
 use crate::dpi::PhysicalPosition;
 
+// Coordinate spaces:
+// * `ScreenPoint`: physical pixels, origin at the top-left of the window, Y pointing down.
+// * `LogicalPoint`: DPI-independent pixels, `ScreenPoint` divided by the window's scale factor.
+// * `WorldPoint`: normalized device coordinates, -1..1 across the window, Y pointing up.
+// * `PanelPoint`: 0..1 across a rectangle, origin at it's top-left, Y pointing down (matches texture coordinates).
+
 /// A point in the worldspace, in world coordinates.
 
 #[repr(C)]
-#[derive(Debug,Copy,Clone,bytemuck::Pod,bytemuck::Zeroable)]
+#[derive(Debug,Copy,Clone,PartialEq,bytemuck::Pod,bytemuck::Zeroable)]
 pub struct WorldPoint {
-    x: f32,
-    y: f32,
-    z: f32,
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
 }
 
 impl WorldPoint {
@@ -23,12 +29,28 @@ impl WorldPoint {
 
     pub fn from_screen_point(screensize: &PhysicalSize<u32>, p: ScreenPoint) -> Self {
         Self {
-            x: (((p.x as f32 /screensize.width as f32 )*2.0)-1.0),
-            y: (((p.y as f32 /screensize.height as f32)*2.0)-1.0),
+            x: ((p.x / screensize.width as f32) * 2.0) - 1.0,
+            y: 1.0 - ((p.y / screensize.height as f32) * 2.0),
             z: 0.0
         }
     }
 
+    pub fn from_logical_point(screensize: &PhysicalSize<u32>, scale_factor: f64, p: LogicalPoint) -> Self {
+        Self::from_screen_point(screensize, p.to_screen(scale_factor))
+    }
+
+    pub fn x(&self) -> f32 {
+        self.x
+    }
+
+    pub fn y(&self) -> f32 {
+        self.y
+    }
+
+    pub fn z(&self) -> f32 {
+        self.z
+    }
+
     /// Equivalent to `WorldPoint::from_screen_point(&screensize, ScreenPoint::from_mouse(&mouse))`
     pub fn from_mouse(screensize: &PhysicalSize<u32>, mouse: &PhysicalPosition<f64>) -> Self {
         WorldPoint::from_screen_point(screensize, ScreenPoint::from_mouse(mouse))
@@ -48,6 +70,8 @@ impl std::ops::Mul for WorldPoint {
 }
 
 /// A Rectangle meausred in WGPU world units.
+/// * `pos` is the top-left corner, the rectangle extends right (+X) and down (-Y).
+#[derive(Debug,Copy,Clone,PartialEq)]
 pub struct WorldRectangle {
     pub pos: WorldPoint,
     pub width: f32,
@@ -56,6 +80,49 @@ pub struct WorldRectangle {
 
 impl WorldRectangle {
 
+    pub fn new(pos: WorldPoint, width: f32, height: f32) -> Self {
+        Self {
+            pos,
+            width,
+            height
+        }
+    }
+
+    /// The rectangle covering the whole window.
+    pub fn full() -> Self {
+        Self::new(WorldPoint::new(-1.0, 1.0, 0.0), 2.0, 2.0)
+    }
+
+    /// Builds a rectangle from any two opposite corners.
+    pub fn from_corners(a: &WorldPoint, b: &WorldPoint) -> Self {
+        Self {
+            pos: WorldPoint::new(a.x.min(b.x), a.y.max(b.y), a.z),
+            width: (a.x - b.x).abs(),
+            height: (a.y - b.y).abs()
+        }
+    }
+
+    pub fn left(&self) -> f32 {
+        self.pos.x
+    }
+
+    pub fn right(&self) -> f32 {
+        self.pos.x + self.width
+    }
+
+    pub fn top(&self) -> f32 {
+        self.pos.y
+    }
+
+    pub fn bottom(&self) -> f32 {
+        self.pos.y - self.height
+    }
+
+    pub fn center(&self) -> WorldPoint {
+        WorldPoint::new(self.pos.x + self.width / 2.0, self.pos.y - self.height / 2.0, self.pos.z)
+    }
+
+    /// The offset of the point from the rectangle's top-left corner, in world units.
     pub fn pos_in(&self, point: &WorldPoint) -> WorldPoint {
         WorldPoint {
             x: point.x-self.pos.x,
@@ -64,23 +131,63 @@ impl WorldRectangle {
         }
     }
 
+    /// Edges are inclusive.
+    pub fn contains(&self, point: &WorldPoint) -> bool {
+        point.x >= self.left() && point.x <= self.right() && point.y <= self.top() && point.y >= self.bottom()
+    }
+
+    /// The overlapping area, or `None` if the rectangles don't overlap.
+    pub fn intersection(&self, other: &WorldRectangle) -> Option<WorldRectangle> {
+        let left = self.left().max(other.left());
+        let right = self.right().min(other.right());
+        let top = self.top().min(other.top());
+        let bottom = self.bottom().max(other.bottom());
+        if left > right || bottom > top {
+            return None;
+        }
+        Some(Self::new(WorldPoint::new(left, top, self.pos.z), right - left, top - bottom))
+    }
+
+    /// The smallest rectangle containing both rectangles.
+    pub fn union(&self, other: &WorldRectangle) -> WorldRectangle {
+        let left = self.left().min(other.left());
+        let right = self.right().max(other.right());
+        let top = self.top().max(other.top());
+        let bottom = self.bottom().min(other.bottom());
+        Self::new(WorldPoint::new(left, top, self.pos.z), right - left, top - bottom)
+    }
+
+    /// Where the point lies within this rectangle, as a `PanelPoint`.
+    pub fn to_panel(&self, point: &WorldPoint) -> PanelPoint {
+        PanelPoint {
+            u: (point.x - self.pos.x) / self.width,
+            v: (self.pos.y - point.y) / self.height
+        }
+    }
+
+    /// The world position of a `PanelPoint` within this rectangle.
+    pub fn from_panel(&self, point: &PanelPoint) -> WorldPoint {
+        WorldPoint::new(self.pos.x + point.u * self.width, self.pos.y - point.v * self.height, self.pos.z)
+    }
+
     pub fn from_screen_rect(screensize: &PhysicalSize<u32>, sr: &ScreenRectangle) -> Self {
         Self {
-            pos: WorldPoint::new(sr.pos.x as f32 /screensize.width as f32 , sr.pos.y as f32 / screensize.height as f32, 0.0),
-            width: (sr.width as f32 /screensize.width as f32),
-            height: sr.height as f32 /screensize.height as f32
+            pos: WorldPoint::from_screen_point(screensize, sr.pos),
+            width: (sr.width / screensize.width as f32) * 2.0,
+            height: (sr.height / screensize.height as f32) * 2.0
         }
     }
 }
 
 /// A point on the screen, in pixels.
+#[derive(Debug,Copy,Clone,PartialEq)]
 pub struct ScreenPoint {
-    x: u32,
-    y: u32,
+    pub x: f32,
+    pub y: f32,
 }
 
 impl ScreenPoint {
-    pub fn new(x: u32, y: u32) -> Self {
+    pub fn new(x: f32, y: f32) -> Self {
         Self {
             x,
             y
@@ -89,28 +196,47 @@ impl ScreenPoint {
 
     pub fn from_mouse(point: &PhysicalPosition<f64>) -> Self {
         Self {
-            x: point.x as u32,
-            y: point.y as u32
+            x: point.x as f32,
+            y: point.y as f32
         }
     }
 
     pub fn from_world_point(screensize: &PhysicalSize<u32>, p: &WorldPoint) -> Self {
         Self {
-            x: (p.x as u32 * screensize.width),
-            y: p.y as u32 * screensize.height
+            x: ((p.x + 1.0) / 2.0) * screensize.width as f32,
+            y: ((1.0 - p.y) / 2.0) * screensize.height as f32
         }
     }
+
+    pub fn from_logical(p: &LogicalPoint, scale_factor: f64) -> Self {
+        p.to_screen(scale_factor)
+    }
+
+    pub fn to_logical(&self, scale_factor: f64) -> LogicalPoint {
+        LogicalPoint::from_screen(self, scale_factor)
+    }
 }
 
 /// A Rectangle meausred in screen pixels
+/// * `pos` is the top-left corner, the rectangle extends right and down.
+#[derive(Debug,Copy,Clone,PartialEq)]
 pub struct ScreenRectangle {
     pub pos: ScreenPoint,
-    pub width: u32,
-    pub height: u32,
+    pub width: f32,
+    pub height: f32,
 }
 
 impl ScreenRectangle {
 
+    pub fn new(pos: ScreenPoint, width: f32, height: f32) -> Self {
+        Self {
+            pos,
+            width,
+            height
+        }
+    }
+
+    /// The offset of the point from the rectangle's top-left corner, in pixels.
     pub fn pos_in(&self, point: &ScreenPoint) -> ScreenPoint {
         ScreenPoint {
             x: point.x-self.pos.x,
@@ -118,19 +244,259 @@ impl ScreenRectangle {
         }
     }
 
+    pub fn right(&self) -> f32 {
+        self.pos.x + self.width
+    }
+
+    pub fn bottom(&self) -> f32 {
+        self.pos.y + self.height
+    }
+
+    /// Edges are inclusive.
+    pub fn contains(&self, point: &ScreenPoint) -> bool {
+        point.x >= self.pos.x && point.x <= self.right() && point.y >= self.pos.y && point.y <= self.bottom()
+    }
+
+    /// The overlapping area, or `None` if the rectangles don't overlap.
+    pub fn intersection(&self, other: &ScreenRectangle) -> Option<ScreenRectangle> {
+        let left = self.pos.x.max(other.pos.x);
+        let right = self.right().min(other.right());
+        let top = self.pos.y.max(other.pos.y);
+        let bottom = self.bottom().min(other.bottom());
+        if left > right || top > bottom {
+            return None;
+        }
+        Some(Self::new(ScreenPoint::new(left, top), right - left, bottom - top))
+    }
+
+    /// The smallest rectangle containing both rectangles.
+    pub fn union(&self, other: &ScreenRectangle) -> ScreenRectangle {
+        let left = self.pos.x.min(other.pos.x);
+        let top = self.pos.y.min(other.pos.y);
+        Self::new(ScreenPoint::new(left, top), self.right().max(other.right()) - left, self.bottom().max(other.bottom()) - top)
+    }
+
+    /// Where the point lies within this rectangle, as a `PanelPoint`.
+    pub fn to_panel(&self, point: &ScreenPoint) -> PanelPoint {
+        PanelPoint {
+            u: (point.x - self.pos.x) / self.width,
+            v: (point.y - self.pos.y) / self.height
+        }
+    }
+
+    /// The screen position of a `PanelPoint` within this rectangle.
+    pub fn from_panel(&self, point: &PanelPoint) -> ScreenPoint {
+        ScreenPoint::new(self.pos.x + point.u * self.width, self.pos.y + point.v * self.height)
+    }
+
     pub fn from_world_rect(screensize: &PhysicalSize<u32>, wr: &WorldRectangle) -> Self {
         Self {
             pos: ScreenPoint::from_world_point(screensize, &wr.pos),
-            width: wr.width as u32 * screensize.width,
-            height: wr.height as u32 * screensize.height
+            width: (wr.width / 2.0) * screensize.width as f32,
+            height: (wr.height / 2.0) * screensize.height as f32
         }
     }
-    
+
     pub fn from_size(width: u32, height: u32) -> Self {
         Self {
-            pos: ScreenPoint::new(0,0),
+            pos: ScreenPoint::new(0.0,0.0),
+            width: width as f32,
+            height: height as f32
+        }
+    }
+}
+
+/// A point on the screen in logical (DPI-scaled) pixels.
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub struct LogicalPoint {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl LogicalPoint {
+    pub fn new(x: f32, y: f32) -> Self {
+        Self {
+            x,
+            y
+        }
+    }
+
+    pub fn from_screen(p: &ScreenPoint, scale_factor: f64) -> Self {
+        Self {
+            x: p.x / scale_factor as f32,
+            y: p.y / scale_factor as f32
+        }
+    }
+
+    pub fn to_screen(&self, scale_factor: f64) -> ScreenPoint {
+        ScreenPoint::new(self.x * scale_factor as f32, self.y * scale_factor as f32)
+    }
+}
+
+/// A Rectangle measured in logical (DPI-scaled) pixels.
+/// * Panels sized this way keep the same apparent size on monitors with different scale factors.
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub struct LogicalRectangle {
+    pub pos: LogicalPoint,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl LogicalRectangle {
+    pub fn new(pos: LogicalPoint, width: f32, height: f32) -> Self {
+        Self {
+            pos,
             width,
             height
         }
     }
-}
\ No newline at end of file
+
+    pub fn from_screen_rect(sr: &ScreenRectangle, scale_factor: f64) -> Self {
+        Self {
+            pos: LogicalPoint::from_screen(&sr.pos, scale_factor),
+            width: sr.width / scale_factor as f32,
+            height: sr.height / scale_factor as f32
+        }
+    }
+
+    pub fn to_screen_rect(&self, scale_factor: f64) -> ScreenRectangle {
+        ScreenRectangle::new(self.pos.to_screen(scale_factor), self.width * scale_factor as f32, self.height * scale_factor as f32)
+    }
+
+    pub fn to_world_rect(&self, screensize: &PhysicalSize<u32>, scale_factor: f64) -> WorldRectangle {
+        WorldRectangle::from_screen_rect(screensize, &self.to_screen_rect(scale_factor))
+    }
+
+    /// The size in physical pixels, rounded up so a texture of this size covers the whole rectangle.
+    pub fn physical_size(&self, scale_factor: f64) -> PhysicalSize<u32> {
+        PhysicalSize::new(
+            (self.width as f64 * scale_factor).ceil().max(1.0) as u32,
+            (self.height as f64 * scale_factor).ceil().max(1.0) as u32
+        )
+    }
+}
+
+/// A point relative to a rectangle (such as a panel), 0..1 on both axes from it's top-left corner.
+/// * Values outside 0..1 lie outside of the rectangle.
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub struct PanelPoint {
+    pub u: f32,
+    pub v: f32,
+}
+
+impl PanelPoint {
+    pub fn new(u: f32, v: f32) -> Self {
+        Self {
+            u,
+            v
+        }
+    }
+
+    pub fn is_inside(&self) -> bool {
+        (0.0..=1.0).contains(&self.u) && (0.0..=1.0).contains(&self.v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    const EPS: f32 = 1e-3;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() <= EPS * (1.0 + a.abs().max(b.abs()))
+    }
+
+    fn screen_size() -> impl Strategy<Value = PhysicalSize<u32>> {
+        (1u32..8000, 1u32..8000).prop_map(|(w, h)| PhysicalSize::new(w, h))
+    }
+
+    fn world_rect() -> impl Strategy<Value = WorldRectangle> {
+        (-2.0f32..2.0, -2.0f32..2.0, 0.01f32..4.0, 0.01f32..4.0)
+            .prop_map(|(x, y, w, h)| WorldRectangle::new(WorldPoint::new(x, y, 0.0), w, h))
+    }
+
+    #[test]
+    fn corners_map_to_ndc() {
+        let size = PhysicalSize::new(800, 600);
+        let tl = WorldPoint::from_screen_point(&size, ScreenPoint::new(0.0, 0.0));
+        let br = WorldPoint::from_screen_point(&size, ScreenPoint::new(800.0, 600.0));
+        assert_eq!((tl.x, tl.y), (-1.0, 1.0));
+        assert_eq!((br.x, br.y), (1.0, -1.0));
+        assert_eq!(ScreenRectangle::from_world_rect(&size, &WorldRectangle::full()), ScreenRectangle::from_size(800, 600));
+    }
+
+    proptest! {
+        #[test]
+        fn screen_world_round_trip(size in screen_size(), x in 0.0f32..1.0, y in 0.0f32..1.0) {
+            let p = ScreenPoint::new(x * size.width as f32, y * size.height as f32);
+            let back = ScreenPoint::from_world_point(&size, &WorldPoint::from_screen_point(&size, p));
+            prop_assert!(close(back.x, p.x) && close(back.y, p.y), "{:?} -> {:?}", p, back);
+        }
+
+        #[test]
+        fn world_screen_round_trip(size in screen_size(), x in -1.0f32..1.0, y in -1.0f32..1.0) {
+            let p = WorldPoint::new(x, y, 0.0);
+            let back = WorldPoint::from_screen_point(&size, ScreenPoint::from_world_point(&size, &p));
+            prop_assert!(close(back.x, p.x) && close(back.y, p.y), "{:?} -> {:?}", p, back);
+        }
+
+        #[test]
+        fn logical_round_trip(scale in 0.5f64..4.0, x in 0.0f32..8000.0, y in 0.0f32..8000.0) {
+            let p = ScreenPoint::new(x, y);
+            let back = p.to_logical(scale).to_screen(scale);
+            prop_assert!(close(back.x, p.x) && close(back.y, p.y));
+        }
+
+        #[test]
+        fn rect_round_trip(size in screen_size(), r in world_rect()) {
+            let back = WorldRectangle::from_screen_rect(&size, &ScreenRectangle::from_world_rect(&size, &r));
+            prop_assert!(close(back.pos.x, r.pos.x) && close(back.pos.y, r.pos.y));
+            prop_assert!(close(back.width, r.width) && close(back.height, r.height));
+        }
+
+        #[test]
+        fn panel_round_trip(r in world_rect(), u in -1.0f32..2.0, v in -1.0f32..2.0) {
+            let p = r.from_panel(&PanelPoint::new(u, v));
+            let back = r.to_panel(&p);
+            prop_assert!(close(back.u, u) && close(back.v, v));
+            let margin = 0.01;
+            if u > margin && u < 1.0 - margin && v > margin && v < 1.0 - margin {
+                prop_assert!(r.contains(&p));
+            }
+            if u < -margin || u > 1.0 + margin || v < -margin || v > 1.0 + margin {
+                prop_assert!(!r.contains(&p));
+            }
+        }
+
+        #[test]
+        fn panel_agrees_between_spaces(size in screen_size(), r in world_rect(), u in 0.0f32..1.0, v in 0.0f32..1.0) {
+            // a panel-local point is the same place whether it's resolved in world or screen space
+            let sr = ScreenRectangle::from_world_rect(&size, &r);
+            let via_world = ScreenPoint::from_world_point(&size, &r.from_panel(&PanelPoint::new(u, v)));
+            let via_screen = sr.from_panel(&PanelPoint::new(u, v));
+            prop_assert!(close(via_world.x, via_screen.x) && close(via_world.y, via_screen.y));
+        }
+
+        #[test]
+        fn intersection_is_contained(a in world_rect(), b in world_rect(), u in 0.0f32..1.0, v in 0.0f32..1.0) {
+            if let Some(i) = a.intersection(&b) {
+                let p = i.from_panel(&PanelPoint::new(u, v));
+                let grow = |r: &WorldRectangle| WorldRectangle::new(
+                    WorldPoint::new(r.pos.x - EPS, r.pos.y + EPS, 0.0), r.width + 2.0 * EPS, r.height + 2.0 * EPS);
+                prop_assert!(grow(&a).contains(&p) && grow(&b).contains(&p));
+            }
+        }
+
+        #[test]
+        fn union_contains_both(a in world_rect(), b in world_rect()) {
+            let un = a.union(&b);
+            for r in [&a, &b] {
+                // right/bottom are derived from width/height, so allow for rounding
+                prop_assert!(un.left() <= r.left() && un.right() + EPS >= r.right());
+                prop_assert!(un.top() >= r.top() && un.bottom() <= r.bottom() + EPS);
+            }
+        }
+    }
+}
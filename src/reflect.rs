@@ -0,0 +1,481 @@
+use anyhow::*;
+use crate::hotreload;
+use crate::pipelinedesc::{BindGroupDesc, BindingDesc, BindingKind, SampleKind, ShaderStage, StepMode, VertexAttributeDesc, VertexBufferDesc, VertexFormat, ViewDimension};
+
+/// A resource a shader declares with `[[group(g), binding(b)]]`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReflectedBinding {
+    /// The variable's name, used to address it in `State::create_bindgroup_named`.
+    pub name: String,
+    pub group: u32,
+    pub binding: u32,
+    /// The stages whose entry points use it.
+    pub visibility: Vec<ShaderStage>,
+    /// Uniform buffers have the size of their struct as `min_size`.
+    pub ty: BindingKind,
+}
+
+impl ReflectedBinding {
+    /// The size of a uniform buffer's struct.
+    pub fn uniform_size(&self) -> Option<u64> {
+        match self.ty {
+            BindingKind::Uniform { min_size, .. } => min_size,
+            _ => None,
+        }
+    }
+
+    pub fn to_binding_desc(&self) -> BindingDesc {
+        BindingDesc {
+            binding: self.binding,
+            // a declared but unused binding still has to be in the layout
+            visibility: if self.visibility.is_empty() { vec![ShaderStage::Vertex, ShaderStage::Fragment] } else { self.visibility.clone() },
+            ty: self.ty.clone(),
+        }
+    }
+}
+
+/// A `[[location(n)]]` input of a vertex entry point.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VertexInput {
+    pub name: String,
+    pub location: u32,
+    pub format: VertexFormat,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReflectedEntryPoint {
+    pub name: String,
+    pub stage: ShaderStage,
+    /// Sorted by location, only for vertex entry points.
+    pub inputs: Vec<VertexInput>,
+}
+
+/// What a WGSL shader declares: it's entry points, their vertex inputs and it's resource bindings.
+#[derive(Clone, Debug, Default)]
+pub struct ShaderReflection {
+    pub entry_points: Vec<ReflectedEntryPoint>,
+    /// Sorted by group, then binding.
+    pub bindings: Vec<ReflectedBinding>,
+}
+
+impl ShaderReflection {
+    pub fn from_wgsl(source: &str) -> Result<Self> {
+        let (module, info) = hotreload::parse_wgsl(source)?;
+        Self::from_module(&module, &info)
+    }
+
+    /// Reflect a validated module.
+    /// * Fails on storage textures, which `BindingKind` can't describe yet.
+    pub fn from_module(module: &naga::Module, info: &naga::valid::ModuleInfo) -> Result<Self> {
+        let mut layouter = naga::proc::Layouter::default();
+        layouter.update(&module.types, &module.constants).map_err(|e| anyhow!("can't lay out the shader's types: {:?}", e))?;
+
+        let stages: Vec<ShaderStage> = module.entry_points.iter().map(|e| stage(e.stage)).collect();
+        let mut bindings = vec![];
+        for (handle, var) in module.global_variables.iter() {
+            let resource = match &var.binding {
+                Some(b) => b,
+                None => continue,
+            };
+            let name = var.name.clone().unwrap_or_default();
+            let mut visibility = vec![];
+            for (i, s) in stages.iter().enumerate() {
+                if !info.get_entry_point(i)[handle].is_empty() && !visibility.contains(s) {
+                    visibility.push(*s);
+                }
+            }
+            let ty = match (&var.class, &module.types[var.ty].inner) {
+                (naga::StorageClass::Uniform, _) => BindingKind::Uniform {
+                    dynamic: false,
+                    min_size: Some(layouter[var.ty].size as u64),
+                },
+                (naga::StorageClass::Storage { access }, _) => BindingKind::Storage {
+                    read_only: !access.contains(naga::StorageAccess::STORE),
+                    dynamic: false,
+                },
+                (_, naga::TypeInner::Image { dim, arrayed, class }) => {
+                    let dimension = view_dimension(*dim, *arrayed)
+                        .ok_or_else(|| anyhow!("`{}` is an arrayed {:?} texture, which WebGPU doesn't have", name, dim))?;
+                    match class {
+                        naga::ImageClass::Sampled { kind, multi } => BindingKind::Texture {
+                            sample: match kind {
+                                naga::ScalarKind::Sint => SampleKind::Sint,
+                                naga::ScalarKind::Uint => SampleKind::Uint,
+                                _ => SampleKind::Float,
+                            },
+                            dimension,
+                            multisampled: *multi,
+                        },
+                        naga::ImageClass::Depth { multi } => BindingKind::Texture {
+                            sample: SampleKind::Depth,
+                            dimension,
+                            multisampled: *multi,
+                        },
+                        naga::ImageClass::Storage { .. } => bail!("`{}` is a storage texture, which can't be reflected yet", name),
+                    }
+                }
+                (_, naga::TypeInner::Sampler { comparison }) => BindingKind::Sampler {
+                    filtering: true,
+                    comparison: *comparison,
+                },
+                (class, _) => bail!("`{}` has a binding but is in the {:?} storage class", name, class),
+            };
+            bindings.push(ReflectedBinding {
+                name,
+                group: resource.group,
+                binding: resource.binding,
+                visibility,
+                ty,
+            });
+        }
+        bindings.sort_by_key(|b| (b.group, b.binding));
+
+        let mut entry_points = vec![];
+        for e in &module.entry_points {
+            let mut inputs = vec![];
+            if e.stage == naga::ShaderStage::Vertex {
+                for arg in &e.function.arguments {
+                    let name = arg.name.as_deref().unwrap_or_default();
+                    match (&arg.binding, &module.types[arg.ty].inner) {
+                        (Some(binding), inner) => push_input(&mut inputs, name, binding, inner)?,
+                        (None, naga::TypeInner::Struct { members, .. }) => {
+                            for m in members {
+                                if let Some(binding) = &m.binding {
+                                    push_input(&mut inputs, m.name.as_deref().unwrap_or_default(), binding, &module.types[m.ty].inner)?;
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                inputs.sort_by_key(|i| i.location);
+            }
+            entry_points.push(ReflectedEntryPoint {
+                name: e.name.clone(),
+                stage: stage(e.stage),
+                inputs,
+            });
+        }
+
+        Ok(Self { entry_points, bindings })
+    }
+
+    pub fn entry_point(&self, stage: ShaderStage, name: &str) -> Option<&ReflectedEntryPoint> {
+        self.entry_points.iter().find(|e| e.stage == stage && e.name == name)
+    }
+
+    /// Like `entry_point`, failing with the names the shader does have.
+    pub fn check_entry_point(&self, stage: ShaderStage, name: &str) -> Result<&ReflectedEntryPoint> {
+        self.entry_point(stage, name).ok_or_else(|| {
+            let names: Vec<&str> = self.entry_points.iter().filter(|e| e.stage == stage).map(|e| e.name.as_str()).collect();
+            anyhow!("the shader has no {:?} entry point named `{}`, it has {:?}", stage, name, names)
+        })
+    }
+
+    pub fn binding(&self, name: &str) -> Option<&ReflectedBinding> {
+        self.bindings.iter().find(|b| b.name == name)
+    }
+
+    pub fn group(&self, group: u32) -> impl Iterator<Item = &ReflectedBinding> {
+        self.bindings.iter().filter(move |b| b.group == group)
+    }
+
+    /// One past the highest group the shader uses.
+    pub fn group_count(&self) -> u32 {
+        self.bindings.iter().map(|b| b.group + 1).max().unwrap_or(0)
+    }
+
+    /// A layout for every group up to `group_count`, with the bindings the shader declares.
+    /// * Uniform buffers never get a dynamic offset here, so `BindGroupDesc::Transforms` has to be given by hand.
+    pub fn bind_groups(&self) -> Vec<BindGroupDesc> {
+        (0..self.group_count())
+            .map(|g| BindGroupDesc::Entries(self.group(g).map(ReflectedBinding::to_binding_desc).collect()))
+            .collect()
+    }
+
+    /// One tightly packed per-vertex buffer with every input of a vertex entry point, as 32-bit attributes.
+    pub fn vertex_buffer(&self, entry_point: &str) -> Option<VertexBufferDesc> {
+        let e = self.entry_point(ShaderStage::Vertex, entry_point)?;
+        if e.inputs.is_empty() {
+            return None;
+        }
+        Some(VertexBufferDesc {
+            step: StepMode::Vertex,
+            stride: None,
+            attributes: e.inputs.iter().map(|i| VertexAttributeDesc {
+                location: i.location,
+                format: i.format,
+                offset: None,
+            }).collect(),
+        })
+    }
+
+    /// Check that a layout for `group` has every binding the shader uses there, of a matching kind and visibility.
+    pub fn check_layout(&self, group: u32, entries: &[wgpu::BindGroupLayoutEntry]) -> Result<()> {
+        for b in self.group(group) {
+            let entry = entries.iter().find(|e| e.binding == b.binding)
+                .ok_or_else(|| anyhow!("`{}` at group({}) binding({}) is missing from the layout", b.name, group, b.binding))?;
+            let expected = b.to_binding_desc().to_wgpu();
+            if !b.visibility.is_empty() && !entry.visibility.contains(expected.visibility) {
+                bail!("`{}` is used by {:?}, but the layout only makes it visible to {:?}", b.name, b.visibility, entry.visibility);
+            }
+            if !compatible(&entry.ty, &expected.ty) {
+                bail!("`{}` is a {} in the shader, but the layout has a {}", b.name, describe(&expected.ty), describe(&entry.ty));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn stage(stage: naga::ShaderStage) -> ShaderStage {
+    match stage {
+        naga::ShaderStage::Vertex => ShaderStage::Vertex,
+        naga::ShaderStage::Fragment => ShaderStage::Fragment,
+        naga::ShaderStage::Compute => ShaderStage::Compute,
+    }
+}
+
+fn view_dimension(dim: naga::ImageDimension, arrayed: bool) -> Option<ViewDimension> {
+    Some(match (dim, arrayed) {
+        (naga::ImageDimension::D1, false) => ViewDimension::D1,
+        (naga::ImageDimension::D2, false) => ViewDimension::D2,
+        (naga::ImageDimension::D2, true) => ViewDimension::D2Array,
+        (naga::ImageDimension::D3, false) => ViewDimension::D3,
+        (naga::ImageDimension::Cube, false) => ViewDimension::Cube,
+        (naga::ImageDimension::Cube, true) => ViewDimension::CubeArray,
+        _ => return None,
+    })
+}
+
+fn push_input(inputs: &mut Vec<VertexInput>, name: &str, binding: &naga::Binding, inner: &naga::TypeInner) -> Result<()> {
+    if let naga::Binding::Location { location, .. } = binding {
+        let format = vertex_format(inner).ok_or_else(|| anyhow!("vertex input `{}` has a type no vertex format matches", name))?;
+        inputs.push(VertexInput {
+            name: name.to_string(),
+            location: *location,
+            format,
+        });
+    }
+    Ok(())
+}
+
+fn vertex_format(inner: &naga::TypeInner) -> Option<VertexFormat> {
+    use naga::ScalarKind as K;
+    let (size, kind) = match *inner {
+        naga::TypeInner::Scalar { kind, width: 4 } => (1, kind),
+        naga::TypeInner::Vector { size, kind, width: 4 } => (size as u8, kind),
+        _ => return None,
+    };
+    Some(match (kind, size) {
+        (K::Float, 1) => VertexFormat::Float32,
+        (K::Float, 2) => VertexFormat::Float32x2,
+        (K::Float, 3) => VertexFormat::Float32x3,
+        (K::Float, 4) => VertexFormat::Float32x4,
+        (K::Uint, 1) => VertexFormat::Uint32,
+        (K::Uint, 2) => VertexFormat::Uint32x2,
+        (K::Uint, 3) => VertexFormat::Uint32x3,
+        (K::Uint, 4) => VertexFormat::Uint32x4,
+        (K::Sint, 1) => VertexFormat::Sint32,
+        (K::Sint, 2) => VertexFormat::Sint32x2,
+        (K::Sint, 3) => VertexFormat::Sint32x3,
+        (K::Sint, 4) => VertexFormat::Sint32x4,
+        _ => return None,
+    })
+}
+
+/// Whether a layout's binding can hold what the shader declares.
+fn compatible(layout: &wgpu::BindingType, shader: &wgpu::BindingType) -> bool {
+    use wgpu::BindingType as B;
+    use wgpu::BufferBindingType as Buf;
+    match (layout, shader) {
+        (B::Buffer { ty: have, min_binding_size: have_size, .. }, B::Buffer { ty: need, min_binding_size: need_size, .. }) => {
+            let kind = match (have, need) {
+                (Buf::Uniform, Buf::Uniform) => true,
+                // a read-only layout can't hold a buffer the shader writes
+                (Buf::Storage { read_only: have }, Buf::Storage { read_only: need }) => !*have || *need,
+                _ => false,
+            };
+            let size = match (have_size, need_size) {
+                (Some(have), Some(need)) => have >= need,
+                _ => true,
+            };
+            kind && size
+        }
+        (B::Texture { sample_type: a, view_dimension: ad, multisampled: am }, B::Texture { sample_type: b, view_dimension: bd, multisampled: bm }) => {
+            std::mem::discriminant(a) == std::mem::discriminant(b) && ad == bd && am == bm
+        }
+        (B::Sampler { comparison: a, .. }, B::Sampler { comparison: b, .. }) => a == b,
+        _ => false,
+    }
+}
+
+pub(crate) fn describe(ty: &wgpu::BindingType) -> String {
+    match ty {
+        wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Uniform, min_binding_size: Some(size), .. } => format!("uniform buffer of {} bytes", size),
+        wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Uniform, .. } => String::from("uniform buffer"),
+        wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Storage { read_only: true }, .. } => String::from("read-only storage buffer"),
+        wgpu::BindingType::Buffer { .. } => String::from("storage buffer"),
+        wgpu::BindingType::Texture { sample_type, view_dimension, multisampled } => {
+            format!("{}{:?} texture of {:?}", if *multisampled { "multisampled " } else { "" }, view_dimension, sample_type)
+        }
+        wgpu::BindingType::Sampler { comparison: true, .. } => String::from("comparison sampler"),
+        wgpu::BindingType::Sampler { .. } => String::from("sampler"),
+        wgpu::BindingType::StorageTexture { .. } => String::from("storage texture"),
+    }
+}
+
+/// A resource for one binding of `State::create_bindgroup_named`, checked against the shader's declaration.
+pub enum BindResource<'a> {
+    /// A texture from `State::textures`, checked for it's dimension and sample type.
+    Texture(usize),
+    /// The sampler of a texture from `State::textures`.
+    Sampler(usize),
+    /// A value from `State::uniforms` (`UniformId::index`), checked to be at least as big as the shader's struct.
+    Uniform(usize),
+    /// A buffer from `State::storage_buffers`.
+    Storage(usize),
+    /// Anything else, only checked to be the right kind of resource.
+    Resource(wgpu::BindingResource<'a>),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHADER: &str = r#"
+[[block]]
+struct Globals {
+    time: f32;
+    color: vec4<f32>;
+};
+
+[[block]]
+struct Data {
+    values: array<f32>;
+};
+
+[[group(0), binding(0)]] var t: texture_2d<f32>;
+[[group(0), binding(1)]] var s: sampler;
+[[group(1), binding(0)]] var<uniform> globals: Globals;
+[[group(2), binding(0)]] var<storage, read> data: Data;
+
+struct VertexInput {
+    [[location(1)]] uv: vec2<f32>;
+    [[location(0)]] position: vec3<f32>;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] clip: vec4<f32>;
+    [[location(0)]] uv: vec2<f32>;
+};
+
+[[stage(vertex)]]
+fn main(input: VertexInput, [[location(2)]] index: u32) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip = vec4<f32>(input.position * globals.time, 1.0);
+    out.uv = input.uv;
+    return out;
+}
+
+[[stage(fragment)]]
+fn fs_main(input: VertexOutput) -> [[location(0)]] vec4<f32> {
+    return textureSample(t, s, input.uv) * globals.color * data.values[0];
+}
+"#;
+
+    fn layout(r: &ShaderReflection, group: u32) -> Vec<wgpu::BindGroupLayoutEntry> {
+        r.group(group).map(|b| b.to_binding_desc().to_wgpu()).collect()
+    }
+
+    #[test]
+    fn reflects_bindings() {
+        let r = ShaderReflection::from_wgsl(SHADER).unwrap();
+        let names: Vec<(&str, u32, u32)> = r.bindings.iter().map(|b| (b.name.as_str(), b.group, b.binding)).collect();
+        assert_eq!(names, vec![("t", 0, 0), ("s", 0, 1), ("globals", 1, 0), ("data", 2, 0)]);
+        assert_eq!(r.group_count(), 3);
+        assert_eq!(r.bind_groups().len(), 3);
+
+        let globals = r.binding("globals").unwrap();
+        assert_eq!(globals.visibility, vec![ShaderStage::Vertex, ShaderStage::Fragment]);
+        // the vec4 is 16-byte aligned, after the f32
+        assert_eq!(globals.uniform_size(), Some(32));
+        assert_eq!(r.binding("t").unwrap().visibility, vec![ShaderStage::Fragment]);
+        assert_eq!(r.binding("t").unwrap().ty, BindingKind::Texture { sample: SampleKind::Float, dimension: ViewDimension::D2, multisampled: false });
+        assert_eq!(r.binding("data").unwrap().ty, BindingKind::Storage { read_only: true, dynamic: false });
+        assert_eq!(r.binding("s").unwrap().uniform_size(), None);
+    }
+
+    #[test]
+    fn reflects_vertex_inputs() {
+        let r = ShaderReflection::from_wgsl(SHADER).unwrap();
+        let formats: Vec<(&str, u32, VertexFormat)> = r.entry_point(ShaderStage::Vertex, "main").unwrap()
+            .inputs.iter().map(|i| (i.name.as_str(), i.location, i.format)).collect();
+        assert_eq!(formats, vec![("position", 0, VertexFormat::Float32x3), ("uv", 1, VertexFormat::Float32x2), ("index", 2, VertexFormat::Uint32)]);
+
+        let buffer = r.vertex_buffer("main").unwrap();
+        assert_eq!(buffer.attributes.iter().map(|a| (a.location, a.format)).collect::<Vec<_>>(),
+            vec![(0, VertexFormat::Float32x3), (1, VertexFormat::Float32x2), (2, VertexFormat::Uint32)]);
+        assert!(r.vertex_buffer("fs_main").is_none());
+        assert!(r.vertex_buffer("nope").is_none());
+
+        assert!(r.check_entry_point(ShaderStage::Fragment, "fs_main").is_ok());
+        let err = r.check_entry_point(ShaderStage::Vertex, "vs_main").unwrap_err().to_string();
+        assert!(err.contains("no Vertex entry point named `vs_main`, it has [\"main\"]"), "{}", err);
+    }
+
+    #[test]
+    fn checks_layouts() {
+        let r = ShaderReflection::from_wgsl(SHADER).unwrap();
+        for g in 0..3 {
+            r.check_layout(g, &layout(&r, g)).unwrap();
+        }
+
+        let message = |group: u32, entries: &[wgpu::BindGroupLayoutEntry]| r.check_layout(group, entries).unwrap_err().to_string();
+        let mut entries = layout(&r, 0);
+        assert!(message(0, &entries[..1]).contains("`s` at group(0) binding(1) is missing from the layout"));
+
+        entries[0].visibility = wgpu::ShaderStages::VERTEX;
+        assert!(message(0, &entries).contains("`t` is used by [Fragment]"));
+
+        let mut entries = layout(&r, 0);
+        entries[1].ty = wgpu::BindingType::Sampler { filtering: true, comparison: true };
+        assert!(message(0, &entries).contains("`s` is a sampler in the shader, but the layout has a comparison sampler"));
+
+        let mut entries = layout(&r, 1);
+        entries[0].ty = wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Uniform, has_dynamic_offset: false, min_binding_size: wgpu::BufferSize::new(16) };
+        assert!(message(1, &entries).contains("uniform buffer of 32 bytes in the shader, but the layout has a uniform buffer of 16 bytes"));
+    }
+
+    #[test]
+    fn compatible_bindings() {
+        let buffer = |ty, size| wgpu::BindingType::Buffer { ty, has_dynamic_offset: false, min_binding_size: wgpu::BufferSize::new(size) };
+        let uniform = wgpu::BufferBindingType::Uniform;
+        let read_only = wgpu::BufferBindingType::Storage { read_only: true };
+        let writable = wgpu::BufferBindingType::Storage { read_only: false };
+
+        assert!(compatible(&buffer(uniform, 64), &buffer(uniform, 32)));
+        assert!(!compatible(&buffer(uniform, 16), &buffer(uniform, 32)));
+        // no size in the layout is checked when binding instead
+        assert!(compatible(&buffer(uniform, 0), &buffer(uniform, 32)));
+        assert!(compatible(&buffer(writable, 0), &buffer(read_only, 0)));
+        assert!(!compatible(&buffer(read_only, 0), &buffer(writable, 0)));
+        assert!(!compatible(&buffer(uniform, 0), &buffer(read_only, 0)));
+
+        let texture = |sample_type, view_dimension| wgpu::BindingType::Texture { sample_type, view_dimension, multisampled: false };
+        let float = wgpu::TextureSampleType::Float { filterable: true };
+        // filterability isn't part of the shader's declaration
+        assert!(compatible(&texture(wgpu::TextureSampleType::Float { filterable: false }, wgpu::TextureViewDimension::D2), &texture(float, wgpu::TextureViewDimension::D2)));
+        assert!(!compatible(&texture(float, wgpu::TextureViewDimension::Cube), &texture(float, wgpu::TextureViewDimension::D2)));
+        assert!(!compatible(&texture(wgpu::TextureSampleType::Uint, wgpu::TextureViewDimension::D2), &texture(float, wgpu::TextureViewDimension::D2)));
+        assert!(!compatible(&texture(float, wgpu::TextureViewDimension::D2), &buffer(uniform, 0)));
+    }
+
+    #[test]
+    fn reflection_errors() {
+        assert!(ShaderReflection::from_wgsl("fn main( {").is_err());
+        let storage = "[[group(0), binding(0)]] var img: texture_storage_2d<rgba8unorm, write>;\n\
+            [[stage(fragment)]]\nfn main() -> [[location(0)]] vec4<f32> {\n    textureStore(img, vec2<i32>(0, 0), vec4<f32>(1.0));\n    return vec4<f32>(1.0);\n}\n";
+        let err = ShaderReflection::from_wgsl(storage).unwrap_err().to_string();
+        assert!(err.contains("`img` is a storage texture"), "{}", err);
+    }
+}
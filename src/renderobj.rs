@@ -1,7 +1,10 @@
+use crate::atlas::TextureAtlas;
 use crate::rect::WorldPoint;
 use crate::transform2d::Transform2D;
 use crate::wgpustate::State;
 use crate::modelbuffers::Model;
+use crate::transformbuffer::ObjectUniform;
+use crate::uniform::UniformId;
 use wgpu::Buffer;
 
 pub struct Position {
@@ -55,53 +58,137 @@ impl Position {
 
 /// Data for a renderable object.
 pub struct RenderObject {
+    /// The translation of `transform`, kept in sync by `set_position` and `set_transform`.
     pub position: WorldPoint,
+    /// Use `set_transform` or `set_position` to change this, so that it is uploaded.
+    pub transform: Transform2D,
     pub pipeline: usize,
     pub bind_group: usize,
     pub model: usize,
+    /// The part of the texture drawn on the model as `[u, v, width, height]`, see `set_uv_rect`.
+    pub uv_rect: [f32; 4],
+    /// A tint multiplied with the texture, see `set_color`.
+    pub color: [f32; 4],
+    /// This object's slot in the `State`'s `TransformBuffer`.
+    pub transform_slot: usize,
+    /// `State::uniforms` slots (`UniformId::index`) bound at `group(3)` onward by `render_this`, see `add_uniform`.
     pub uniforms: Vec<usize>,
+    /// Transparent objects are drawn after opaque ones, back-to-front, by a `RenderQueue`.
+    pub transparent: bool,
 }
 
 impl RenderObject {
 
+    /// Creates an object using the default pipeline, bind group and model, with it's own transform slot.
     pub fn new(state: &mut State) -> Self {
-        // create/choose pipeline
-        // create/choose bindgroup
-        // create/choose model
-        // create uniforms
+        let transform = Transform2D::identity();
+        let transform_slot = state.transforms.allocate(&state.device, &state.queue, &ObjectUniform::from_transform(&transform));
 
         Self {
             position: WorldPoint::new(0.0,0.0,0.0),
+            transform,
             pipeline: 0,
             bind_group: 0,
             model: 0,
+            uv_rect: ObjectUniform::FULL_UV,
+            color: ObjectUniform::WHITE,
+            transform_slot,
             uniforms: vec![],
+            transparent: false,
         }
-        
+
     }
 
-    pub fn new_placeholder_rect() -> Self {
-        RenderObject {
-            // default 0.0,0.0 position
-            position: WorldPoint::new(0.0, 0.0, 0.0),
-            // default textured verticies pipeline/shader
-            pipeline: 0,
-            // default tree_texure and position uniforms
-            bind_group: 0,
-            // default rect model (vert/index buffer)
-            model: 0,
-            uniforms: vec![],
+    pub fn new_placeholder_rect(state: &mut State) -> Self {
+        // default textured verticies pipeline/shader,
+        // default tree_texure bindgroup,
+        // and default rect model (vert/index buffer)
+        Self::new(state)
+    }
+
+    /// Move the object, keeping it's rotation and scale, and upload it's new transform.
+    pub fn set_position(&mut self, state: &State, position: WorldPoint) {
+        self.position = position;
+        self.transform.set_translation(position.x(), position.y());
+        self.transform.depth = position.z();
+        self.upload_transform(state);
+    }
+
+    /// Replace the object's transform and upload it.
+    pub fn set_transform(&mut self, state: &State, transform: Transform2D) {
+        let [x, y] = transform.translation();
+        self.position = WorldPoint::new(x, y, transform.depth);
+        self.transform = transform;
+        self.upload_transform(state);
+    }
+
+    /// Draw only part of the texture, given in normalized texture coordinates, and upload it.
+    pub fn set_uv_rect(&mut self, state: &State, uv_min: [f32; 2], uv_max: [f32; 2]) {
+        self.uv_rect = [uv_min[0], uv_min[1], uv_max[0] - uv_min[0], uv_max[1] - uv_min[1]];
+        self.upload_transform(state);
+    }
+
+    /// Tint the object and upload it.
+    pub fn set_color(&mut self, state: &State, color: [f32; 4]) {
+        self.color = color;
+        self.upload_transform(state);
+    }
+
+    /// Draw a sprite from a `TextureAtlas`, switching to it's page's bind group.
+    pub fn set_sprite(&mut self, state: &State, atlas: &TextureAtlas, sprite: usize) {
+        let region = atlas.region(sprite);
+        self.bind_group = atlas.page_bind_group(region.page);
+        self.set_uv_rect(state, region.uv_min, region.uv_max);
+    }
+
+    /// Upload `transform`, `uv_rect` and `color` to this object's slot, needed after mutating them directly.
+    pub fn upload_transform(&self, state: &State) {
+        state.transforms.write(&state.queue, self.transform_slot, &ObjectUniform::new(&self.transform, self.uv_rect, self.color));
+    }
+
+    /// Maps a world point into the object's model space, e.g. to test it against the rect model's -1..1 bounds.
+    pub fn world_to_local(&self, point: &WorldPoint) -> Option<WorldPoint> {
+        self.transform.inverse_transform_point(point)
+    }
+
+    /// Bind a uniform at the next group after `group(2)`.
+    /// * Fails when the device has no bind group left for it; the default limit of 4 groups leaves room for one.
+    pub fn add_uniform<T>(&mut self, state: &State, uniform: UniformId<T>) -> anyhow::Result<()> {
+        let group = 3 + self.uniforms.len() as u32;
+        let max = state.device.limits().max_bind_groups;
+        if group >= max {
+            anyhow::bail!("Can't bind another uniform at group({}), the device allows {} bind groups and `group(0..3)` are used by every object", group, max);
         }
+        self.uniforms.push(uniform.index());
+        Ok(())
+    }
+
+    /// Frees the object's transform slot, so it can be reused.
+    pub fn release(self, state: &mut State) {
+        state.transforms.release(self.transform_slot);
     }
 
     pub fn render_this<'a>(&self, state: &'a State, render_pass: &mut wgpu::RenderPass<'a>) {
 
         let my_model = &state.models[self.model];
 
-        render_pass.set_pipeline(&state.render_pipelines[self.pipeline]);
+        // a pipeline that failed to build for this target is logged by `State::prepare_pipelines`
+        let pipeline = match state.render_pipeline(self.pipeline) {
+            Some(p) => p,
+            None => return,
+        };
+        render_pass.set_pipeline(pipeline);
         render_pass.set_bind_group(0, &state.bind_groups[self.bind_group], &[]);
+        render_pass.set_bind_group(1, &state.transforms.bind_group, &[state.transforms.offset(self.transform_slot)]);
+        render_pass.set_bind_group(2, &state.cameras[state.pass.camera].bind_group, &[]);
+        // `add_uniform` keeps within the limit, but the field can be pushed to directly
+        let max = state.device.limits().max_bind_groups.saturating_sub(3) as usize;
+        debug_assert!(self.uniforms.len() <= max, "{} uniforms on a RenderObject, the device only has room for {}", self.uniforms.len(), max);
+        for (i, uniform) in self.uniforms.iter().take(max).enumerate() {
+            render_pass.set_bind_group(3 + i as u32, state.uniforms.slots[*uniform].bind_group(), &[]);
+        }
         render_pass.set_vertex_buffer(0, my_model.vertex_buffer.slice(..));
-        render_pass.set_index_buffer(my_model.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        render_pass.set_index_buffer(my_model.index_buffer.slice(..), my_model.index_format);
         render_pass.draw_indexed(0..my_model.num_indices, 0, 0..1);
     }
 }
\ No newline at end of file
@@ -0,0 +1,227 @@
+use crate::renderobj::RenderObject;
+use crate::wgpustate::State;
+
+/// The state needed to draw one `RenderObject`, copied out at submit time.
+#[derive(Clone, Debug)]
+struct DrawItem {
+    pipeline: usize,
+    bind_group: usize,
+    model: usize,
+    /// `State::uniforms` slots bound at `group(3)` onward, like `RenderObject::render_this` does.
+    uniforms: Vec<usize>,
+    transform: usize,
+    depth: f32,
+    transparent: bool,
+}
+
+impl DrawItem {
+    fn state_key(&self) -> (usize, usize, usize, &[usize]) {
+        (self.pipeline, self.bind_group, self.model, &self.uniforms)
+    }
+}
+
+/// Counters describing the work done by the last `RenderQueue::flush`.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct RenderQueueStats {
+    pub draws: usize,
+    pub pipeline_changes: usize,
+    pub bind_group_changes: usize,
+    pub model_changes: usize,
+    /// Objects left out because their pipeline couldn't be built for the target.
+    pub skipped: usize,
+}
+
+/// Collects `RenderObject`s during a `ProgramHook::render` call and draws them in an order
+/// that keeps `RenderPass` state changes to a minimum.
+/// * Opaque objects are grouped by pipeline, then bind group, then model.
+/// * Transparent objects are drawn after every opaque object, back-to-front by their depth (`position.z`).
+pub struct RenderQueue {
+    items: Vec<DrawItem>,
+}
+
+impl Default for RenderQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RenderQueue {
+    pub fn new() -> Self {
+        Self {
+            items: vec![],
+        }
+    }
+
+    /// Queue a `RenderObject` to be drawn on the next `flush`.
+    pub fn submit(&mut self, obj: &RenderObject) {
+        self.items.push(DrawItem {
+            pipeline: obj.pipeline,
+            bind_group: obj.bind_group,
+            model: obj.model,
+            uniforms: obj.uniforms.clone(),
+            transform: obj.transform_slot,
+            depth: obj.position.z(),
+            transparent: obj.transparent,
+        });
+    }
+
+    /// Queue every `RenderObject` in the iterator.
+    pub fn submit_all<'o>(&mut self, objs: impl IntoIterator<Item = &'o RenderObject>) {
+        for obj in objs {
+            self.submit(obj);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Drop everything that was submitted without drawing it.
+    pub fn clear(&mut self) {
+        self.items.clear();
+    }
+
+    /// Sort the submitted objects, record them into the `RenderPass` and empty the queue.
+    pub fn flush<'a>(&mut self, state: &'a State, render_pass: &mut wgpu::RenderPass<'a>) -> RenderQueueStats {
+        sort_items(&mut self.items);
+
+        let mut stats = RenderQueueStats::default();
+        let mut last_pipeline: Option<usize> = None;
+        let mut last_bind_group: Option<usize> = None;
+        let mut last_model: Option<usize> = None;
+        let mut last_uniforms: Option<Vec<usize>> = None;
+        let max_uniforms = state.device.limits().max_bind_groups.saturating_sub(3) as usize;
+
+        for item in self.items.drain(..) {
+            if last_pipeline != Some(item.pipeline) {
+                match state.render_pipeline(item.pipeline) {
+                    Some(pipeline) => render_pass.set_pipeline(pipeline),
+                    None => {
+                        stats.skipped += 1;
+                        continue;
+                    }
+                }
+                render_pass.set_bind_group(2, &state.cameras[state.pass.camera].bind_group, &[]);
+                last_pipeline = Some(item.pipeline);
+                stats.pipeline_changes += 1;
+            }
+
+            if last_bind_group != Some(item.bind_group) {
+                render_pass.set_bind_group(0, &state.bind_groups[item.bind_group], &[]);
+                last_bind_group = Some(item.bind_group);
+                stats.bind_group_changes += 1;
+            }
+
+            if last_uniforms.as_ref() != Some(&item.uniforms) {
+                for (i, uniform) in item.uniforms.iter().take(max_uniforms).enumerate() {
+                    render_pass.set_bind_group(3 + i as u32, state.uniforms.slots[*uniform].bind_group(), &[]);
+                }
+                last_uniforms = Some(item.uniforms);
+            }
+
+            let model = &state.models[item.model];
+            if last_model != Some(item.model) {
+                render_pass.set_vertex_buffer(0, model.vertex_buffer.slice(..));
+                render_pass.set_index_buffer(model.index_buffer.slice(..), model.index_format);
+                last_model = Some(item.model);
+                stats.model_changes += 1;
+            }
+
+            // every object has it's own transform slot, selected by dynamic offset
+            render_pass.set_bind_group(1, &state.transforms.bind_group, &[state.transforms.offset(item.transform)]);
+            render_pass.draw_indexed(0..model.num_indices, 0, 0..1);
+            stats.draws += 1;
+        }
+
+        stats
+    }
+}
+
+/// Opaque items grouped by state, then transparent items back-to-front.
+fn sort_items(items: &mut [DrawItem]) {
+    items.sort_by(|a, b| {
+        match (a.transparent, b.transparent) {
+            (false, false) => a.state_key().cmp(&b.state_key()),
+            (false, true) => std::cmp::Ordering::Less,
+            (true, false) => std::cmp::Ordering::Greater,
+            // farthest first, falling back to the state key so equal depths still batch
+            (true, true) => b.depth
+                .partial_cmp(&a.depth)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| a.state_key().cmp(&b.state_key())),
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(pipeline: usize, bind_group: usize, model: usize, depth: f32, transparent: bool) -> DrawItem {
+        DrawItem { pipeline, bind_group, model, uniforms: vec![], transform: 0, depth, transparent }
+    }
+
+    fn sorted(mut items: Vec<DrawItem>) -> Vec<DrawItem> {
+        sort_items(&mut items);
+        items
+    }
+
+    /// How many times each part of the state changes when drawing the items in order.
+    fn changes(items: &[DrawItem]) -> [usize; 3] {
+        let mut counts = [0; 3];
+        for (i, item) in items.iter().enumerate() {
+            let prev = i.checked_sub(1).map(|p| &items[p]);
+            counts[0] += (prev.map(|p| p.pipeline) != Some(item.pipeline)) as usize;
+            counts[1] += (prev.map(|p| p.bind_group) != Some(item.bind_group)) as usize;
+            counts[2] += (prev.map(|p| p.model) != Some(item.model)) as usize;
+        }
+        counts
+    }
+
+    #[test]
+    fn opaque_items_are_grouped_by_state() {
+        let items = sorted(vec![
+            item(1, 0, 0, 0.0, false),
+            item(0, 1, 0, 0.5, false),
+            item(1, 0, 1, 0.2, false),
+            item(0, 0, 0, 0.9, false),
+            item(1, 0, 0, 0.1, false),
+            item(0, 1, 0, 0.3, false),
+        ]);
+        let keys: Vec<(usize, usize, usize)> = items.iter().map(|i| (i.pipeline, i.bind_group, i.model)).collect();
+        assert_eq!(keys, vec![(0, 0, 0), (0, 1, 0), (0, 1, 0), (1, 0, 0), (1, 0, 0), (1, 0, 1)]);
+        assert_eq!(changes(&items), [2, 3, 2]);
+    }
+
+    #[test]
+    fn transparent_items_come_last_back_to_front() {
+        let items = sorted(vec![
+            item(0, 0, 0, 0.2, true),
+            item(5, 5, 5, 0.0, false),
+            item(0, 1, 0, 0.8, true),
+            item(1, 0, 0, 0.5, true),
+            item(0, 0, 0, 0.5, true),
+            item(0, 0, 0, 0.9, false),
+        ]);
+        assert!(items[..2].iter().all(|i| !i.transparent));
+        assert!(items[2..].iter().all(|i| i.transparent));
+        let order: Vec<(f32, usize)> = items[2..].iter().map(|i| (i.depth, i.pipeline)).collect();
+        // equal depths fall back to the state key
+        assert_eq!(order, vec![(0.8, 0), (0.5, 0), (0.5, 1), (0.2, 0)]);
+    }
+
+    #[test]
+    fn uniforms_are_part_of_the_state() {
+        let mut a = item(0, 0, 0, 0.0, false);
+        a.uniforms = vec![2];
+        let mut b = item(0, 0, 0, 0.0, false);
+        b.uniforms = vec![1];
+        let items = sorted(vec![a, item(0, 0, 0, 0.0, false), b]);
+        let uniforms: Vec<&[usize]> = items.iter().map(|i| i.uniforms.as_slice()).collect();
+        assert_eq!(uniforms, vec![&[][..], &[1][..], &[2][..]]);
+    }
+}
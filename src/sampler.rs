@@ -0,0 +1,199 @@
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+/// How a texture is sampled.
+#[derive(Copy, Clone, Debug)]
+pub struct SamplerOptions {
+    /// U, V and W address modes.
+    pub address_mode: [wgpu::AddressMode; 3],
+    pub mag_filter: wgpu::FilterMode,
+    pub min_filter: wgpu::FilterMode,
+    pub mipmap_filter: wgpu::FilterMode,
+    pub lod_min_clamp: f32,
+    pub lod_max_clamp: f32,
+    pub compare: Option<wgpu::CompareFunction>,
+    /// Maximum anisotropy: 1 (off), 2, 4, 8 or 16.
+    /// * Needs every filter to be `Linear`, and is ignored on adapters without anisotropic filtering.
+    pub anisotropy: u8,
+    pub border_color: Option<wgpu::SamplerBorderColor>,
+}
+
+impl Default for SamplerOptions {
+    /// The sampler `Texture::from_image` has always used.
+    fn default() -> Self {
+        Self {
+            address_mode: [wgpu::AddressMode::ClampToEdge; 3],
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            lod_min_clamp: 0.0,
+            lod_max_clamp: f32::MAX,
+            compare: None,
+            anisotropy: 1,
+            border_color: None,
+        }
+    }
+}
+
+impl SamplerOptions {
+    /// Linear filtering within and between mip levels.
+    pub fn linear() -> Self {
+        Self {
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Self::default()
+        }
+    }
+
+    /// Nearest filtering everywhere, for pixel art.
+    pub fn nearest() -> Self {
+        Self {
+            mag_filter: wgpu::FilterMode::Nearest,
+            ..Self::default()
+        }
+    }
+
+    /// Linear filtering that wraps around at the edges, for tiling backgrounds.
+    pub fn repeat() -> Self {
+        Self::linear().with_address_mode(wgpu::AddressMode::Repeat)
+    }
+
+    /// Linear filtering with up to `anisotropy` samples for surfaces seen at an angle.
+    pub fn anisotropic(anisotropy: u8) -> Self {
+        Self::linear().with_anisotropy(anisotropy)
+    }
+
+    /// Rounds down to a supported value.
+    pub fn with_anisotropy(mut self, anisotropy: u8) -> Self {
+        self.anisotropy = match anisotropy {
+            0..=1 => 1,
+            2..=3 => 2,
+            4..=7 => 4,
+            8..=15 => 8,
+            _ => 16,
+        };
+        self
+    }
+
+    pub fn with_address_mode(mut self, mode: wgpu::AddressMode) -> Self {
+        self.address_mode = [mode; 3];
+        self
+    }
+
+    pub fn descriptor(&self) -> wgpu::SamplerDescriptor<'static> {
+        wgpu::SamplerDescriptor {
+            label: None,
+            address_mode_u: self.address_mode[0],
+            address_mode_v: self.address_mode[1],
+            address_mode_w: self.address_mode[2],
+            mag_filter: self.mag_filter,
+            min_filter: self.min_filter,
+            mipmap_filter: self.mipmap_filter,
+            lod_min_clamp: self.lod_min_clamp,
+            lod_max_clamp: self.lod_max_clamp,
+            compare: self.compare,
+            anisotropy_clamp: std::num::NonZeroU8::new(self.anisotropy).filter(|a| a.get() > 1),
+            border_color: self.border_color,
+        }
+    }
+}
+
+impl SamplerOptions {
+    /// Everything that identifies the sampler, with the LOD clamps compared by their bits.
+    /// * Adding `0.0` turns `-0.0` into `0.0`, so clamps that compare equal have the same bits.
+    #[allow(clippy::type_complexity)]
+    fn key(&self) -> ([wgpu::AddressMode; 3], [wgpu::FilterMode; 3], [u32; 2], Option<wgpu::CompareFunction>, u8, Option<wgpu::SamplerBorderColor>) {
+        (
+            self.address_mode,
+            [self.mag_filter, self.min_filter, self.mipmap_filter],
+            [(self.lod_min_clamp + 0.0).to_bits(), (self.lod_max_clamp + 0.0).to_bits()],
+            self.compare,
+            self.anisotropy,
+            self.border_color,
+        )
+    }
+}
+
+impl PartialEq for SamplerOptions {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for SamplerOptions {}
+
+impl Hash for SamplerOptions {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key().hash(state);
+    }
+}
+
+/// Creates each distinct sampler once, so textures with the same `SamplerOptions` share it.
+#[derive(Default)]
+pub struct SamplerCache {
+    samplers: HashMap<SamplerOptions, Arc<wgpu::Sampler>>,
+}
+
+impl SamplerCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The sampler for these options, created if it doesn't exist yet.
+    pub fn get(&mut self, device: &wgpu::Device, options: &SamplerOptions) -> Arc<wgpu::Sampler> {
+        self.samplers.entry(*options)
+            .or_insert_with(|| Arc::new(device.create_sampler(&options.descriptor())))
+            .clone()
+    }
+
+    /// The number of distinct samplers created.
+    pub fn len(&self) -> usize {
+        self.samplers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samplers.is_empty()
+    }
+
+    /// Forget samplers that no texture uses any more.
+    pub fn purge(&mut self) {
+        self.samplers.retain(|_, s| Arc::strong_count(s) > 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn anisotropy_rounds_down() {
+        let rounded: Vec<u8> = [0, 1, 2, 3, 4, 5, 7, 8, 15, 16, 17, 255].iter()
+            .map(|a| SamplerOptions::default().with_anisotropy(*a).anisotropy)
+            .collect();
+        assert_eq!(rounded, [1, 1, 2, 2, 4, 4, 4, 8, 8, 16, 16, 16]);
+        assert_eq!(SamplerOptions::anisotropic(6), SamplerOptions::linear().with_anisotropy(4));
+
+        // 1 means off
+        assert_eq!(SamplerOptions::default().descriptor().anisotropy_clamp, None);
+        assert_eq!(SamplerOptions::anisotropic(16).descriptor().anisotropy_clamp.map(|a| a.get()), Some(16));
+    }
+
+    #[test]
+    fn equal_options_share_a_key() {
+        let mut set = HashSet::new();
+        set.insert(SamplerOptions::linear());
+        set.insert(SamplerOptions::linear());
+        set.insert(SamplerOptions { lod_min_clamp: -0.0, ..SamplerOptions::linear() });
+        assert_eq!(set.len(), 1);
+
+        set.insert(SamplerOptions::nearest());
+        set.insert(SamplerOptions::repeat());
+        set.insert(SamplerOptions::anisotropic(8));
+        set.insert(SamplerOptions { lod_max_clamp: 4.0, ..SamplerOptions::linear() });
+        set.insert(SamplerOptions { compare: Some(wgpu::CompareFunction::Less), ..SamplerOptions::linear() });
+        assert_eq!(set.len(), 6);
+        assert!(set.contains(&SamplerOptions::linear().with_anisotropy(9)));
+    }
+}
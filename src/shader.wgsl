@@ -1,12 +1,6 @@
 // Vertex shader
 
-[[block]]
-struct WorldPoint {
-    pos: vec3<f32>;
-};
-
-[[group(0), binding(2)]]
-var<uniform> trans: WorldPoint;
+#include "panels/transforms.wgsl"
 
 struct VertexInput {
     [[location(0)]] position: vec3<f32>;
@@ -23,8 +17,8 @@ fn main(
     model: VertexInput
 ) -> VertexOutput {
     var out: VertexOutput;
-    out.tex_coords = model.tex_coords;
-    out.clip_position = vec4<f32>(model.position+trans.pos, 1.0);
+    out.tex_coords = object.uv_rect.xy + model.tex_coords * object.uv_rect.zw;
+    out.clip_position = camera.view_proj * object.model * vec4<f32>(model.position, 1.0);
     return out;
 }
 
@@ -39,5 +33,5 @@ var s_diffuse: sampler;
 
 [[stage(fragment)]]
 fn main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
-    return textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    return textureSample(t_diffuse, s_diffuse, in.tex_coords) * object.color;
 }
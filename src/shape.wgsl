@@ -0,0 +1,33 @@
+// Shape shader
+// Shapes are tessellated on the CPU, so vertices only carry a position and a color.
+
+[[block]]
+struct CameraUniform {
+    view_proj: mat4x4<f32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> camera: CameraUniform;
+
+struct VertexInput {
+    [[location(0)]] position: vec3<f32>;
+    [[location(1)]] color: vec4<f32>;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] color: vec4<f32>;
+};
+
+[[stage(vertex)]]
+fn main(v: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.color = v.color;
+    out.clip_position = camera.view_proj * vec4<f32>(v.position, 1.0);
+    return out;
+}
+
+[[stage(fragment)]]
+fn main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    return in.color;
+}
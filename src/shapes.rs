@@ -0,0 +1,550 @@
+use std::f32::consts::PI;
+use winit::dpi::PhysicalSize;
+use crate::dynbuffer::{DynamicBuffer, DynamicSlice};
+use crate::pipelines::{PipelineTarget, TargetPipelines};
+use crate::rect::{WorldPoint, WorldRectangle};
+use crate::wgpustate::State;
+
+/// How a filled shape is colored.
+/// * Gradients are evaluated at the vertices of the tessellated shape and interpolated between them.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Fill {
+    Solid([f32; 4]),
+    /// Blends from `start_color` at `start` to `end_color` at `end`, constant beyond either end.
+    LinearGradient {
+        start: WorldPoint,
+        end: WorldPoint,
+        start_color: [f32; 4],
+        end_color: [f32; 4],
+    },
+    /// Blends from `inner` at `center` to `outer` at `radius` pixels away.
+    RadialGradient {
+        center: WorldPoint,
+        radius: f32,
+        inner: [f32; 4],
+        outer: [f32; 4],
+    },
+}
+
+impl From<[f32; 4]> for Fill {
+    fn from(color: [f32; 4]) -> Self {
+        Fill::Solid(color)
+    }
+}
+
+/// How two segments of a stroked polyline are connected.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LineJoin {
+    /// Extends the outer edges until they meet, falling back to `Bevel` for very sharp angles.
+    Miter,
+    Bevel,
+    Round,
+}
+
+#[repr(C)]
+#[derive(Debug,Copy,Clone,bytemuck::Pod,bytemuck::Zeroable)]
+pub struct ShapeVertex {
+    pub position: [f32; 3],
+    pub color: [f32; 4],
+}
+
+impl ShapeVertex {
+    pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<ShapeVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+            ],
+        }
+    }
+}
+
+fn lerp_color(a: [f32; 4], b: [f32; 4], t: f32) -> [f32; 4] {
+    [
+        a[0] + (b[0] - a[0]) * t,
+        a[1] + (b[1] - a[1]) * t,
+        a[2] + (b[2] - a[2]) * t,
+        a[3] + (b[3] - a[3]) * t,
+    ]
+}
+
+fn sub(a: [f32; 2], b: [f32; 2]) -> [f32; 2] { [a[0] - b[0], a[1] - b[1]] }
+fn add(a: [f32; 2], b: [f32; 2]) -> [f32; 2] { [a[0] + b[0], a[1] + b[1]] }
+fn mul(a: [f32; 2], s: f32) -> [f32; 2] { [a[0] * s, a[1] * s] }
+fn dot(a: [f32; 2], b: [f32; 2]) -> f32 { a[0] * b[0] + a[1] * b[1] }
+fn cross(a: [f32; 2], b: [f32; 2]) -> f32 { a[0] * b[1] - a[1] * b[0] }
+fn normalize(a: [f32; 2]) -> [f32; 2] {
+    let len = dot(a, a).sqrt();
+    if len > 0.0 { mul(a, 1.0 / len) } else { [0.0, 0.0] }
+}
+
+/// Collects shapes for one frame as triangles, uploaded with `State::prepare_shapes`.
+/// * Positions are world coordinates; line widths and radii are in pixels of the render target, so circles stay round.
+/// * Shapes are drawn in the order they were added.
+pub struct ShapeBuilder {
+    pub vertices: Vec<ShapeVertex>,
+    pub indices: Vec<u32>,
+    /// World units per pixel along X and Y.
+    units_per_pixel: [f32; 2],
+    /// The maximum distance in pixels between a curve and the segments approximating it.
+    pub tolerance: f32,
+}
+
+impl ShapeBuilder {
+    pub fn new(units_per_pixel: [f32; 2]) -> Self {
+        Self {
+            vertices: vec![],
+            indices: vec![],
+            units_per_pixel,
+            tolerance: 0.25,
+        }
+    }
+
+    /// A builder for the default `Ndc` camera drawing into a target of this size.
+    /// * Pixel sizes are only right when the pass's camera uses `Projection::Ndc`.
+    ///   For other orthographic cameras use `ShapeBuilder::new` with the camera's world units per pixel,
+    ///   e.g. `[1.0, 1.0]` for `Camera::orthographic_pixels`.
+    pub fn for_target(size: PhysicalSize<u32>) -> Self {
+        Self::new([2.0 / size.width.max(1) as f32, 2.0 / size.height.max(1) as f32])
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+        self.indices.clear();
+    }
+
+    fn to_px(&self, p: WorldPoint) -> [f32; 2] {
+        [p.x / self.units_per_pixel[0], p.y / self.units_per_pixel[1]]
+    }
+
+    fn color_at(&self, fill: &Fill, px: [f32; 2]) -> [f32; 4] {
+        match *fill {
+            Fill::Solid(c) => c,
+            Fill::LinearGradient { start, end, start_color, end_color } => {
+                let (s, e) = (self.to_px(start), self.to_px(end));
+                let axis = sub(e, s);
+                let len2 = dot(axis, axis);
+                let t = if len2 > 0.0 { dot(sub(px, s), axis) / len2 } else { 0.0 };
+                lerp_color(start_color, end_color, t.clamp(0.0, 1.0))
+            }
+            Fill::RadialGradient { center, radius, inner, outer } => {
+                let d = sub(px, self.to_px(center));
+                let t = if radius > 0.0 { dot(d, d).sqrt() / radius } else { 1.0 };
+                lerp_color(inner, outer, t.clamp(0.0, 1.0))
+            }
+        }
+    }
+
+    fn vertex(&mut self, px: [f32; 2], z: f32, color: [f32; 4]) -> u32 {
+        self.vertices.push(ShapeVertex {
+            position: [px[0] * self.units_per_pixel[0], px[1] * self.units_per_pixel[1], z],
+            color,
+        });
+        self.vertices.len() as u32 - 1
+    }
+
+    fn triangle(&mut self, a: [f32; 2], b: [f32; 2], c: [f32; 2], z: f32, color: [f32; 4]) {
+        let a = self.vertex(a, z, color);
+        let b = self.vertex(b, z, color);
+        let c = self.vertex(c, z, color);
+        self.indices.extend_from_slice(&[a, b, c]);
+    }
+
+    /// The number of segments needed for an arc of this radius and angle to stay within `tolerance`.
+    fn arc_segments(&self, radius: f32, angle: f32) -> usize {
+        if radius <= self.tolerance {
+            return 1;
+        }
+        let step = 2.0 * (1.0 - self.tolerance / radius).acos();
+        ((angle.abs() / step).ceil() as usize).clamp(1, 256)
+    }
+
+    /// Points along an arc in pixel space, including both ends.
+    fn arc(&self, center: [f32; 2], radius: f32, start: f32, sweep: f32, out: &mut Vec<[f32; 2]>) {
+        let n = self.arc_segments(radius, sweep);
+        for i in 0..=n {
+            let a = start + sweep * i as f32 / n as f32;
+            out.push([center[0] + radius * a.cos(), center[1] + radius * a.sin()]);
+        }
+    }
+
+    /// The outline of a rectangle with rounded corners in pixel space, counter-clockwise.
+    fn rounded_outline(&self, rect: &WorldRectangle, radius: f32) -> Vec<[f32; 2]> {
+        let tl = self.to_px(rect.pos);
+        let br = self.to_px(WorldPoint::new(rect.right(), rect.bottom(), rect.pos.z));
+        let (l, r, t, b) = (tl[0].min(br[0]), tl[0].max(br[0]), tl[1].max(br[1]), tl[1].min(br[1]));
+        let radius = radius.min((r - l) / 2.0).min((t - b) / 2.0).max(0.0);
+
+        let mut points = vec![];
+        if radius <= 0.0 {
+            points.extend_from_slice(&[[l, b], [r, b], [r, t], [l, t]]);
+            return points;
+        }
+        self.arc([r - radius, b + radius], radius, -PI / 2.0, PI / 2.0, &mut points);
+        self.arc([r - radius, t - radius], radius, 0.0, PI / 2.0, &mut points);
+        self.arc([l + radius, t - radius], radius, PI / 2.0, PI / 2.0, &mut points);
+        self.arc([l + radius, b + radius], radius, PI, PI / 2.0, &mut points);
+        points
+    }
+
+    fn circle_outline(&self, center: WorldPoint, radius: f32) -> Vec<[f32; 2]> {
+        let mut points = vec![];
+        self.arc(self.to_px(center), radius, 0.0, 2.0 * PI, &mut points);
+        // the last point repeats the first
+        points.pop();
+        points
+    }
+
+    /// Fills a convex polygon given in pixel space as a fan around it's centroid.
+    fn fill_convex_px(&mut self, points: &[[f32; 2]], z: f32, fill: &Fill) {
+        if points.len() < 3 {
+            return;
+        }
+        let n = points.len() as f32;
+        let centroid = points.iter().fold([0.0, 0.0], |acc, p| add(acc, mul(*p, 1.0 / n)));
+        let color = self.color_at(fill, centroid);
+        let c = self.vertex(centroid, z, color);
+        let first = self.vertices.len() as u32;
+        for p in points {
+            let color = self.color_at(fill, *p);
+            self.vertex(*p, z, color);
+        }
+        let count = points.len() as u32;
+        for i in 0..count {
+            self.indices.extend_from_slice(&[c, first + i, first + (i + 1) % count]);
+        }
+    }
+
+    /// Fills a convex polygon; concave polygons are not tessellated correctly.
+    pub fn fill_convex_polygon(&mut self, points: &[WorldPoint], fill: impl Into<Fill>) {
+        let z = points.first().map(|p| p.z).unwrap_or(0.0);
+        let px: Vec<[f32; 2]> = points.iter().map(|p| self.to_px(*p)).collect();
+        self.fill_convex_px(&px, z, &fill.into());
+    }
+
+    pub fn fill_rect(&mut self, rect: &WorldRectangle, fill: impl Into<Fill>) {
+        let outline = self.rounded_outline(rect, 0.0);
+        self.fill_convex_px(&outline, rect.pos.z, &fill.into());
+    }
+
+    /// The stroke is centered on the rectangle's edges.
+    pub fn stroke_rect(&mut self, rect: &WorldRectangle, width: f32, color: [f32; 4]) {
+        let outline = self.rounded_outline(rect, 0.0);
+        self.stroke_px(&outline, rect.pos.z, width, color, LineJoin::Miter, true);
+    }
+
+    pub fn fill_rounded_rect(&mut self, rect: &WorldRectangle, radius: f32, fill: impl Into<Fill>) {
+        let outline = self.rounded_outline(rect, radius);
+        self.fill_convex_px(&outline, rect.pos.z, &fill.into());
+    }
+
+    pub fn stroke_rounded_rect(&mut self, rect: &WorldRectangle, radius: f32, width: f32, color: [f32; 4]) {
+        let outline = self.rounded_outline(rect, radius);
+        self.stroke_px(&outline, rect.pos.z, width, color, LineJoin::Miter, true);
+    }
+
+    pub fn fill_circle(&mut self, center: WorldPoint, radius: f32, fill: impl Into<Fill>) {
+        let outline = self.circle_outline(center, radius);
+        self.fill_convex_px(&outline, center.z, &fill.into());
+    }
+
+    pub fn stroke_circle(&mut self, center: WorldPoint, radius: f32, width: f32, color: [f32; 4]) {
+        let outline = self.circle_outline(center, radius);
+        self.stroke_px(&outline, center.z, width, color, LineJoin::Miter, true);
+    }
+
+    /// Strokes a line through the points, `width` pixels wide.
+    /// * `closed` also connects the last point back to the first.
+    pub fn polyline(&mut self, points: &[WorldPoint], width: f32, color: [f32; 4], join: LineJoin, closed: bool) {
+        let z = points.first().map(|p| p.z).unwrap_or(0.0);
+        let px: Vec<[f32; 2]> = points.iter().map(|p| self.to_px(*p)).collect();
+        self.stroke_px(&px, z, width, color, join, closed);
+    }
+
+    pub fn line(&mut self, a: WorldPoint, b: WorldPoint, width: f32, color: [f32; 4]) {
+        self.polyline(&[a, b], width, color, LineJoin::Miter, false);
+    }
+
+    fn stroke_px(&mut self, points: &[[f32; 2]], z: f32, width: f32, color: [f32; 4], join: LineJoin, closed: bool) {
+        // drop repeated points, they have no direction
+        let mut pts: Vec<[f32; 2]> = Vec::with_capacity(points.len());
+        for p in points {
+            if pts.last().map(|l| sub(*p, *l)) != Some([0.0, 0.0]) {
+                pts.push(*p);
+            }
+        }
+        if closed && pts.len() > 2 && pts.first() == pts.last() {
+            pts.pop();
+        }
+        if pts.len() < 2 {
+            return;
+        }
+
+        let half = width / 2.0;
+        let n = pts.len();
+        let segments = if closed { n } else { n - 1 };
+
+        for i in 0..segments {
+            let (a, b) = (pts[i], pts[(i + 1) % n]);
+            let normal = {
+                let d = normalize(sub(b, a));
+                mul([-d[1], d[0]], half)
+            };
+            let v0 = self.vertex(add(a, normal), z, color);
+            let v1 = self.vertex(sub(a, normal), z, color);
+            let v2 = self.vertex(sub(b, normal), z, color);
+            let v3 = self.vertex(add(b, normal), z, color);
+            self.indices.extend_from_slice(&[v0, v1, v2, v2, v3, v0]);
+        }
+
+        let joins = if closed { 0..n } else { 1..n - 1 };
+        for i in joins {
+            let prev = pts[(i + n - 1) % n];
+            let p = pts[i];
+            let next = pts[(i + 1) % n];
+            self.join(prev, p, next, z, half, color, join);
+        }
+    }
+
+    /// Fills the gap on the outside of the turn at `p`.
+    #[allow(clippy::too_many_arguments)]
+    fn join(&mut self, prev: [f32; 2], p: [f32; 2], next: [f32; 2], z: f32, half: f32, color: [f32; 4], join: LineJoin) {
+        let d0 = normalize(sub(p, prev));
+        let d1 = normalize(sub(next, p));
+        let turn = cross(d0, d1);
+        if turn.abs() < 1e-6 && dot(d0, d1) > 0.0 {
+            return;
+        }
+        // the outer side is on the right of a left turn
+        let side = if turn > 0.0 { -1.0 } else { 1.0 };
+        let n0 = mul([-d0[1], d0[0]], half * side);
+        let n1 = mul([-d1[1], d1[0]], half * side);
+        let (o0, o1) = (add(p, n0), add(p, n1));
+        let first = self.indices.len();
+
+        match join {
+            LineJoin::Bevel => self.triangle(p, o0, o1, z, color),
+            LineJoin::Miter => {
+                let bisector = normalize(add(n0, n1));
+                let cos_half = dot(bisector, normalize(n0));
+                // limit the miter to 4 line widths, like SVG's default
+                if cos_half > 0.25 {
+                    let m = add(p, mul(bisector, half / cos_half));
+                    self.triangle(p, o0, m, z, color);
+                    self.triangle(p, m, o1, z, color);
+                } else {
+                    self.triangle(p, o0, o1, z, color);
+                }
+            }
+            LineJoin::Round => {
+                let start = n0[1].atan2(n0[0]);
+                let mut sweep = n1[1].atan2(n1[0]) - start;
+                if sweep > PI {
+                    sweep -= 2.0 * PI;
+                } else if sweep < -PI {
+                    sweep += 2.0 * PI;
+                }
+                let mut arc = vec![];
+                self.arc(p, half, start, sweep, &mut arc);
+                for w in arc.windows(2) {
+                    self.triangle(p, w[0], w[1], z, color);
+                }
+            }
+        }
+        // keep the triangles of right turns counter-clockwise too
+        if side > 0.0 {
+            for tri in self.indices[first..].chunks_mut(3) {
+                tri.swap(1, 2);
+            }
+        }
+    }
+}
+
+/// Shapes uploaded with `State::prepare_shapes`, drawn with `ShapeBatch::draw`.
+pub struct ShapeBatch {
+    vertices: DynamicSlice,
+    indices: DynamicSlice,
+    index_count: u32,
+}
+
+impl ShapeBatch {
+    pub fn draw<'a>(&self, state: &'a State, render_pass: &mut wgpu::RenderPass<'a>) {
+        if self.index_count == 0 {
+            return;
+        }
+        let shapes = &state.shapes;
+        render_pass.set_pipeline(shapes.pipelines.get(&state.pass.target));
+        render_pass.set_bind_group(0, &state.cameras[state.pass.camera].bind_group, &[]);
+        render_pass.set_vertex_buffer(0, shapes.vertices.slice(&self.vertices));
+        render_pass.set_index_buffer(shapes.indices.slice(&self.indices), wgpu::IndexFormat::Uint32);
+        render_pass.draw_indexed(0..self.index_count, 0, 0..1);
+    }
+}
+
+/// The pipeline and per-frame buffers for `ShapeBuilder` geometry.
+pub struct ShapeRenderer {
+    pipelines: TargetPipelines,
+    vertices: DynamicBuffer,
+    indices: DynamicBuffer,
+}
+
+impl ShapeRenderer {
+    pub fn new(device: &wgpu::Device, target: &PipelineTarget, camera_layout: &wgpu::BindGroupLayout) -> Self {
+        let pipelines = TargetPipelines::new(device, "panels:shapes", include_str!("shape.wgsl"), &[camera_layout], ShapeVertex::desc(), target);
+
+        Self {
+            pipelines,
+            vertices: DynamicBuffer::new(device, "panels:shapes:vertices", wgpu::BufferUsages::VERTEX, 64 * 1024),
+            indices: DynamicBuffer::new(device, "panels:shapes:indices", wgpu::BufferUsages::INDEX, 32 * 1024),
+        }
+    }
+
+    /// Build the pipeline for a target before a pass draws into it.
+    pub fn prepare_target(&mut self, device: &wgpu::Device, target: &PipelineTarget) {
+        self.pipelines.prepare(device, target);
+    }
+
+    /// Start a new frame of shape geometry.
+    pub fn begin_frame(&mut self) {
+        self.vertices.reset();
+        self.indices.reset();
+    }
+
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, shapes: &ShapeBuilder) -> ShapeBatch {
+        ShapeBatch {
+            vertices: self.vertices.push(device, queue, bytemuck::cast_slice(&shapes.vertices)),
+            indices: self.indices.push(device, queue, bytemuck::cast_slice(&shapes.indices)),
+            index_count: shapes.indices.len() as u32,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHITE: [f32; 4] = [1.0; 4];
+
+    /// Twice the signed area of each triangle, positive when counter-clockwise.
+    fn areas(shapes: &ShapeBuilder) -> Vec<f32> {
+        shapes.indices.chunks(3).map(|t| {
+            let [a, b, c] = [t[0], t[1], t[2]].map(|i| {
+                let p = shapes.vertices[i as usize].position;
+                [p[0], p[1]]
+            });
+            cross(sub(b, a), sub(c, a))
+        }).collect()
+    }
+
+    fn assert_ccw(shapes: &ShapeBuilder) {
+        for (i, area) in areas(shapes).iter().enumerate() {
+            assert!(*area > 0.0, "triangle {} has area {}", i, area);
+        }
+    }
+
+    fn points(points: &[[f32; 2]]) -> Vec<WorldPoint> {
+        points.iter().map(|p| WorldPoint::new(p[0], p[1], 0.0)).collect()
+    }
+
+    #[test]
+    fn fill_rect_is_a_fan() {
+        let mut shapes = ShapeBuilder::new([1.0, 1.0]);
+        shapes.fill_rect(&WorldRectangle::new(WorldPoint::new(10.0, 20.0, 0.5), 30.0, 10.0), WHITE);
+
+        // the centroid and 4 corners
+        assert_eq!(shapes.vertices.len(), 5);
+        assert_eq!(shapes.indices.len(), 12);
+        assert_eq!(shapes.vertices[0].position, [25.0, 15.0, 0.5]);
+        assert_ccw(&shapes);
+        assert_eq!(areas(&shapes).iter().sum::<f32>() / 2.0, 300.0);
+    }
+
+    #[test]
+    fn fill_circle_follows_the_tolerance() {
+        let mut shapes = ShapeBuilder::new([1.0, 1.0]);
+        shapes.fill_circle(WorldPoint::new(0.0, 0.0, 0.0), 50.0, WHITE);
+        let segments = shapes.arc_segments(50.0, 2.0 * PI);
+        assert_eq!(shapes.vertices.len(), segments + 1);
+        assert_eq!(shapes.indices.len(), segments * 3);
+        assert_ccw(&shapes);
+        let area = areas(&shapes).iter().sum::<f32>() / 2.0;
+        assert!(area < PI * 2500.0 && area > PI * 2500.0 * 0.99, "area {}", area);
+
+        // a looser tolerance needs fewer segments
+        let mut coarse = ShapeBuilder::new([1.0, 1.0]);
+        coarse.tolerance = 2.0;
+        coarse.fill_circle(WorldPoint::new(0.0, 0.0, 0.0), 50.0, WHITE);
+        assert!(coarse.vertices.len() < shapes.vertices.len());
+    }
+
+    #[test]
+    fn open_stroke_joins_inner_points() {
+        // a left turn then a right turn
+        let path = points(&[[0.0, 0.0], [100.0, 0.0], [100.0, 100.0], [200.0, 100.0]]);
+        let mut shapes = ShapeBuilder::new([1.0, 1.0]);
+        shapes.polyline(&path, 10.0, WHITE, LineJoin::Miter, false);
+
+        // 3 quads of 4 vertices and 2 miters of 2 triangles
+        assert_eq!(shapes.vertices.len(), 3 * 4 + 2 * 6);
+        assert_eq!(shapes.indices.len(), 3 * 6 + 2 * 6);
+        assert_ccw(&shapes);
+        // the miters reach the outer corners
+        let has = |p: [f32; 3]| shapes.vertices.iter().any(|v| v.position == p);
+        assert!(has([105.0, -5.0, 0.0]));
+        assert!(has([95.0, 105.0, 0.0]));
+    }
+
+    #[test]
+    fn closed_stroke_joins_every_point() {
+        let path = points(&[[0.0, 0.0], [100.0, 0.0], [50.0, 80.0], [0.0, 0.0]]);
+        let mut shapes = ShapeBuilder::new([1.0, 1.0]);
+        shapes.polyline(&path, 4.0, WHITE, LineJoin::Bevel, true);
+        // the repeated first point is dropped
+        assert_eq!(shapes.vertices.len(), 3 * 4 + 3 * 3);
+        assert_eq!(shapes.indices.len(), 3 * 6 + 3 * 3);
+        assert_ccw(&shapes);
+
+        let mut round = ShapeBuilder::new([1.0, 1.0]);
+        round.polyline(&path, 4.0, WHITE, LineJoin::Round, true);
+        assert!(round.indices.len() > shapes.indices.len());
+        assert_ccw(&round);
+    }
+
+    #[test]
+    fn sharp_miters_fall_back_to_bevels() {
+        let mut shapes = ShapeBuilder::new([1.0, 1.0]);
+        // a turn of about 174 degrees, far past the miter limit, both ways
+        shapes.polyline(&points(&[[0.0, 0.0], [100.0, 0.0], [0.0, 10.0]]), 10.0, WHITE, LineJoin::Miter, false);
+        shapes.polyline(&points(&[[0.0, 0.0], [100.0, 0.0], [0.0, -10.0]]), 10.0, WHITE, LineJoin::Miter, false);
+
+        // 2 quads and 1 bevel triangle each
+        assert_eq!(shapes.vertices.len(), 2 * (2 * 4 + 3));
+        assert_eq!(shapes.indices.len(), 2 * (2 * 6 + 3));
+        assert_ccw(&shapes);
+        for v in &shapes.vertices {
+            assert!(v.position[0] <= 105.0, "{:?} sticks out", v.position);
+        }
+    }
+
+    #[test]
+    fn degenerate_strokes_draw_nothing() {
+        let mut shapes = ShapeBuilder::new([1.0, 1.0]);
+        shapes.polyline(&points(&[[5.0, 5.0], [5.0, 5.0]]), 2.0, WHITE, LineJoin::Miter, false);
+        shapes.polyline(&points(&[[5.0, 5.0]]), 2.0, WHITE, LineJoin::Miter, true);
+        assert!(shapes.is_empty());
+    }
+}
@@ -0,0 +1,42 @@
+// Sprite shader
+// Batched textured quads, tinted by a per-vertex color.
+
+[[block]]
+struct CameraUniform {
+    view_proj: mat4x4<f32>;
+};
+
+[[group(1), binding(0)]]
+var<uniform> camera: CameraUniform;
+
+struct VertexInput {
+    [[location(0)]] position: vec3<f32>;
+    [[location(1)]] uv: vec2<f32>;
+    [[location(2)]] color: vec4<f32>;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] uv: vec2<f32>;
+    [[location(1)]] color: vec4<f32>;
+};
+
+[[stage(vertex)]]
+fn main(v: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.uv = v.uv;
+    out.color = v.color;
+    out.clip_position = camera.view_proj * vec4<f32>(v.position, 1.0);
+    return out;
+}
+
+[[group(0), binding(0)]]
+var t_diffuse: texture_2d<f32>;
+
+[[group(0), binding(1)]]
+var s_diffuse: sampler;
+
+[[stage(fragment)]]
+fn main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    return textureSample(t_diffuse, s_diffuse, in.uv) * in.color;
+}
@@ -0,0 +1,356 @@
+use std::collections::HashMap;
+use std::time::Instant;
+use crate::atlas::TextureAtlas;
+use crate::renderobj::RenderObject;
+use crate::timing::Timing;
+use crate::wgpustate::State;
+
+/// What a clip does after it's last frame.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PlayMode {
+    /// Start again from the first frame.
+    Loop,
+    /// Play backwards to the first frame, then forwards again.
+    PingPong,
+    /// Stop on the last frame.
+    Once,
+}
+
+/// One frame of a clip: a bind group from `State::bind_groups` and the part of it's texture to show.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SpriteFrame {
+    pub bind_group: usize,
+    pub uv_min: [f32; 2],
+    pub uv_max: [f32; 2],
+}
+
+/// A sequence of frames played at a fixed rate.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpriteClip {
+    pub frames: Vec<SpriteFrame>,
+    /// Frames per second.
+    pub fps: f32,
+    pub mode: PlayMode,
+}
+
+impl SpriteClip {
+    pub fn new(frames: Vec<SpriteFrame>, fps: f32, mode: PlayMode) -> Self {
+        Self { frames, fps, mode }
+    }
+
+    /// A clip of sprites from an atlas, in the given order.
+    pub fn from_atlas(atlas: &TextureAtlas, sprites: &[usize], fps: f32, mode: PlayMode) -> Self {
+        let frames = sprites.iter().map(|s| {
+            let region = atlas.region(*s);
+            SpriteFrame {
+                bind_group: atlas.page_bind_group(region.page),
+                uv_min: region.uv_min,
+                uv_max: region.uv_max,
+            }
+        }).collect();
+        Self::new(frames, fps, mode)
+    }
+
+    /// A clip of `count` cells of a sprite sheet with `[columns, rows]` cells, read left to right then top to bottom,
+    /// where the sheet covers `uv_min..uv_max` of the texture in `bind_group`.
+    pub fn from_grid(bind_group: usize, uv_min: [f32; 2], uv_max: [f32; 2], [columns, rows]: [u32; 2], count: usize, fps: f32, mode: PlayMode) -> Self {
+        let cell = [(uv_max[0] - uv_min[0]) / columns as f32, (uv_max[1] - uv_min[1]) / rows as f32];
+        let frames = (0..count.min((columns * rows) as usize)).map(|i| {
+            let (col, row) = (i as u32 % columns, i as u32 / columns);
+            let min = [uv_min[0] + col as f32 * cell[0], uv_min[1] + row as f32 * cell[1]];
+            SpriteFrame {
+                bind_group,
+                uv_min: min,
+                uv_max: [min[0] + cell[0], min[1] + cell[1]],
+            }
+        }).collect();
+        Self::new(frames, fps, mode)
+    }
+
+    /// A clip from a sprite sheet that was added to an atlas as a single sprite.
+    pub fn from_atlas_grid(atlas: &TextureAtlas, sheet: usize, grid: [u32; 2], count: usize, fps: f32, mode: PlayMode) -> Self {
+        let region = atlas.region(sheet);
+        Self::from_grid(atlas.page_bind_group(region.page), region.uv_min, region.uv_max, grid, count, fps, mode)
+    }
+
+    /// The number of frames before the clip repeats, `None` for `Once`.
+    fn cycle_len(&self) -> Option<usize> {
+        let n = self.frames.len();
+        match self.mode {
+            PlayMode::Loop => Some(n),
+            PlayMode::PingPong => Some((2 * n).saturating_sub(2).max(1)),
+            PlayMode::Once => None,
+        }
+    }
+
+    /// The frame shown after `ticks` frame durations.
+    fn frame_at(&self, ticks: usize) -> usize {
+        let n = self.frames.len();
+        match self.mode {
+            PlayMode::Loop => ticks % n,
+            PlayMode::PingPong => {
+                let pos = ticks % self.cycle_len().unwrap();
+                if pos < n { pos } else { 2 * n - 2 - pos }
+            }
+            PlayMode::Once => ticks.min(n - 1),
+        }
+    }
+}
+
+/// Something that happened while a clip was advanced.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AnimationEvent {
+    /// A `Loop` or `PingPong` clip started another cycle.
+    Looped { clip: usize },
+    /// A `Once` clip reached it's last frame and stopped.
+    Finished { clip: usize },
+}
+
+/// Plays `SpriteClip`s on a `RenderObject` by changing it's bind group and UV rect.
+/// * Call `update` from `ProgramHook::update`; it advances by the time since the renderer was last updated.
+pub struct SpriteAnimator {
+    pub clips: Vec<SpriteClip>,
+    pub clip_map: HashMap<String, usize>,
+    /// Playback rate multiplier.
+    pub speed: f32,
+    current: Option<usize>,
+    /// Seconds since the current clip started.
+    time: f32,
+    frame: usize,
+    playing: bool,
+    /// Used to measure time when the renderer's update `Timing` doesn't keep track of it.
+    last_update: Option<Instant>,
+}
+
+impl Default for SpriteAnimator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SpriteAnimator {
+    pub fn new() -> Self {
+        Self {
+            clips: vec![],
+            clip_map: HashMap::new(),
+            speed: 1.0,
+            current: None,
+            time: 0.0,
+            frame: 0,
+            playing: false,
+            last_update: None,
+        }
+    }
+
+    /// Add a clip and return it's index. An empty `name` isn't registered.
+    pub fn add_clip(&mut self, name: &str, clip: SpriteClip) -> usize {
+        let id = self.clips.len();
+        self.clips.push(clip);
+        if !name.is_empty() {
+            self.clip_map.insert(name.to_string(), id);
+        }
+        id
+    }
+
+    /// Play a clip by name from it's first frame. Returns `false` if there is no such clip.
+    pub fn play(&mut self, name: &str) -> bool {
+        match self.clip_map.get(name).copied() {
+            Some(id) => {
+                self.play_id(id);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Play a clip from it's first frame.
+    pub fn play_id(&mut self, clip: usize) {
+        self.current = Some(clip);
+        self.time = 0.0;
+        self.frame = 0;
+        self.playing = true;
+    }
+
+    pub fn pause(&mut self) {
+        self.playing = false;
+    }
+
+    pub fn resume(&mut self) {
+        self.playing = self.current.is_some();
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    pub fn current_clip(&self) -> Option<usize> {
+        self.current
+    }
+
+    pub fn frame_index(&self) -> usize {
+        self.frame
+    }
+
+    pub fn current_frame(&self) -> Option<&SpriteFrame> {
+        self.current.and_then(|c| self.clips[c].frames.get(self.frame))
+    }
+
+    /// Advance the current clip by `dt` seconds.
+    pub fn advance(&mut self, dt: f32) -> Vec<AnimationEvent> {
+        let mut events = vec![];
+        let id = match (self.current, self.playing) {
+            (Some(id), true) => id,
+            _ => return events,
+        };
+        let clip = &self.clips[id];
+        if clip.frames.is_empty() || clip.fps <= 0.0 {
+            return events;
+        }
+
+        let before = (self.time * clip.fps) as usize;
+        self.time += dt * self.speed;
+        let after = (self.time * clip.fps) as usize;
+        self.frame = clip.frame_at(after);
+
+        match clip.cycle_len() {
+            Some(len) => {
+                for _ in before / len..after / len {
+                    events.push(AnimationEvent::Looped { clip: id });
+                }
+            }
+            None => {
+                if after >= clip.frames.len() {
+                    self.playing = false;
+                    events.push(AnimationEvent::Finished { clip: id });
+                }
+            }
+        }
+        events
+    }
+
+    /// Show the current frame on the object.
+    pub fn apply(&self, state: &State, object: &mut RenderObject) {
+        if let Some(frame) = self.current_frame() {
+            object.bind_group = frame.bind_group;
+            if object.uv_rect != [frame.uv_min[0], frame.uv_min[1], frame.uv_max[0] - frame.uv_min[0], frame.uv_max[1] - frame.uv_min[1]] {
+                object.set_uv_rect(state, frame.uv_min, frame.uv_max);
+            }
+        }
+    }
+
+    /// Advance by the time since the last update and show the new frame on the object.
+    /// * `timing` is the renderer's `updatef_status`, e.g. `animator.update(&renderer.updatef_status, state, &mut renderer.my_objects[0])`.
+    pub fn update(&mut self, timing: &Timing, state: &State, object: &mut RenderObject) -> Vec<AnimationEvent> {
+        let now = Instant::now();
+        let dt = match (timing.elapsed(), self.last_update) {
+            (Some(elapsed), _) => elapsed.as_secs_f32(),
+            (None, Some(last)) => (now - last).as_secs_f32(),
+            (None, None) => 0.0,
+        };
+        self.last_update = Some(now);
+        let events = self.advance(dt);
+        self.apply(state, object);
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clip(frames: usize, mode: PlayMode) -> SpriteClip {
+        let frame = SpriteFrame { bind_group: 0, uv_min: [0.0, 0.0], uv_max: [1.0, 1.0] };
+        // 4 fps so that quarter seconds add up exactly
+        SpriteClip::new(vec![frame; frames], 4.0, mode)
+    }
+
+    fn frames(clip: &SpriteClip, ticks: usize) -> Vec<usize> {
+        (0..ticks).map(|t| clip.frame_at(t)).collect()
+    }
+
+    #[test]
+    fn frame_order() {
+        assert_eq!(frames(&clip(3, PlayMode::Loop), 7), [0, 1, 2, 0, 1, 2, 0]);
+        assert_eq!(frames(&clip(4, PlayMode::PingPong), 10), [0, 1, 2, 3, 2, 1, 0, 1, 2, 3]);
+        assert_eq!(frames(&clip(3, PlayMode::Once), 5), [0, 1, 2, 2, 2]);
+    }
+
+    #[test]
+    fn short_ping_pong_clips() {
+        // the ends aren't repeated, so 2 frames just alternate and 1 frame never changes
+        assert_eq!(clip(2, PlayMode::PingPong).cycle_len(), Some(2));
+        assert_eq!(frames(&clip(2, PlayMode::PingPong), 5), [0, 1, 0, 1, 0]);
+        assert_eq!(clip(1, PlayMode::PingPong).cycle_len(), Some(1));
+        assert_eq!(frames(&clip(1, PlayMode::PingPong), 3), [0, 0, 0]);
+    }
+
+    #[test]
+    fn large_steps_count_every_loop() {
+        let mut animator = SpriteAnimator::new();
+        let id = animator.add_clip("walk", clip(3, PlayMode::Loop));
+        assert!(animator.play("walk"));
+
+        // 2.5 cycles of 3 frames at 4 fps
+        let events = animator.advance(2.5 * 0.75);
+        assert_eq!(events, vec![AnimationEvent::Looped { clip: id }; 2]);
+        assert_eq!(animator.frame_index(), 1);
+
+        // finishing the third cycle loops once more
+        assert_eq!(animator.advance(0.25).len(), 0);
+        assert_eq!(animator.advance(0.25), vec![AnimationEvent::Looped { clip: id }]);
+        assert_eq!(animator.frame_index(), 0);
+    }
+
+    #[test]
+    fn ping_pong_loops_after_coming_back() {
+        let mut animator = SpriteAnimator::new();
+        let id = animator.add_clip("", clip(3, PlayMode::PingPong));
+        animator.play_id(id);
+
+        // 0 1 2 1 is one cycle
+        assert_eq!(animator.advance(0.75).len(), 0);
+        assert_eq!(animator.frame_index(), 1);
+        assert_eq!(animator.advance(0.25), vec![AnimationEvent::Looped { clip: id }]);
+        assert_eq!(animator.frame_index(), 0);
+    }
+
+    #[test]
+    fn once_finishes_on_the_last_frame() {
+        let mut animator = SpriteAnimator::new();
+        let id = animator.add_clip("die", clip(3, PlayMode::Once));
+        animator.play("die");
+
+        assert_eq!(animator.advance(0.5).len(), 0);
+        assert_eq!(animator.frame_index(), 2);
+        assert!(animator.is_playing());
+
+        assert_eq!(animator.advance(0.25), vec![AnimationEvent::Finished { clip: id }]);
+        assert_eq!(animator.frame_index(), 2);
+        assert!(!animator.is_playing());
+
+        // stopped clips don't advance or finish again
+        assert_eq!(animator.advance(10.0).len(), 0);
+        assert_eq!(animator.frame_index(), 2);
+        animator.resume();
+        assert!(animator.is_playing());
+    }
+
+    #[test]
+    fn speed_and_pause() {
+        let mut animator = SpriteAnimator::new();
+        animator.add_clip("walk", clip(4, PlayMode::Loop));
+        assert!(!animator.play("run"));
+        animator.play("walk");
+
+        animator.speed = 2.0;
+        animator.advance(0.25);
+        assert_eq!(animator.frame_index(), 2);
+
+        animator.pause();
+        animator.advance(1.0);
+        assert_eq!(animator.frame_index(), 2);
+        animator.resume();
+        animator.advance(0.125);
+        assert_eq!(animator.frame_index(), 3);
+    }
+}
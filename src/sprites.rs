@@ -0,0 +1,169 @@
+use crate::atlas::TextureAtlas;
+use crate::dynbuffer::{DynamicBuffer, DynamicSlice};
+use crate::pipelines::{PipelineTarget, TargetPipelines};
+use crate::rect::WorldRectangle;
+use crate::wgpustate::State;
+
+#[repr(C)]
+#[derive(Debug,Copy,Clone,bytemuck::Pod,bytemuck::Zeroable)]
+pub struct SpriteVertex {
+    pub position: [f32; 3],
+    pub uv: [f32; 2],
+    pub color: [f32; 4],
+}
+
+impl SpriteVertex {
+    pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<SpriteVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 5]>() as wgpu::BufferAddress,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+            ],
+        }
+    }
+}
+
+/// Collects textured quads for one frame, uploaded with `State::prepare_sprites`.
+/// * Consecutive quads that use the same bind group are drawn with a single draw call.
+#[derive(Default)]
+pub struct SpriteBuilder {
+    vertices: Vec<SpriteVertex>,
+    /// (bind group, first vertex) of every run of quads sharing a bind group.
+    runs: Vec<(usize, u32)>,
+}
+
+impl SpriteBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vertices.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+        self.runs.clear();
+    }
+
+    /// Adds a quad covering `rect`, sampling `uv_min..uv_max` of the texture in `State::bind_groups[bind_group]`.
+    pub fn quad(&mut self, bind_group: usize, rect: &WorldRectangle, uv_min: [f32; 2], uv_max: [f32; 2], color: [f32; 4]) {
+        if self.runs.last().map(|r| r.0) != Some(bind_group) {
+            self.runs.push((bind_group, self.vertices.len() as u32));
+        }
+        let z = rect.pos.z;
+        let tl = SpriteVertex { position: [rect.left(), rect.top(), z], uv: uv_min, color };
+        let bl = SpriteVertex { position: [rect.left(), rect.bottom(), z], uv: [uv_min[0], uv_max[1]], color };
+        let br = SpriteVertex { position: [rect.right(), rect.bottom(), z], uv: uv_max, color };
+        let tr = SpriteVertex { position: [rect.right(), rect.top(), z], uv: [uv_max[0], uv_min[1]], color };
+        self.vertices.extend_from_slice(&[tl, bl, br, br, tr, tl]);
+    }
+
+    /// Adds a sprite from an atlas, tinted by `color`.
+    pub fn sprite(&mut self, atlas: &TextureAtlas, sprite: usize, rect: &WorldRectangle, color: [f32; 4]) {
+        let region = atlas.region(sprite);
+        self.quad(atlas.page_bind_group(region.page), rect, region.uv_min, region.uv_max, color);
+    }
+}
+
+/// Sprites uploaded with `State::prepare_sprites`, drawn with `SpriteBatch::draw`.
+pub struct SpriteBatch {
+    slice: DynamicSlice,
+    runs: Vec<(usize, std::ops::Range<u32>)>,
+}
+
+impl SpriteBatch {
+    pub fn draw<'a>(&self, state: &'a State, render_pass: &mut wgpu::RenderPass<'a>) {
+        if self.runs.is_empty() {
+            return;
+        }
+        let sprites = &state.sprites;
+        render_pass.set_pipeline(sprites.pipelines.get(&state.pass.target));
+        render_pass.set_bind_group(1, &state.cameras[state.pass.camera].bind_group, &[]);
+        render_pass.set_vertex_buffer(0, sprites.vertices.slice(&self.slice));
+        for (bind_group, range) in &self.runs {
+            render_pass.set_bind_group(0, &state.bind_groups[*bind_group], &[]);
+            render_pass.draw(range.clone(), 0..1);
+        }
+    }
+}
+
+/// The pipeline and per-frame vertex buffer for `SpriteBuilder` quads.
+pub struct SpriteRenderer {
+    pipelines: TargetPipelines,
+    vertices: DynamicBuffer,
+}
+
+impl SpriteRenderer {
+    pub fn new(device: &wgpu::Device, target: &PipelineTarget, camera_layout: &wgpu::BindGroupLayout) -> Self {
+        // the same layout as `default:pipe:textured`, so any of it's bind groups can be drawn as a sprite
+        let texture_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler {
+                        filtering: true,
+                        comparison: false,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let pipelines = TargetPipelines::new(device, "panels:sprites", include_str!("sprite.wgsl"), &[&texture_layout, camera_layout], SpriteVertex::desc(), target);
+
+        Self {
+            pipelines,
+            vertices: DynamicBuffer::new(device, "panels:sprites", wgpu::BufferUsages::VERTEX, 64 * 1024),
+        }
+    }
+
+    /// Build the pipeline for a target before a pass draws into it.
+    pub fn prepare_target(&mut self, device: &wgpu::Device, target: &PipelineTarget) {
+        self.pipelines.prepare(device, target);
+    }
+
+    /// Start a new frame of sprite vertices.
+    pub fn begin_frame(&mut self) {
+        self.vertices.reset();
+    }
+
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, sprites: &SpriteBuilder) -> SpriteBatch {
+        let total = sprites.vertices.len() as u32;
+        let runs = sprites.runs.iter().enumerate().map(|(i, (bind_group, start))| {
+            let end = sprites.runs.get(i + 1).map(|r| r.1).unwrap_or(total);
+            (*bind_group, *start..end)
+        }).collect();
+        SpriteBatch {
+            slice: self.vertices.push(device, queue, bytemuck::cast_slice(&sprites.vertices)),
+            runs,
+        }
+    }
+}
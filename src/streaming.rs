@@ -0,0 +1,204 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll, Waker};
+use anyhow::*;
+use crate::sampler::SamplerOptions;
+use crate::texture::Texture;
+use crate::wgpustate::State;
+
+/// Staging buffers per `StreamingTexture`: one being written while earlier uploads are still in flight.
+pub const STAGING_RING_SIZE: usize = 3;
+
+type MapFuture = Pin<Box<dyn Future<Output = Result<(), wgpu::BufferAsyncError>> + Send>>;
+
+struct StagingBuffer {
+    buffer: wgpu::Buffer,
+    size: wgpu::BufferAddress,
+    /// Set while the GPU copies out of the buffer and it is mapped again.
+    mapping: Option<MapFuture>,
+}
+
+impl StagingBuffer {
+    fn new(device: &wgpu::Device, size: wgpu::BufferAddress) -> Self {
+        Self {
+            buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("panels:streaming"),
+                size,
+                usage: wgpu::BufferUsages::MAP_WRITE | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: true,
+            }),
+            size,
+            mapping: None,
+        }
+    }
+}
+
+/// A texture whose contents are replaced often, e.g. camera feeds, video frames or software-rendered content.
+/// * Uploads are written into a ring of mapped staging buffers and copied on the GPU, so writing never waits
+///   for the previous frame's copy. When every buffer is still in flight, the upload goes through
+///   `queue.write_texture` instead and `fallback_uploads` is counted.
+/// * The texture lives in `State::textures[texture]`, and `bind_group` shows it with `default:pipe:textured`.
+pub struct StreamingTexture {
+    pub texture: usize,
+    pub bind_group: usize,
+    width: u32,
+    height: u32,
+    bytes_per_pixel: u32,
+    ring: Vec<StagingBuffer>,
+    /// Uploads that couldn't get a staging buffer.
+    pub fallback_uploads: u64,
+}
+
+impl StreamingTexture {
+    /// `format` must be uncompressed, e.g. `Rgba8UnormSrgb` or `Bgra8UnormSrgb` for video frames.
+    pub fn new(state: &mut State, width: u32, height: u32, format: wgpu::TextureFormat, sampler: &SamplerOptions) -> Result<Self> {
+        let info = format.describe();
+        if info.block_dimensions != (1, 1) {
+            bail!("{:?} is compressed and can't be streamed", format);
+        }
+
+        let size = wgpu::Extent3d {
+            width: width.max(1),
+            height: height.max(1),
+            depth_or_array_layers: 1,
+        };
+        let texture = state.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("panels:streaming"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = state.sampler(sampler);
+        let texture = state.add_texture(Texture {
+            texture,
+            size,
+            format,
+            view_dimension: wgpu::TextureViewDimension::D2,
+            view,
+            sampler,
+        });
+        let bind_group = state.texture_bind_group(texture);
+
+        let bytes_per_pixel = info.block_size as u32;
+        let buffer_size = padded_bytes_per_row(size.width * bytes_per_pixel) as wgpu::BufferAddress * size.height as wgpu::BufferAddress;
+        let ring = (0..STAGING_RING_SIZE).map(|_| StagingBuffer::new(&state.device, buffer_size)).collect();
+
+        Ok(Self {
+            texture,
+            bind_group,
+            width: size.width,
+            height: size.height,
+            bytes_per_pixel,
+            ring,
+            fallback_uploads: 0,
+        })
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// Replace the whole texture. `texels` are tightly packed rows.
+    pub fn write_frame(&mut self, state: &State, texels: &[u8]) {
+        self.write_rect(state, [0, 0], [self.width, self.height], texels);
+    }
+
+    /// Replace the `size` texels at `origin`. `texels` are tightly packed rows of the rectangle.
+    pub fn write_rect(&mut self, state: &State, origin: [u32; 2], size: [u32; 2], texels: &[u8]) {
+        assert!(origin[0] + size[0] <= self.width && origin[1] + size[1] <= self.height, "rectangle is outside the streaming texture");
+        let row = size[0] * self.bytes_per_pixel;
+        assert!(texels.len() >= (row * size[1]) as usize, "not enough texels for the rectangle");
+        if size[0] == 0 || size[1] == 0 {
+            return;
+        }
+
+        self.poll(state);
+        let texture = &state.textures[self.texture].texture;
+        let destination = wgpu::ImageCopyTexture {
+            texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d { x: origin[0], y: origin[1], z: 0 },
+            aspect: wgpu::TextureAspect::All,
+        };
+        let extent = wgpu::Extent3d { width: size[0], height: size[1], depth_or_array_layers: 1 };
+
+        let staging = match self.ring.iter_mut().find(|s| s.mapping.is_none()) {
+            Some(s) => s,
+            None => {
+                self.fallback_uploads += 1;
+                state.queue.write_texture(
+                    destination,
+                    texels,
+                    wgpu::ImageDataLayout {
+                        offset: 0,
+                        bytes_per_row: std::num::NonZeroU32::new(row),
+                        rows_per_image: std::num::NonZeroU32::new(size[1]),
+                    },
+                    extent,
+                );
+                return;
+            }
+        };
+
+        // buffer copies need rows aligned to `COPY_BYTES_PER_ROW_ALIGNMENT`
+        let padded = padded_bytes_per_row(row);
+        {
+            let mut mapped = staging.buffer.slice(..).get_mapped_range_mut();
+            for (y, src) in texels.chunks_exact(row as usize).take(size[1] as usize).enumerate() {
+                let start = y * padded as usize;
+                mapped[start..start + row as usize].copy_from_slice(src);
+            }
+        }
+        staging.buffer.unmap();
+
+        let mut encoder = state.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("panels:streaming"),
+        });
+        encoder.copy_buffer_to_texture(
+            wgpu::ImageCopyBuffer {
+                buffer: &staging.buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: std::num::NonZeroU32::new(padded),
+                    rows_per_image: std::num::NonZeroU32::new(size[1]),
+                },
+            },
+            destination,
+            extent,
+        );
+        state.queue.submit(std::iter::once(encoder.finish()));
+        staging.mapping = Some(Box::pin(staging.buffer.slice(..).map_async(wgpu::MapMode::Write)));
+    }
+
+    /// Reclaim staging buffers whose copies have finished. Called by every write.
+    pub fn poll(&mut self, state: &State) {
+        state.device.poll(wgpu::Maintain::Poll);
+        let mut cx = Context::from_waker(Waker::noop());
+        for staging in self.ring.iter_mut() {
+            let result = match staging.mapping.as_mut() {
+                Some(future) => future.as_mut().poll(&mut cx),
+                None => continue,
+            };
+            match result {
+                Poll::Ready(Result::Ok(())) => staging.mapping = None,
+                // the buffer can't be mapped again, so replace it
+                Poll::Ready(Err(_)) => *staging = StagingBuffer::new(&state.device, staging.size),
+                Poll::Pending => {}
+            }
+        }
+    }
+
+    /// Staging buffers free for the next upload.
+    pub fn free_staging_buffers(&self) -> usize {
+        self.ring.iter().filter(|s| s.mapping.is_none()).count()
+    }
+}
+
+fn padded_bytes_per_row(bytes: u32) -> u32 {
+    let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    bytes.div_ceil(align) * align
+}
@@ -0,0 +1,666 @@
+use anyhow::*;
+use image::DynamicImage;
+
+/// How 8 and 16 bit color values in an image are meant to be read.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ColorSpace {
+    /// Colors are sRGB encoded, e.g. photos and UI art. Sampling returns linear values.
+    #[default]
+    Srgb,
+    /// Values are used as they are, e.g. normal maps, masks and lookup tables.
+    Linear,
+}
+
+/// The float format 16-bit and HDR images are loaded into.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum FloatPrecision {
+    /// `Rgba16Float`, which can be filtered and rendered to.
+    #[default]
+    Half,
+    /// `Rgba32Float`, which can't be filtered; sample it with a non-filtering layout and a `Nearest` sampler.
+    Full,
+}
+
+/// Texels ready to be uploaded, with every mip level the source had.
+pub struct TextureData {
+    pub format: wgpu::TextureFormat,
+    pub width: u32,
+    pub height: u32,
+    /// Tightly packed levels, largest first.
+    pub levels: Vec<Vec<u8>>,
+}
+
+const KTX2_MAGIC: [u8; 12] = [0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A];
+const DDS_MAGIC: &[u8] = b"DDS ";
+const EXR_MAGIC: [u8; 4] = [0x76, 0x2F, 0x31, 0x01];
+
+impl TextureData {
+    /// Decode any supported file: KTX2, DDS, Radiance HDR, OpenEXR, or anything the `image` crate reads.
+    pub fn from_bytes(bytes: &[u8], color_space: ColorSpace, precision: FloatPrecision) -> Result<Self> {
+        if bytes.starts_with(&KTX2_MAGIC) {
+            Self::from_ktx2(bytes)
+        } else if bytes.starts_with(DDS_MAGIC) {
+            Self::from_dds(bytes, color_space)
+        } else if bytes.starts_with(b"#?RADIANCE") || bytes.starts_with(b"#?RGBE") {
+            Self::from_hdr(bytes, precision)
+        } else if bytes.starts_with(&EXR_MAGIC) {
+            Self::from_exr(bytes, precision)
+        } else {
+            Ok(Self::from_image(&image::load_from_memory(bytes)?, color_space, precision))
+        }
+    }
+
+    /// 8-bit images become `Rgba8UnormSrgb`/`Rgba8Unorm`; 16-bit images become float textures.
+    /// * Images without an alpha channel get an opaque one.
+    pub fn from_image(img: &DynamicImage, color_space: ColorSpace, precision: FloatPrecision) -> Self {
+        match img {
+            DynamicImage::ImageLuma16(_) | DynamicImage::ImageLumaA16(_) | DynamicImage::ImageRgb16(_) | DynamicImage::ImageRgba16(_) => {
+                let rgba = img.to_rgba16();
+                let (width, height) = rgba.dimensions();
+                let texels: Vec<f32> = rgba.as_raw().chunks_exact(4).flat_map(|p| {
+                    let c = |v: u16| {
+                        let v = v as f32 / 65535.0;
+                        match color_space {
+                            ColorSpace::Srgb => srgb_to_linear(v),
+                            ColorSpace::Linear => v,
+                        }
+                    };
+                    [c(p[0]), c(p[1]), c(p[2]), p[3] as f32 / 65535.0]
+                }).collect();
+                Self::from_f32(width, height, &texels, precision)
+            }
+            _ => {
+                let rgba = img.to_rgba8();
+                let (width, height) = rgba.dimensions();
+                let format = match color_space {
+                    ColorSpace::Srgb => wgpu::TextureFormat::Rgba8UnormSrgb,
+                    ColorSpace::Linear => wgpu::TextureFormat::Rgba8Unorm,
+                };
+                Self { format, width, height, levels: vec![rgba.into_raw()] }
+            }
+        }
+    }
+
+    /// Decode a Radiance `.hdr` image, which holds linear values.
+    pub fn from_hdr(bytes: &[u8], precision: FloatPrecision) -> Result<Self> {
+        let decoder = image::codecs::hdr::HdrDecoder::new(bytes)?;
+        let meta = decoder.metadata();
+        let texels: Vec<f32> = decoder.read_image_hdr()?.iter()
+            .flat_map(|p| [p.0[0], p.0[1], p.0[2], 1.0])
+            .collect();
+        Ok(Self::from_f32(meta.width, meta.height, &texels, precision))
+    }
+
+    /// Decode a scanline OpenEXR image, which holds linear values.
+    /// * Reads `R`, `G`, `B` and `A`, or `Y` as gray, in `HALF`, `FLOAT` or `UINT`. Missing colors are 0 and missing alpha is 1.
+    /// * Only uncompressed, RLE, ZIPS and ZIP compression are supported, not tiled or multi-part files.
+    pub fn from_exr(bytes: &[u8], precision: FloatPrecision) -> Result<Self> {
+        if !bytes.starts_with(&EXR_MAGIC) {
+            bail!("not an OpenEXR file");
+        }
+        let version = read_u32(bytes, 4)?;
+        if version & 0x200 != 0 {
+            bail!("tiled OpenEXR images aren't supported");
+        }
+        if version & 0x1800 != 0 {
+            bail!("multi-part and deep OpenEXR images aren't supported");
+        }
+
+        let mut pos = 8;
+        let mut channels: Vec<(String, u32)> = vec![];
+        let mut compression = None;
+        let mut window = None;
+        loop {
+            let name = read_cstr(bytes, &mut pos)?;
+            if name.is_empty() {
+                break;
+            }
+            let ty = read_cstr(bytes, &mut pos)?;
+            let size = read_u32(bytes, pos)? as usize;
+            pos += 4;
+            let value = bytes.get(pos..pos + size).ok_or_else(|| anyhow!("OpenEXR attribute {} is truncated", name))?;
+            pos += size;
+            match (name.as_str(), ty.as_str()) {
+                ("channels", "chlist") => {
+                    let mut at = 0;
+                    loop {
+                        let channel = read_cstr(value, &mut at)?;
+                        if channel.is_empty() {
+                            break;
+                        }
+                        let pixel_type = read_u32(value, at)?;
+                        if read_u32(value, at + 8)? != 1 || read_u32(value, at + 12)? != 1 {
+                            bail!("OpenEXR channel {} is subsampled, which isn't supported", channel);
+                        }
+                        channels.push((channel, pixel_type));
+                        at += 16;
+                    }
+                }
+                ("compression", "compression") => compression = value.first().copied(),
+                ("dataWindow", "box2i") => {
+                    let c = |i: usize| read_u32(value, i * 4).map(|v| v as i32);
+                    window = Some([c(0)?, c(1)?, c(2)?, c(3)?]);
+                }
+                _ => {}
+            }
+        }
+        let [x_min, y_min, x_max, y_max] = window.ok_or_else(|| anyhow!("OpenEXR header has no dataWindow"))?;
+        if x_max < x_min || y_max < y_min {
+            bail!("OpenEXR dataWindow is empty");
+        }
+        let (width, height) = ((x_max - x_min + 1) as u32, (y_max - y_min + 1) as u32);
+        let lines_per_chunk = match compression.ok_or_else(|| anyhow!("OpenEXR header has no compression"))? {
+            0..=2 => 1,
+            3 => 16,
+            c => bail!("OpenEXR compression {} isn't supported, only none, RLE, ZIPS and ZIP", c),
+        };
+        let sizes = channels.iter().map(|(name, ty)| match ty {
+            1 => Ok(2),
+            0 | 2 => Ok(4),
+            _ => bail!("OpenEXR channel {} has an unknown pixel type {}", name, ty),
+        }).collect::<Result<Vec<usize>>>()?;
+        let line_bytes: usize = sizes.iter().sum::<usize>() * width as usize;
+        // where each of R, G, B and A comes from, `Y` filling in for missing colors
+        let find = |n: &str| channels.iter().position(|(name, _)| name == n);
+        let sources = [
+            find("R").or_else(|| find("Y")),
+            find("G").or_else(|| find("Y")),
+            find("B").or_else(|| find("Y")),
+            find("A"),
+        ];
+
+        let mut texels = [0.0, 0.0, 0.0, 1.0].repeat(width as usize * height as usize);
+        let chunk_count = height.div_ceil(lines_per_chunk);
+        for chunk in 0..chunk_count as usize {
+            let offset = read_u64(bytes, pos + chunk * 8)? as usize;
+            let y = read_u32(bytes, offset)? as i32;
+            let size = read_u32(bytes, offset + 4)? as usize;
+            let data = bytes.get(offset + 8..offset + 8 + size).ok_or_else(|| anyhow!("OpenEXR chunk {} is truncated", chunk))?;
+            let first = (y - y_min) as u32;
+            let lines = lines_per_chunk.min(height.saturating_sub(first));
+            let expected = line_bytes * lines as usize;
+            let raw = if size == expected || compression == Some(0) {
+                // a chunk that doesn't get smaller is stored as it is
+                data.to_vec()
+            } else if compression == Some(1) {
+                exr_unpredict(&exr_rle_decode(data, expected)?)
+            } else {
+                let inflated = miniz_oxide::inflate::decompress_to_vec_zlib(data)
+                    .map_err(|e| anyhow!("OpenEXR chunk {} doesn't inflate: {:?}", chunk, e))?;
+                exr_unpredict(&inflated)
+            };
+            if raw.len() != expected {
+                bail!("OpenEXR chunk {} holds {} bytes, expected {}", chunk, raw.len(), expected);
+            }
+
+            for line in 0..lines as usize {
+                let row = first as usize + line;
+                let mut at = line * line_bytes;
+                for (c, size) in sizes.iter().enumerate() {
+                    for x in 0..width as usize {
+                        let b = &raw[at + x * size..at + (x + 1) * size];
+                        let value = match channels[c].1 {
+                            1 => f16_to_f32(u16::from_le_bytes([b[0], b[1]])),
+                            0 => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f32,
+                            _ => f32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+                        };
+                        let texel = (row * width as usize + x) * 4;
+                        for (component, source) in sources.iter().enumerate() {
+                            if *source == Some(c) {
+                                texels[texel + component] = value;
+                            }
+                        }
+                    }
+                    at += size * width as usize;
+                }
+            }
+        }
+        Ok(Self::from_f32(width, height, &texels, precision))
+    }
+
+    /// RGBA float texels.
+    pub fn from_f32(width: u32, height: u32, texels: &[f32], precision: FloatPrecision) -> Self {
+        let (format, data) = match precision {
+            FloatPrecision::Half => {
+                let half: Vec<u16> = texels.iter().map(|v| f32_to_f16(*v)).collect();
+                (wgpu::TextureFormat::Rgba16Float, bytemuck::cast_slice(&half).to_vec())
+            }
+            FloatPrecision::Full => (wgpu::TextureFormat::Rgba32Float, bytemuck::cast_slice(texels).to_vec()),
+        };
+        Self { format, width, height, levels: vec![data] }
+    }
+
+    /// Read a KTX2 file without supercompression. Only the first layer/face is used.
+    pub fn from_ktx2(bytes: &[u8]) -> Result<Self> {
+        if !bytes.starts_with(&KTX2_MAGIC) {
+            bail!("not a KTX2 file");
+        }
+        let vk_format = read_u32(bytes, 12)?;
+        let width = read_u32(bytes, 20)?;
+        let height = read_u32(bytes, 24)?.max(1);
+        let level_count = read_u32(bytes, 40)?.max(1);
+        let supercompression = read_u32(bytes, 44)?;
+        if supercompression != 0 {
+            bail!("KTX2 supercompression scheme {} isn't supported", supercompression);
+        }
+        let format = match vk_format {
+            37 => wgpu::TextureFormat::Rgba8Unorm,
+            43 => wgpu::TextureFormat::Rgba8UnormSrgb,
+            97 => wgpu::TextureFormat::Rgba16Float,
+            109 => wgpu::TextureFormat::Rgba32Float,
+            131 | 133 => wgpu::TextureFormat::Bc1RgbaUnorm,
+            132 | 134 => wgpu::TextureFormat::Bc1RgbaUnormSrgb,
+            135 => wgpu::TextureFormat::Bc2RgbaUnorm,
+            136 => wgpu::TextureFormat::Bc2RgbaUnormSrgb,
+            137 => wgpu::TextureFormat::Bc3RgbaUnorm,
+            138 => wgpu::TextureFormat::Bc3RgbaUnormSrgb,
+            139 => wgpu::TextureFormat::Bc4RUnorm,
+            140 => wgpu::TextureFormat::Bc4RSnorm,
+            141 => wgpu::TextureFormat::Bc5RgUnorm,
+            142 => wgpu::TextureFormat::Bc5RgSnorm,
+            143 => wgpu::TextureFormat::Bc6hRgbUfloat,
+            144 => wgpu::TextureFormat::Bc6hRgbSfloat,
+            145 => wgpu::TextureFormat::Bc7RgbaUnorm,
+            146 => wgpu::TextureFormat::Bc7RgbaUnormSrgb,
+            _ => bail!("KTX2 vkFormat {} isn't supported", vk_format),
+        };
+
+        let mut levels = Vec::with_capacity(level_count as usize);
+        for level in 0..level_count {
+            let index = 80 + level as usize * 24;
+            let offset = read_u64(bytes, index)? as usize;
+            // a level holds every layer and face; the first image comes first
+            let size = level_size(format, width, height, level);
+            let data = bytes.get(offset..offset + size).ok_or_else(|| anyhow!("KTX2 level {} is truncated", level))?;
+            levels.push(data.to_vec());
+        }
+        Ok(Self { format, width, height, levels })
+    }
+
+    /// Read a DDS file holding BCn data, or any supported format with a DX10 header. Only the first image is used.
+    /// * Legacy DXT files don't say whether they are sRGB, so `color_space` decides.
+    pub fn from_dds(bytes: &[u8], color_space: ColorSpace) -> Result<Self> {
+        if !bytes.starts_with(DDS_MAGIC) {
+            bail!("not a DDS file");
+        }
+        let flags = read_u32(bytes, 8)?;
+        let height = read_u32(bytes, 12)?;
+        let width = read_u32(bytes, 16)?;
+        // DDSD_MIPMAPCOUNT
+        let level_count = if flags & 0x20000 != 0 { read_u32(bytes, 28)?.max(1) } else { 1 };
+        let four_cc = bytes.get(84..88).ok_or_else(|| anyhow!("DDS header is truncated"))?;
+        let srgb = color_space == ColorSpace::Srgb;
+
+        let (format, mut offset) = match four_cc {
+            b"DXT1" => (if srgb { wgpu::TextureFormat::Bc1RgbaUnormSrgb } else { wgpu::TextureFormat::Bc1RgbaUnorm }, 128),
+            b"DXT2" | b"DXT3" => (if srgb { wgpu::TextureFormat::Bc2RgbaUnormSrgb } else { wgpu::TextureFormat::Bc2RgbaUnorm }, 128),
+            b"DXT4" | b"DXT5" => (if srgb { wgpu::TextureFormat::Bc3RgbaUnormSrgb } else { wgpu::TextureFormat::Bc3RgbaUnorm }, 128),
+            b"ATI1" | b"BC4U" => (wgpu::TextureFormat::Bc4RUnorm, 128),
+            b"BC4S" => (wgpu::TextureFormat::Bc4RSnorm, 128),
+            b"ATI2" | b"BC5U" => (wgpu::TextureFormat::Bc5RgUnorm, 128),
+            b"BC5S" => (wgpu::TextureFormat::Bc5RgSnorm, 128),
+            b"DX10" => {
+                let dxgi = read_u32(bytes, 128)?;
+                let format = match dxgi {
+                    2 => wgpu::TextureFormat::Rgba32Float,
+                    10 => wgpu::TextureFormat::Rgba16Float,
+                    28 => wgpu::TextureFormat::Rgba8Unorm,
+                    29 => wgpu::TextureFormat::Rgba8UnormSrgb,
+                    71 => wgpu::TextureFormat::Bc1RgbaUnorm,
+                    72 => wgpu::TextureFormat::Bc1RgbaUnormSrgb,
+                    74 => wgpu::TextureFormat::Bc2RgbaUnorm,
+                    75 => wgpu::TextureFormat::Bc2RgbaUnormSrgb,
+                    77 => wgpu::TextureFormat::Bc3RgbaUnorm,
+                    78 => wgpu::TextureFormat::Bc3RgbaUnormSrgb,
+                    80 => wgpu::TextureFormat::Bc4RUnorm,
+                    81 => wgpu::TextureFormat::Bc4RSnorm,
+                    83 => wgpu::TextureFormat::Bc5RgUnorm,
+                    84 => wgpu::TextureFormat::Bc5RgSnorm,
+                    95 => wgpu::TextureFormat::Bc6hRgbUfloat,
+                    96 => wgpu::TextureFormat::Bc6hRgbSfloat,
+                    98 => wgpu::TextureFormat::Bc7RgbaUnorm,
+                    99 => wgpu::TextureFormat::Bc7RgbaUnormSrgb,
+                    _ => bail!("DXGI format {} isn't supported", dxgi),
+                };
+                (format, 148)
+            }
+            _ => bail!("DDS pixel format {:?} isn't supported", String::from_utf8_lossy(four_cc)),
+        };
+
+        let mut levels = Vec::with_capacity(level_count as usize);
+        for level in 0..level_count {
+            let size = level_size(format, width, height, level);
+            let data = bytes.get(offset..offset + size).ok_or_else(|| anyhow!("DDS level {} is truncated", level))?;
+            levels.push(data.to_vec());
+            offset += size;
+        }
+        Ok(Self { format, width, height, levels })
+    }
+
+    /// The size of a level in texels, rounded up to whole blocks.
+    pub fn level_extent(&self, level: u32) -> wgpu::Extent3d {
+        wgpu::Extent3d {
+            width: (self.width >> level).max(1),
+            height: (self.height >> level).max(1),
+            depth_or_array_layers: 1,
+        }.physical_size(self.format)
+    }
+
+    /// The bytes in one row of blocks of a level.
+    pub fn bytes_per_row(&self, level: u32) -> u32 {
+        let info = self.format.describe();
+        let blocks = self.level_extent(level).width / info.block_dimensions.0 as u32;
+        blocks * info.block_size as u32
+    }
+}
+
+/// Bytes in a tightly packed mip level.
+fn level_size(format: wgpu::TextureFormat, width: u32, height: u32, level: u32) -> usize {
+    let info = format.describe();
+    let (bw, bh) = (info.block_dimensions.0 as u32, info.block_dimensions.1 as u32);
+    let (w, h) = ((width >> level).max(1), (height >> level).max(1));
+    (w.div_ceil(bw) * h.div_ceil(bh) * info.block_size as u32) as usize
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32> {
+    let b = bytes.get(offset..offset + 4).ok_or_else(|| anyhow!("header is truncated"))?;
+    Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Result<u64> {
+    Ok(read_u32(bytes, offset)? as u64 | (read_u32(bytes, offset + 4)? as u64) << 32)
+}
+
+/// A null-terminated string, moving `pos` past it.
+fn read_cstr(bytes: &[u8], pos: &mut usize) -> Result<String> {
+    let rest = bytes.get(*pos..).unwrap_or_default();
+    let end = rest.iter().position(|b| *b == 0).ok_or_else(|| anyhow!("header is truncated"))?;
+    *pos += end + 1;
+    Ok(String::from_utf8_lossy(&rest[..end]).into_owned())
+}
+
+/// Undo OpenEXR's RLE: a negative count is followed by that many literal bytes, any other count by one byte repeated count + 1 times.
+fn exr_rle_decode(data: &[u8], expected: usize) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(expected);
+    let mut i = 0;
+    while i < data.len() {
+        let count = data[i] as i8;
+        i += 1;
+        if count < 0 {
+            let literal = data.get(i..i + (-(count as i32)) as usize).ok_or_else(|| anyhow!("OpenEXR RLE data is truncated"))?;
+            out.extend_from_slice(literal);
+            i += literal.len();
+        } else {
+            let value = *data.get(i).ok_or_else(|| anyhow!("OpenEXR RLE data is truncated"))?;
+            out.extend(std::iter::repeat_n(value, count as usize + 1));
+            i += 1;
+        }
+    }
+    Ok(out)
+}
+
+/// Undo the delta predictor and byte split OpenEXR applies before RLE and ZIP compression.
+fn exr_unpredict(data: &[u8]) -> Vec<u8> {
+    let mut deltas = data.to_vec();
+    for i in 1..deltas.len() {
+        deltas[i] = deltas[i - 1].wrapping_add(deltas[i]).wrapping_sub(128);
+    }
+    // the first half holds the even bytes, the second half the odd ones
+    let half = deltas.len().div_ceil(2);
+    (0..deltas.len()).map(|i| if i % 2 == 0 { deltas[i / 2] } else { deltas[half + i / 2] }).collect()
+}
+
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn f16_to_f32(half: u16) -> f32 {
+    let sign = ((half & 0x8000) as u32) << 16;
+    let exponent = ((half >> 10) & 0x1f) as u32;
+    let mantissa = (half & 0x3ff) as u32;
+    let bits = match exponent {
+        0 if mantissa == 0 => sign,
+        // subnormal: normalize the mantissa
+        0 => {
+            let shift = mantissa.leading_zeros() - 21;
+            sign | ((113 - shift) << 23) | ((mantissa << shift) & 0x3ff) << 13
+        }
+        0x1f => sign | 0x7f80_0000 | mantissa << 13,
+        _ => sign | ((exponent + 112) << 23) | mantissa << 13,
+    };
+    f32::from_bits(bits)
+}
+
+/// Round to the nearest half float.
+fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+    if exponent == 0xff {
+        // infinity, or NaN kept quiet
+        return sign | 0x7c00 | if mantissa != 0 { 0x200 } else { 0 };
+    }
+    let e = exponent - 127 + 15;
+    if e >= 0x1f {
+        return sign | 0x7c00;
+    }
+    if e <= 0 {
+        // subnormal, or too small and flushed to zero
+        if e < -10 {
+            return sign;
+        }
+        let m = mantissa | 0x80_0000;
+        let shift = (14 - e) as u32;
+        let round = (m >> (shift - 1)) & 1;
+        return sign | ((m >> shift) + round) as u16;
+    }
+    let half = sign | ((e as u16) << 10) | (mantissa >> 13) as u16;
+    // a carry out of the mantissa correctly bumps the exponent
+    half + ((mantissa >> 12) & 1) as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u32s(values: &[u32]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    fn floats(data: &TextureData) -> Vec<f32> {
+        data.levels[0].chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect()
+    }
+
+    #[test]
+    fn half_floats() {
+        let cases: [(f32, u16); 10] = [
+            (0.0, 0x0000),
+            (-0.0, 0x8000),
+            (1.0, 0x3c00),
+            (-2.0, 0xc000),
+            (0.5, 0x3800),
+            (0.1, 0x2e66),
+            (65504.0, 0x7bff),
+            // the smallest subnormal
+            (5.960_464_5e-8, 0x0001),
+            (f32::INFINITY, 0x7c00),
+            (f32::NEG_INFINITY, 0xfc00),
+        ];
+        for (value, half) in cases {
+            assert_eq!(f32_to_f16(value), half, "{}", value);
+        }
+        // too big rounds to infinity, too small to zero
+        assert_eq!(f32_to_f16(65520.0), 0x7c00);
+        assert_eq!(f32_to_f16(1e-9), 0);
+        assert!(f16_to_f32(f32_to_f16(f32::NAN)).is_nan());
+        // every finite half survives the round trip
+        for half in (0..=0xffffu16).filter(|h| h & 0x7c00 != 0x7c00) {
+            assert_eq!(f32_to_f16(f16_to_f32(half)), half, "{:#06x}", half);
+        }
+    }
+
+    #[test]
+    fn level_sizes() {
+        assert_eq!(level_size(wgpu::TextureFormat::Rgba8Unorm, 4, 4, 0), 64);
+        assert_eq!(level_size(wgpu::TextureFormat::Rgba8Unorm, 4, 4, 1), 16);
+        assert_eq!(level_size(wgpu::TextureFormat::Rgba8Unorm, 4, 4, 5), 4);
+        assert_eq!(level_size(wgpu::TextureFormat::Rgba16Float, 3, 1, 0), 24);
+        // blocks are rounded up, and never fewer than one
+        assert_eq!(level_size(wgpu::TextureFormat::Bc1RgbaUnorm, 5, 5, 0), 32);
+        assert_eq!(level_size(wgpu::TextureFormat::Bc1RgbaUnorm, 5, 5, 2), 8);
+        assert_eq!(level_size(wgpu::TextureFormat::Bc7RgbaUnorm, 8, 4, 0), 32);
+    }
+
+    fn ktx2(vk_format: u32, supercompression: u32, levels: &[&[u8]]) -> Vec<u8> {
+        let mut bytes = KTX2_MAGIC.to_vec();
+        // vkFormat, typeSize, width, height, depth, layers, faces, levels, supercompression
+        bytes.extend(u32s(&[vk_format, 1, 2, 2, 0, 0, 1, levels.len() as u32, supercompression]));
+        // dfd, kvd and sgd ranges
+        bytes.extend(u32s(&[0; 8]));
+        let mut offset = 80 + levels.len() * 24;
+        for level in levels {
+            bytes.extend(u32s(&[offset as u32, 0, level.len() as u32, 0, level.len() as u32, 0]));
+            offset += level.len();
+        }
+        for level in levels {
+            bytes.extend_from_slice(level);
+        }
+        bytes
+    }
+
+    #[test]
+    fn ktx2_levels() {
+        let level0: Vec<u8> = (0..16).collect();
+        let level1 = [200u8, 201, 202, 203];
+        let data = TextureData::from_bytes(&ktx2(43, 0, &[&level0, &level1]), ColorSpace::Linear, FloatPrecision::Half).unwrap();
+        assert_eq!(data.format, wgpu::TextureFormat::Rgba8UnormSrgb);
+        assert_eq!((data.width, data.height), (2, 2));
+        assert_eq!(data.levels, vec![level0.clone(), level1.to_vec()]);
+
+        assert!(TextureData::from_ktx2(&ktx2(43, 2, &[&level0])).is_err());
+        assert!(TextureData::from_ktx2(&ktx2(1, 0, &[&level0])).is_err());
+        assert!(TextureData::from_ktx2(&ktx2(43, 0, &[&level0[..8]])).is_err());
+    }
+
+    fn dds(four_cc: &[u8; 4], dx10: Option<u32>, levels: u32, width: u32, height: u32, data: &[u8]) -> Vec<u8> {
+        let mut bytes = DDS_MAGIC.to_vec();
+        // size, flags with DDSD_MIPMAPCOUNT, height, width, pitch, depth, mip count
+        bytes.extend(u32s(&[124, 0x2_1007, height, width, 0, 0, levels]));
+        bytes.resize(84, 0);
+        bytes.extend_from_slice(four_cc);
+        bytes.resize(128, 0);
+        if let Some(dxgi) = dx10 {
+            bytes.extend(u32s(&[dxgi, 3, 0, 1, 0]));
+        }
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    #[test]
+    fn dds_levels() {
+        // 8x4 is two BC1 blocks, then 4x2 is one
+        let blocks: Vec<u8> = (0..24).collect();
+        let srgb = TextureData::from_bytes(&dds(b"DXT1", None, 2, 8, 4, &blocks), ColorSpace::Srgb, FloatPrecision::Half).unwrap();
+        assert_eq!(srgb.format, wgpu::TextureFormat::Bc1RgbaUnormSrgb);
+        assert_eq!(srgb.levels, vec![blocks[..16].to_vec(), blocks[16..].to_vec()]);
+        let linear = TextureData::from_dds(&dds(b"DXT1", None, 2, 8, 4, &blocks), ColorSpace::Linear).unwrap();
+        assert_eq!(linear.format, wgpu::TextureFormat::Bc1RgbaUnorm);
+
+        let texels = [7u8; 8];
+        let half = TextureData::from_dds(&dds(b"DX10", Some(10), 1, 1, 1, &texels), ColorSpace::Srgb).unwrap();
+        assert_eq!(half.format, wgpu::TextureFormat::Rgba16Float);
+        assert_eq!(half.levels, vec![texels.to_vec()]);
+
+        assert!(TextureData::from_dds(&dds(b"DXT1", None, 2, 8, 4, &blocks[..20]), ColorSpace::Srgb).is_err());
+        assert!(TextureData::from_dds(&dds(b"NOPE", None, 1, 4, 4, &blocks), ColorSpace::Srgb).is_err());
+        assert!(TextureData::from_dds(&dds(b"DX10", Some(1), 1, 1, 1, &texels), ColorSpace::Srgb).is_err());
+    }
+
+    fn attribute(name: &str, ty: &str, value: &[u8]) -> Vec<u8> {
+        let mut bytes = [name.as_bytes(), &[0], ty.as_bytes(), &[0]].concat();
+        bytes.extend(u32s(&[value.len() as u32]));
+        bytes.extend_from_slice(value);
+        bytes
+    }
+
+    /// A scanline EXR with HALF `B`, `G` and `R` channels.
+    fn exr(compression: u8, width: u32, height: u32, chunks: &[(u32, Vec<u8>)]) -> Vec<u8> {
+        let mut channels = vec![];
+        for name in ["B", "G", "R"] {
+            channels.extend([name.as_bytes(), &[0]].concat());
+            channels.extend(u32s(&[1, 0, 1, 1]));
+        }
+        channels.push(0);
+        let mut bytes = EXR_MAGIC.to_vec();
+        bytes.extend(u32s(&[2]));
+        bytes.extend(attribute("channels", "chlist", &channels));
+        bytes.extend(attribute("compression", "compression", &[compression]));
+        bytes.extend(attribute("dataWindow", "box2i", &u32s(&[0, 0, width - 1, height - 1])));
+        bytes.extend(attribute("lineOrder", "lineOrder", &[0]));
+        bytes.push(0);
+        let mut offset = bytes.len() + chunks.len() * 8;
+        for (_, data) in chunks {
+            bytes.extend(u32s(&[offset as u32, 0]));
+            offset += 8 + data.len();
+        }
+        for (y, data) in chunks {
+            bytes.extend(u32s(&[*y, data.len() as u32]));
+            bytes.extend_from_slice(data);
+        }
+        bytes
+    }
+
+    /// One line of 2 texels in channel order B, G, R.
+    fn exr_line(texels: [[f32; 3]; 2]) -> Vec<u8> {
+        (0..3).rev().flat_map(|c| texels.iter().flat_map(move |t| f32_to_f16(t[c]).to_le_bytes())).collect()
+    }
+
+    /// The inverse of `exr_unpredict`.
+    fn exr_predict(data: &[u8]) -> Vec<u8> {
+        let split: Vec<u8> = data.iter().step_by(2).chain(data.iter().skip(1).step_by(2)).copied().collect();
+        (0..split.len()).map(|i| if i == 0 { split[0] } else { split[i].wrapping_sub(split[i - 1]).wrapping_add(128) }).collect()
+    }
+
+    #[test]
+    fn exr_uncompressed() {
+        let rows = [[[1.0, 0.5, 0.25], [2.0, 0.0, -1.0]], [[0.125, 8.0, 3.0], [0.0, 0.0, 100.0]]];
+        let file = exr(0, 2, 2, &[(0, exr_line(rows[0])), (1, exr_line(rows[1]))]);
+        let data = TextureData::from_bytes(&file, ColorSpace::Srgb, FloatPrecision::Full).unwrap();
+        assert_eq!(data.format, wgpu::TextureFormat::Rgba32Float);
+        assert_eq!((data.width, data.height), (2, 2));
+        let expected: Vec<f32> = rows.iter().flatten().flat_map(|t| [t[0], t[1], t[2], 1.0]).collect();
+        assert_eq!(floats(&data), expected);
+    }
+
+    #[test]
+    fn exr_rle_and_zip() {
+        let rows = [[[1.0, 0.5, 0.25], [2.0, 0.0, -1.0]], [[0.125, 8.0, 3.0], [0.0, 0.0, 100.0]]];
+        let expected: Vec<f32> = rows.iter().flatten().flat_map(|t| [t[0], t[1], t[2], 1.0]).collect();
+
+        let rle = |line: Vec<u8>| {
+            let predicted = exr_predict(&line);
+            [vec![(-(predicted.len() as i8)) as u8], predicted].concat()
+        };
+        let file = exr(1, 2, 2, &[(0, rle(exr_line(rows[0]))), (1, rle(exr_line(rows[1])))]);
+        assert_eq!(floats(&TextureData::from_exr(&file, FloatPrecision::Full).unwrap()), expected);
+
+        // ZIP holds 16 lines per chunk
+        let lines = [exr_line(rows[0]), exr_line(rows[1])].concat();
+        let zipped = miniz_oxide::deflate::compress_to_vec_zlib(&exr_predict(&lines), 6);
+        let file = exr(3, 2, 2, &[(0, zipped)]);
+        assert_eq!(floats(&TextureData::from_exr(&file, FloatPrecision::Full).unwrap()), expected);
+
+        assert!(TextureData::from_exr(&exr(4, 2, 2, &[(0, lines)]), FloatPrecision::Full).is_err());
+    }
+
+    #[test]
+    fn exr_rle_runs() {
+        assert_eq!(exr_rle_decode(&[2, 9, (-2i8) as u8, 1, 2], 5).unwrap(), vec![9, 9, 9, 1, 2]);
+        assert!(exr_rle_decode(&[(-3i8) as u8, 1], 3).is_err());
+    }
+}
@@ -0,0 +1,562 @@
+use std::collections::HashMap;
+use anyhow::*;
+use rusttype::{GlyphId, Scale};
+use winit::dpi::PhysicalSize;
+use crate::atlas::ShelfPacker;
+use crate::dynbuffer::{DynamicBuffer, DynamicSlice};
+use crate::pipelines::{PipelineTarget, TargetPipelines};
+use crate::rect::WorldRectangle;
+use crate::wgpustate::State;
+
+/// A TrueType/OpenType font, loaded from bytes.
+pub struct Font {
+    font: rusttype::Font<'static>,
+}
+
+impl Font {
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self> {
+        match rusttype::Font::try_from_vec(bytes) {
+            Some(font) => Ok(Self { font }),
+            None => Err(anyhow!("Could not parse font data")),
+        }
+    }
+
+    /// The distance between two baselines at this pixel size, before any `line_spacing`.
+    pub fn line_height(&self, size: f32) -> f32 {
+        let v = self.font.v_metrics(Scale::uniform(size));
+        v.ascent - v.descent + v.line_gap
+    }
+
+    /// The distance from the top of a line to it's baseline.
+    pub fn ascent(&self, size: f32) -> f32 {
+        self.font.v_metrics(Scale::uniform(size)).ascent
+    }
+
+    /// The advance of each character of the string, including kerning with the previous character.
+    fn advances<'s>(&'s self, size: f32, text: &'s str) -> impl Iterator<Item = (GlyphId, f32)> + 's {
+        let scale = Scale::uniform(size);
+        let mut prev: Option<GlyphId> = None;
+        text.chars().map(move |c| {
+            let glyph = self.font.glyph(c);
+            let id = glyph.id();
+            let kern = prev.map(|p| self.font.pair_kerning(scale, p, id)).unwrap_or(0.0);
+            prev = Some(id);
+            (id, kern + glyph.scaled(scale).h_metrics().advance_width)
+        })
+    }
+
+    /// The width of a single line of text in pixels.
+    pub fn measure(&self, size: f32, text: &str) -> f32 {
+        self.advances(size, text).map(|(_, a)| a).sum()
+    }
+
+    /// The X offset in pixels of the caret placed before the `char_index`th character of a single line.
+    pub fn caret_x(&self, size: f32, text: &str, char_index: usize) -> f32 {
+        self.advances(size, text).take(char_index).map(|(_, a)| a).sum()
+    }
+
+    /// The character index closest to an X offset in pixels, the inverse of `caret_x`.
+    pub fn hit_test(&self, size: f32, text: &str, x: f32) -> usize {
+        let mut pen = 0.0;
+        for (i, (_, a)) in self.advances(size, text).enumerate() {
+            if x < pen + a / 2.0 {
+                return i;
+            }
+            pen += a;
+        }
+        text.chars().count()
+    }
+
+    /// Splits a paragraph into lines no wider than `max_width`, breaking at spaces where possible.
+    fn wrap(&self, size: f32, paragraph: &str, max_width: f32, lines: &mut Vec<String>) {
+        let mut current = String::new();
+        for word in paragraph.split(' ') {
+            let candidate = if current.is_empty() { word.to_string() } else { format!("{} {}", current, word) };
+            if self.measure(size, &candidate) <= max_width {
+                current = candidate;
+                continue;
+            }
+            if !current.is_empty() {
+                lines.push(std::mem::take(&mut current));
+            }
+            // a single word wider than the line is broken between characters
+            for c in word.chars() {
+                current.push(c);
+                if self.measure(size, &current) > max_width && current.chars().count() > 1 {
+                    current.pop();
+                    lines.push(std::mem::replace(&mut current, c.to_string()));
+                }
+            }
+        }
+        lines.push(current);
+    }
+
+    /// Places every glyph of the text, in pixels relative to the top-left corner of the layout box.
+    /// * `max_width` is the width of the box, used for wrapping and alignment. Without it the widest line is used.
+    pub fn layout(&self, text: &str, size: f32, max_width: Option<f32>, wrap: bool, align: HorizontalAlign, line_spacing: f32) -> TextLayout {
+        let mut lines = vec![];
+        for paragraph in text.split('\n') {
+            match (wrap, max_width) {
+                (true, Some(w)) => self.wrap(size, paragraph, w, &mut lines),
+                _ => lines.push(paragraph.to_string()),
+            }
+        }
+
+        let widths: Vec<f32> = lines.iter().map(|l| self.measure(size, l)).collect();
+        let widest = widths.iter().cloned().fold(0.0, f32::max);
+        let box_width = max_width.unwrap_or(widest);
+        let line_height = self.line_height(size) * line_spacing;
+        let ascent = self.ascent(size);
+
+        let mut glyphs = vec![];
+        for (i, line) in lines.iter().enumerate() {
+            let mut pen = match align {
+                HorizontalAlign::Left => 0.0,
+                HorizontalAlign::Center => (box_width - widths[i]) / 2.0,
+                HorizontalAlign::Right => box_width - widths[i],
+            };
+            let baseline = ascent + i as f32 * line_height;
+            let mut prev: Option<GlyphId> = None;
+            let scale = Scale::uniform(size);
+            for c in line.chars() {
+                let glyph = self.font.glyph(c);
+                let id = glyph.id();
+                if let Some(p) = prev {
+                    pen += self.font.pair_kerning(scale, p, id);
+                }
+                glyphs.push(LayoutGlyph { id, x: pen, baseline, line: i });
+                pen += glyph.scaled(scale).h_metrics().advance_width;
+                prev = Some(id);
+            }
+        }
+
+        TextLayout {
+            glyphs,
+            lines: lines.len(),
+            width: widest,
+            height: lines.len() as f32 * line_height,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum HorizontalAlign {
+    Left,
+    Center,
+    Right,
+}
+
+/// A glyph placed by `Font::layout`, in pixels.
+#[derive(Copy, Clone, Debug)]
+pub struct LayoutGlyph {
+    pub id: GlyphId,
+    /// The pen position, from the left of the layout box.
+    pub x: f32,
+    /// The baseline, from the top of the layout box.
+    pub baseline: f32,
+    pub line: usize,
+}
+
+/// The result of `Font::layout`.
+pub struct TextLayout {
+    pub glyphs: Vec<LayoutGlyph>,
+    pub lines: usize,
+    /// The width of the widest line, in pixels.
+    pub width: f32,
+    pub height: f32,
+}
+
+/// A string to draw, and where.
+#[derive(Clone, Debug)]
+pub struct TextSection<'t> {
+    pub text: &'t str,
+    /// The index of the font in the `State`, from `State::load_font`.
+    pub font: usize,
+    /// The font size in pixels of the render target.
+    pub size: f32,
+    /// Linear RGBA.
+    pub color: [f32; 4],
+    /// Text starts at the top-left corner of this rectangle, and is aligned within it's width.
+    pub rect: WorldRectangle,
+    pub align: HorizontalAlign,
+    /// Wrap lines that are wider than `rect`.
+    pub wrap: bool,
+    /// Multiplier on the font's line height.
+    pub line_spacing: f32,
+}
+
+impl<'t> TextSection<'t> {
+    /// White, left-aligned, wrapping text.
+    pub fn new(text: &'t str, font: usize, size: f32, rect: WorldRectangle) -> Self {
+        Self {
+            text,
+            font,
+            size,
+            color: [1.0, 1.0, 1.0, 1.0],
+            rect,
+            align: HorizontalAlign::Left,
+            wrap: true,
+            line_spacing: 1.0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug,Copy,Clone,bytemuck::Pod,bytemuck::Zeroable)]
+pub struct TextVertex {
+    pub position: [f32; 3],
+    /// Atlas coordinates in pixels.
+    pub uv: [f32; 2],
+    pub color: [f32; 4],
+}
+
+impl TextVertex {
+    pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<TextVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 5]>() as wgpu::BufferAddress,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+            ],
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+struct GlyphKey {
+    font: usize,
+    id: u16,
+    /// Size in quarter pixels.
+    size: u32,
+}
+
+/// Where a rasterized glyph lives in the atlas, in pixels.
+#[derive(Copy, Clone, Debug)]
+struct GlyphInfo {
+    atlas_pos: [f32; 2],
+    size: [f32; 2],
+    /// The top-left of the bitmap relative to the pen position on the baseline.
+    offset: [f32; 2],
+}
+
+/// An `R8Unorm` texture of rasterized glyphs that grows as more glyphs are needed.
+struct GlyphAtlas {
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    size: u32,
+    packer: ShelfPacker,
+    /// `None` for glyphs without any pixels, such as spaces.
+    glyphs: HashMap<GlyphKey, Option<GlyphInfo>>,
+    /// Set when a glyph didn't fit at the size limit. The atlas is cleared by the next `begin_frame`,
+    /// since quads of this frame still point into it.
+    full: bool,
+}
+
+impl GlyphAtlas {
+    const INITIAL_SIZE: u32 = 512;
+    const PADDING: u32 = 1;
+
+    fn create_texture(device: &wgpu::Device, size: u32) -> wgpu::Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("panels:glyph_atlas"),
+            size: wgpu::Extent3d { width: size, height: size, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::COPY_SRC,
+        })
+    }
+
+    fn new(device: &wgpu::Device) -> Self {
+        let texture = Self::create_texture(device, Self::INITIAL_SIZE);
+        Self {
+            view: texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            texture,
+            size: Self::INITIAL_SIZE,
+            packer: ShelfPacker::new(Self::INITIAL_SIZE, Self::INITIAL_SIZE),
+            glyphs: HashMap::new(),
+            full: false,
+        }
+    }
+
+    /// Clear the atlas if it filled up last frame.
+    fn begin_frame(&mut self) {
+        if self.full {
+            log::warn!("Glyph atlas is full at {}px, clearing it", self.size);
+            self.glyphs.clear();
+            self.packer = ShelfPacker::new(self.size, self.size);
+            self.full = false;
+        }
+    }
+
+    /// Doubles the atlas, copying the existing glyphs. Returns `false` at the device's size limit.
+    fn grow(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) -> bool {
+        let new_size = self.size * 2;
+        if new_size > device.limits().max_texture_dimension_2d {
+            return false;
+        }
+        let texture = Self::create_texture(device, new_size);
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("panels:glyph_atlas:grow") });
+        encoder.copy_texture_to_texture(
+            self.texture.as_image_copy(),
+            texture.as_image_copy(),
+            wgpu::Extent3d { width: self.size, height: self.size, depth_or_array_layers: 1 },
+        );
+        queue.submit(std::iter::once(encoder.finish()));
+
+        self.view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        self.texture = texture;
+        self.size = new_size;
+        self.packer.grow(new_size, new_size);
+        true
+    }
+
+    /// The glyph's place in the atlas, rasterizing it if it's new.
+    /// * `None` for glyphs without pixels, and for glyphs that don't fit this frame because the atlas is full.
+    fn get_or_insert(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, fonts: &[Font], key: GlyphKey) -> Option<GlyphInfo> {
+        if let Some(info) = self.glyphs.get(&key) {
+            return *info;
+        }
+
+        let glyph = fonts[key.font].font
+            .glyph(GlyphId(key.id))
+            .scaled(Scale::uniform(key.size as f32 / 4.0))
+            .positioned(rusttype::point(0.0, 0.0));
+
+        let bb = match glyph.pixel_bounding_box() {
+            Some(bb) => bb,
+            None => {
+                self.glyphs.insert(key, None);
+                return None;
+            }
+        };
+        let (w, h) = (bb.width() as u32, bb.height() as u32);
+        let max = device.limits().max_texture_dimension_2d;
+        if w + Self::PADDING > max || h + Self::PADDING > max {
+            // clearing wouldn't help
+            self.glyphs.insert(key, None);
+            return None;
+        }
+        let pos = loop {
+            if let Some(pos) = self.packer.allocate(w + Self::PADDING, h + Self::PADDING) {
+                break pos;
+            }
+            if !self.grow(device, queue) {
+                // dropped for this frame, and rasterized again after `begin_frame` clears the atlas
+                self.full = true;
+                return None;
+            }
+        };
+
+        let mut pixels = vec![0u8; (w * h) as usize];
+        glyph.draw(|x, y, v| {
+            pixels[(y * w + x) as usize] = (v * 255.0).round() as u8;
+        });
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d { x: pos.0, y: pos.1, z: 0 },
+                aspect: wgpu::TextureAspect::All,
+            },
+            &pixels,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: std::num::NonZeroU32::new(w),
+                rows_per_image: std::num::NonZeroU32::new(h),
+            },
+            wgpu::Extent3d { width: w, height: h, depth_or_array_layers: 1 },
+        );
+
+        let info = Some(GlyphInfo {
+            atlas_pos: [pos.0 as f32, pos.1 as f32],
+            size: [w as f32, h as f32],
+            offset: [bb.min.x as f32, bb.min.y as f32],
+        });
+        self.glyphs.insert(key, info);
+        info
+    }
+}
+
+/// Vertices for text prepared with `State::prepare_text`, drawn with `TextBatch::draw`.
+pub struct TextBatch {
+    slice: DynamicSlice,
+    vertex_count: u32,
+}
+
+impl TextBatch {
+    pub fn draw<'a>(&self, state: &'a State, render_pass: &mut wgpu::RenderPass<'a>) {
+        if self.vertex_count == 0 {
+            return;
+        }
+        let text = &state.text;
+        render_pass.set_pipeline(text.pipelines.get(&state.pass.target));
+        render_pass.set_bind_group(0, &text.bind_group, &[]);
+        render_pass.set_bind_group(1, &state.cameras[state.pass.camera].bind_group, &[]);
+        render_pass.set_vertex_buffer(0, text.vertices.slice(&self.slice));
+        render_pass.draw(0..self.vertex_count, 0..1);
+    }
+}
+
+/// Fonts, the shared glyph atlas, and the pipeline that draws text.
+pub struct TextSystem {
+    pub fonts: Vec<Font>,
+    atlas: GlyphAtlas,
+    atlas_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    bind_group: wgpu::BindGroup,
+    /// The atlas size the bind group was made for, to notice when it grew.
+    bound_size: u32,
+    pipelines: TargetPipelines,
+    vertices: DynamicBuffer,
+}
+
+impl TextSystem {
+    pub fn new(device: &wgpu::Device, target: &PipelineTarget, camera_layout: &wgpu::BindGroupLayout) -> Self {
+        let atlas = GlyphAtlas::new(device);
+
+        let atlas_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("panels:text"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler {
+                        filtering: true,
+                        comparison: false,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("panels:text"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let bind_group = Self::create_bind_group(device, &atlas_layout, &atlas.view, &sampler);
+
+        let pipelines = TargetPipelines::new(device, "panels:text", include_str!("text.wgsl"), &[&atlas_layout, camera_layout], TextVertex::desc(), target);
+
+        Self {
+            fonts: vec![],
+            bound_size: atlas.size,
+            atlas,
+            atlas_layout,
+            sampler,
+            bind_group,
+            pipelines,
+            vertices: DynamicBuffer::new(device, "panels:text", wgpu::BufferUsages::VERTEX, 64 * 1024),
+        }
+    }
+
+    fn create_bind_group(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, view: &wgpu::TextureView, sampler: &wgpu::Sampler) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("panels:text"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+            ],
+        })
+    }
+
+    pub fn add_font(&mut self, font: Font) -> usize {
+        self.fonts.push(font);
+        self.fonts.len() - 1
+    }
+
+    /// Build the pipeline for a target before a pass draws into it.
+    pub fn prepare_target(&mut self, device: &wgpu::Device, target: &PipelineTarget) {
+        self.pipelines.prepare(device, target);
+    }
+
+    /// Start a new frame of text vertices.
+    pub fn begin_frame(&mut self) {
+        self.vertices.reset();
+        self.atlas.begin_frame();
+    }
+
+    /// Lays out the sections, rasterizes any missing glyphs and uploads the quads.
+    /// * Sizes are in pixels of a target of `target_size`, assuming the default `Ndc` camera.
+    ///   Section rects are in NDC, so draw the batch with a pass whose camera uses `Projection::Ndc`;
+    ///   with any other camera the text is scaled and placed wrong.
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, target_size: PhysicalSize<u32>, sections: &[TextSection]) -> TextBatch {
+        // world units per target pixel
+        let wpp = [2.0 / target_size.width.max(1) as f32, 2.0 / target_size.height.max(1) as f32];
+        let mut vertices: Vec<TextVertex> = vec![];
+
+        for section in sections {
+            let font = &self.fonts[section.font];
+            let box_width = section.rect.width / wpp[0];
+            let layout = font.layout(section.text, section.size, Some(box_width), section.wrap, section.align, section.line_spacing);
+            let size_key = (section.size * 4.0).round() as u32;
+
+            for g in &layout.glyphs {
+                let key = GlyphKey { font: section.font, id: g.id.0, size: size_key };
+                let info = match self.atlas.get_or_insert(device, queue, &self.fonts, key) {
+                    Some(info) => info,
+                    None => continue,
+                };
+
+                // snap to whole pixels so the glyph bitmap isn't resampled
+                let x0 = g.x.round() + info.offset[0];
+                let y0 = g.baseline.round() + info.offset[1];
+                let (x1, y1) = (x0 + info.size[0], y0 + info.size[1]);
+                let to_world = |x: f32, y: f32| [section.rect.pos.x + x * wpp[0], section.rect.pos.y - y * wpp[1], section.rect.pos.z];
+                let (u0, v0) = (info.atlas_pos[0], info.atlas_pos[1]);
+                let (u1, v1) = (u0 + info.size[0], v0 + info.size[1]);
+
+                let tl = TextVertex { position: to_world(x0, y0), uv: [u0, v0], color: section.color };
+                let bl = TextVertex { position: to_world(x0, y1), uv: [u0, v1], color: section.color };
+                let br = TextVertex { position: to_world(x1, y1), uv: [u1, v1], color: section.color };
+                let tr = TextVertex { position: to_world(x1, y0), uv: [u1, v0], color: section.color };
+                vertices.extend_from_slice(&[tl, bl, br, br, tr, tl]);
+            }
+        }
+
+        if self.bound_size != self.atlas.size {
+            self.bind_group = Self::create_bind_group(device, &self.atlas_layout, &self.atlas.view, &self.sampler);
+            self.bound_size = self.atlas.size;
+        }
+
+        TextBatch {
+            slice: self.vertices.push(device, queue, bytemuck::cast_slice(&vertices)),
+            vertex_count: vertices.len() as u32,
+        }
+    }
+}
@@ -1,22 +1,61 @@
-use image::GenericImageView;
 use anyhow::*;
+use std::sync::Arc;
+use crate::mipmap::{self, MipmapGenerator};
+pub use crate::sampler::SamplerOptions;
+use crate::sampler::SamplerCache;
+pub use crate::texformat::{ColorSpace, FloatPrecision, TextureData};
+
+/// How `Texture::from_image_with` creates a texture.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct TextureOptions {
+    /// Generate a full mip chain, so the texture doesn't shimmer when drawn smaller than it's size.
+    pub mipmaps: bool,
+    pub sampler: SamplerOptions,
+    pub color_space: ColorSpace,
+    /// The format 16-bit and HDR images are loaded into.
+    pub float_precision: FloatPrecision,
+}
+
+impl TextureOptions {
+    /// Mipmapped with trilinear filtering.
+    pub fn mipmapped() -> Self {
+        Self {
+            mipmaps: true,
+            sampler: SamplerOptions::linear(),
+            ..Self::default()
+        }
+    }
+
+    /// For data that isn't a color, like normal maps.
+    pub fn linear(mut self) -> Self {
+        self.color_space = ColorSpace::Linear;
+        self
+    }
+}
 
 pub struct Texture {
     pub texture: wgpu::Texture,
+    pub size: wgpu::Extent3d,
+    pub format: wgpu::TextureFormat,
+    /// How `view` sees the texture: `D2`, `D2Array`, `Cube` or `D3`.
+    pub view_dimension: wgpu::TextureViewDimension,
     pub view: wgpu::TextureView,
-    pub sampler: wgpu::Sampler
+    /// Shared with other textures when it comes from a `SamplerCache`.
+    pub sampler: Arc<wgpu::Sampler>
 }
 
 impl Texture {
 
-    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, width: u32, height: u32) -> Result<Self> {
+    /// An empty texture with the default sampler from `samplers`.
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, width: u32, height: u32, samplers: &mut SamplerCache) -> Result<Self> {
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1
+        };
         let texture = device.create_texture(&wgpu::TextureDescriptor {
             label: None,
-            size: wgpu::Extent3d {
-                width,
-                height,
-                depth_or_array_layers: 1
-            },
+            size,
             mip_level_count: 0,
             sample_count: 0,
             dimension: wgpu::TextureDimension::D2,
@@ -25,54 +64,286 @@ impl Texture {
         });
 
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-        let sampler = device.create_sampler(&wgpu::SamplerDescriptor::default());
+        let sampler = samplers.get(device, &SamplerOptions::default());
 
         Ok(Self {
             texture,
+            size,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            view_dimension: wgpu::TextureViewDimension::D2,
             view,
             sampler
         })
 
     }
 
+    /// Creates an empty texture that a `TextureRenderer` can draw into, and that can be sampled afterwards.
+    pub fn new_render_target(device: &wgpu::Device, width: u32, height: u32, format: wgpu::TextureFormat, sampler: Arc<wgpu::Sampler>) -> Self {
+        let size = wgpu::Extent3d {
+            width: width.max(1),
+            height: height.max(1),
+            depth_or_array_layers: 1
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("panels:render_target"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_SRC | wgpu::TextureUsages::COPY_DST
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        Self {
+            texture,
+            size,
+            format,
+            view_dimension: wgpu::TextureViewDimension::D2,
+            view,
+            sampler
+        }
+    }
+
+    /// Creates a multisampled color buffer or a depth buffer for a `TextureRenderer`'s pass.
+    /// * Only single-sampled attachments can be sampled afterwards.
+    pub fn new_attachment(device: &wgpu::Device, width: u32, height: u32, format: wgpu::TextureFormat, sample_count: u32, sampler: Arc<wgpu::Sampler>) -> Self {
+        let size = wgpu::Extent3d {
+            width: width.max(1),
+            height: height.max(1),
+            depth_or_array_layers: 1
+        };
+        let mut usage = wgpu::TextureUsages::RENDER_ATTACHMENT;
+        if sample_count == 1 {
+            usage |= wgpu::TextureUsages::TEXTURE_BINDING;
+        }
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("panels:attachment"),
+            size,
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        Self {
+            texture,
+            size,
+            format,
+            view_dimension: wgpu::TextureViewDimension::D2,
+            view,
+            sampler
+        }
+    }
+
+    /// Decode a file with `TextureData::from_bytes`, so compressed and HDR files work here too.
     pub fn from_bytes(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         bytes: &[u8],
+        samplers: &mut SamplerCache
     ) -> Result<Self> {
-        let img = image::load_from_memory(bytes)?;
-        Self::from_image(device, queue, &img)
+        let options = TextureOptions::default();
+        let data = TextureData::from_bytes(bytes, options.color_space, options.float_precision)?;
+        let sampler = samplers.get(device, &options.sampler);
+        Self::create(device, queue, &data, &options, None, sampler)
     }
 
     pub fn from_image(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
-        img: &image::DynamicImage
+        img: &image::DynamicImage,
+        samplers: &mut SamplerCache
     ) -> Result<Self> {
-        // texture creation
+        let options = TextureOptions::default();
+        let data = TextureData::from_image(img, options.color_space, options.float_precision);
+        let sampler = samplers.get(device, &options.sampler);
+        Self::create(device, queue, &data, &options, None, sampler)
+    }
+
+    /// Decode a file and create a texture with the given options, see `Texture::from_data_with`.
+    pub fn from_bytes_with(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        bytes: &[u8],
+        options: &TextureOptions,
+        mipmaps: &mut MipmapGenerator,
+        samplers: &mut SamplerCache
+    ) -> Result<Self> {
+        let data = TextureData::from_bytes(bytes, options.color_space, options.float_precision)?;
+        Self::from_data_with(device, queue, &data, options, mipmaps, samplers)
+    }
+
+    /// Convert an image and create a texture with the given options, see `Texture::from_data_with`.
+    pub fn from_image_with(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        img: &image::DynamicImage,
+        options: &TextureOptions,
+        mipmaps: &mut MipmapGenerator,
+        samplers: &mut SamplerCache
+    ) -> Result<Self> {
+        let data = TextureData::from_image(img, options.color_space, options.float_precision);
+        Self::from_data_with(device, queue, &data, options, mipmaps, samplers)
+    }
 
-        let rgba = img.as_rgba8().unwrap();
+    /// Create a texture with a sampler from the cache.
+    /// * If `options.mipmaps` is set and the data has a single level, the rest of the mip chain is generated.
+    ///   Compressed and non-filterable formats keep the levels they were loaded with.
+    pub fn from_data_with(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        data: &TextureData,
+        options: &TextureOptions,
+        mipmaps: &mut MipmapGenerator,
+        samplers: &mut SamplerCache
+    ) -> Result<Self> {
+        let sampler = samplers.get(device, &options.sampler);
+        Self::create(device, queue, data, options, Some(mipmaps), sampler)
+    }
 
-        let dimensions = img.dimensions();
+    fn create(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        data: &TextureData,
+        options: &TextureOptions,
+        mipmaps: Option<&mut MipmapGenerator>,
+        sampler: Arc<wgpu::Sampler>
+    ) -> Result<Self> {
+        let format = data.format;
+        let info = format.describe();
+        if !device.features().contains(info.required_features) {
+            bail!("{:?} textures need {:?}, which the device doesn't have", format, info.required_features);
+        }
 
         let texture_size = wgpu::Extent3d {
-            width: dimensions.0,
-            height: dimensions.1,
+            width: data.width,
+            height: data.height,
             depth_or_array_layers: 1
         };
 
+        let renderable = info.guaranteed_format_features.allowed_usages.contains(wgpu::TextureUsages::RENDER_ATTACHMENT);
+        let generator = match mipmaps {
+            Some(g) if options.mipmaps && data.levels.len() == 1 && renderable && info.guaranteed_format_features.filterable => Some(g),
+            _ => None,
+        };
+        let (mip_level_count, usage) = match generator {
+            Some(_) => (
+                mipmap::mip_level_count(data.width, data.height),
+                wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::RENDER_ATTACHMENT,
+            ),
+            None => (data.levels.len() as u32, wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST),
+        };
+
         let texture = device.create_texture(
             &wgpu::TextureDescriptor {
                 label: None,
                 size: texture_size,
-                mip_level_count: 1,
+                mip_level_count,
                 sample_count: 1,
                 dimension: wgpu::TextureDimension::D2,
-                format: wgpu::TextureFormat::Rgba8UnormSrgb,
-                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                format,
+                usage,
             }
         );
 
+        for (level, texels) in data.levels.iter().enumerate() {
+            let level = level as u32;
+            let extent = data.level_extent(level);
+            let rows = extent.height / info.block_dimensions.1 as u32;
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &texture,
+                    mip_level: level,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All
+                },
+
+                texels,
+
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: std::num::NonZeroU32::new(data.bytes_per_row(level)),
+                    rows_per_image: std::num::NonZeroU32::new(rows),
+                },
+
+                extent,
+            );
+        }
+
+        if let Some(generator) = generator {
+            generator.generate(device, queue, &texture, format, mip_level_count);
+        }
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        Ok(Self{
+            texture,
+            size: texture_size,
+            format,
+            view_dimension: wgpu::TextureViewDimension::D2,
+            view,
+            sampler
+        })
+
+    }
+}
+
+impl Texture {
+    /// A 2D array with one layer per `TextureData`, sampled as `texture_2d_array` in WGSL.
+    /// * Every layer must have the same format, size and number of mip levels.
+    pub fn new_array(device: &wgpu::Device, queue: &wgpu::Queue, layers: &[TextureData], sampler: Arc<wgpu::Sampler>) -> Result<Self> {
+        Self::create_layered(device, queue, layers, wgpu::TextureViewDimension::D2Array, sampler)
+    }
+
+    /// A cube map from six square faces in `+X, -X, +Y, -Y, +Z, -Z` order, sampled as `texture_cube` in WGSL.
+    pub fn new_cube(device: &wgpu::Device, queue: &wgpu::Queue, faces: &[TextureData; 6], sampler: Arc<wgpu::Sampler>) -> Result<Self> {
+        if faces[0].width != faces[0].height {
+            bail!("cube map faces must be square, not {}x{}", faces[0].width, faces[0].height);
+        }
+        Self::create_layered(device, queue, faces, wgpu::TextureViewDimension::Cube, sampler)
+    }
+
+    /// A cube map with `face_size` texel faces, resampled on the CPU from an equirectangular (latitude/longitude) panorama.
+    /// * The center of the panorama faces -Z, with +Y at the top. Only it's first mip level is used.
+    pub fn cube_from_equirectangular(device: &wgpu::Device, queue: &wgpu::Queue, panorama: &TextureData, face_size: u32, sampler: Arc<wgpu::Sampler>) -> Result<Self> {
+        let faces = equirectangular_to_cube(panorama, face_size)?;
+        Self::new_cube(device, queue, &faces, sampler)
+    }
+
+    /// A volume texture of `size[2]` tightly packed slices, front to back, sampled as `texture_3d` in WGSL.
+    pub fn new_3d(device: &wgpu::Device, queue: &wgpu::Queue, format: wgpu::TextureFormat, size: [u32; 3], texels: &[u8], sampler: Arc<wgpu::Sampler>) -> Result<Self> {
+        let info = format.describe();
+        if info.block_dimensions != (1, 1) {
+            bail!("{:?} is compressed and can't be used for a 3D texture", format);
+        }
+        if !device.features().contains(info.required_features) {
+            bail!("{:?} textures need {:?}, which the device doesn't have", format, info.required_features);
+        }
+        let row = size[0] * info.block_size as u32;
+        let expected = row as usize * size[1] as usize * size[2] as usize;
+        if texels.len() < expected {
+            bail!("a {}x{}x{} {:?} texture needs {} bytes, got {}", size[0], size[1], size[2], format, expected, texels.len());
+        }
+
+        let texture_size = wgpu::Extent3d {
+            width: size[0],
+            height: size[1],
+            depth_or_array_layers: size[2],
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size: texture_size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D3,
+            format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        });
         queue.write_texture(
             wgpu::ImageCopyTexture {
                 texture: &texture,
@@ -80,35 +351,250 @@ impl Texture {
                 origin: wgpu::Origin3d::ZERO,
                 aspect: wgpu::TextureAspect::All
             },
-
-            rgba,
-
+            &texels[..expected],
             wgpu::ImageDataLayout {
                 offset: 0,
-                bytes_per_row: std::num::NonZeroU32::new(4 * dimensions.0),
-                rows_per_image: std::num::NonZeroU32::new(dimensions.1),
+                bytes_per_row: std::num::NonZeroU32::new(row),
+                rows_per_image: std::num::NonZeroU32::new(size[1]),
             },
-
             texture_size,
         );
 
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+        Ok(Self {
+            texture,
+            size: texture_size,
+            format,
+            view_dimension: wgpu::TextureViewDimension::D3,
+            view,
+            sampler
+        })
+    }
+
+    fn create_layered(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layers: &[TextureData],
+        view_dimension: wgpu::TextureViewDimension,
+        sampler: Arc<wgpu::Sampler>
+    ) -> Result<Self> {
+        let first = layers.first().ok_or_else(|| anyhow!("a layered texture needs at least one layer"))?;
+        if layers.iter().any(|l| l.format != first.format || l.width != first.width || l.height != first.height || l.levels.len() != first.levels.len()) {
+            bail!("every layer must have the same format, size and mip levels");
+        }
+        let format = first.format;
+        let info = format.describe();
+        if !device.features().contains(info.required_features) {
+            bail!("{:?} textures need {:?}, which the device doesn't have", format, info.required_features);
+        }
+
+        let texture_size = wgpu::Extent3d {
+            width: first.width,
+            height: first.height,
+            depth_or_array_layers: layers.len() as u32,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
             label: None,
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Linear,
-            min_filter: wgpu::FilterMode::Nearest,
-            mipmap_filter: wgpu::FilterMode::Nearest,
-            ..Default::default()
+            size: texture_size,
+            mip_level_count: first.levels.len() as u32,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
         });
 
-        Ok(Self{
+        for (layer, data) in layers.iter().enumerate() {
+            for (level, texels) in data.levels.iter().enumerate() {
+                let level = level as u32;
+                let extent = data.level_extent(level);
+                queue.write_texture(
+                    wgpu::ImageCopyTexture {
+                        texture: &texture,
+                        mip_level: level,
+                        origin: wgpu::Origin3d { x: 0, y: 0, z: layer as u32 },
+                        aspect: wgpu::TextureAspect::All
+                    },
+                    texels,
+                    wgpu::ImageDataLayout {
+                        offset: 0,
+                        bytes_per_row: std::num::NonZeroU32::new(data.bytes_per_row(level)),
+                        rows_per_image: std::num::NonZeroU32::new(extent.height / info.block_dimensions.1 as u32),
+                    },
+                    extent,
+                );
+            }
+        }
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(view_dimension),
+            ..Default::default()
+        });
+        Ok(Self {
             texture,
+            size: texture_size,
+            format,
+            view_dimension,
             view,
             sampler
         })
+    }
+
+    /// Layout entries for this texture at `binding` and it's sampler at `binding + 1`.
+    pub fn layout_entries(&self, binding: u32, visibility: wgpu::ShaderStages) -> [wgpu::BindGroupLayoutEntry; 2] {
+        texture_layout_entries(binding, visibility, self.view_dimension, sample_type(self.format))
+    }
+
+    /// A layout with this texture at binding 0 and it's sampler at binding 1, visible to fragment shaders.
+    pub fn create_bind_group_layout(&self, device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &self.layout_entries(0, wgpu::ShaderStages::FRAGMENT),
+        })
+    }
+
+    /// A bind group with this texture at binding 0 and it's sampler at binding 1.
+    pub fn create_bind_group(&self, device: &wgpu::Device, layout: &wgpu::BindGroupLayout) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&self.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        })
+    }
+}
+
+/// How shaders can sample a format, with float formats marked unfilterable where the spec says so.
+pub fn sample_type(format: wgpu::TextureFormat) -> wgpu::TextureSampleType {
+    let info = format.describe();
+    match info.sample_type {
+        wgpu::TextureSampleType::Float { .. } => wgpu::TextureSampleType::Float { filterable: info.guaranteed_format_features.filterable },
+        other => other,
+    }
+}
 
+/// Layout entries for a texture at `binding` and a matching sampler at `binding + 1`.
+pub fn texture_layout_entries(binding: u32, visibility: wgpu::ShaderStages, view_dimension: wgpu::TextureViewDimension, sample_type: wgpu::TextureSampleType) -> [wgpu::BindGroupLayoutEntry; 2] {
+    [
+        wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::Texture {
+                sample_type,
+                view_dimension,
+                multisampled: false,
+            },
+            count: None,
+        },
+        wgpu::BindGroupLayoutEntry {
+            binding: binding + 1,
+            visibility,
+            ty: wgpu::BindingType::Sampler {
+                filtering: sample_type == wgpu::TextureSampleType::Float { filterable: true },
+                comparison: sample_type == wgpu::TextureSampleType::Depth,
+            },
+            count: None,
+        },
+    ]
+}
+
+/// Resample a panorama into six cube faces, taking the nearest panorama texel for each face texel.
+fn equirectangular_to_cube(panorama: &TextureData, face_size: u32) -> Result<[TextureData; 6]> {
+    let info = panorama.format.describe();
+    if info.block_dimensions != (1, 1) {
+        bail!("{:?} is compressed and can't be resampled into a cube map", panorama.format);
+    }
+    if panorama.width == 0 || panorama.height == 0 || face_size == 0 {
+        bail!("can't resample a {}x{} panorama into {}px cube faces", panorama.width, panorama.height, face_size);
+    }
+    let bpp = info.block_size as usize;
+    let (pw, ph) = (panorama.width as usize, panorama.height as usize);
+    let source = match panorama.levels.first() {
+        Some(level) if level.len() >= pw * ph * bpp => level,
+        _ => bail!("the panorama has fewer texels than it's {}x{} size", pw, ph),
+    };
+
+    let face = |index: usize| {
+        let mut texels = vec![0u8; face_size as usize * face_size as usize * bpp];
+        for y in 0..face_size {
+            for x in 0..face_size {
+                let u = 2.0 * (x as f32 + 0.5) / face_size as f32 - 1.0;
+                let v = 2.0 * (y as f32 + 0.5) / face_size as f32 - 1.0;
+                let [dx, dy, dz] = match index {
+                    0 => [1.0, -v, -u],
+                    1 => [-1.0, -v, u],
+                    2 => [u, 1.0, v],
+                    3 => [u, -1.0, -v],
+                    4 => [u, -v, 1.0],
+                    _ => [-u, -v, -1.0],
+                };
+                let len = (dx * dx + dy * dy + dz * dz).sqrt();
+                let s = 0.5 + dx.atan2(-dz) / (2.0 * std::f32::consts::PI);
+                let t = (dy / len).acos() / std::f32::consts::PI;
+                let px = ((s * pw as f32) as usize).min(pw - 1);
+                let py = ((t * ph as f32) as usize).min(ph - 1);
+                let src = (py * pw + px) * bpp;
+                let dst = (y as usize * face_size as usize + x as usize) * bpp;
+                texels[dst..dst + bpp].copy_from_slice(&source[src..src + bpp]);
+            }
+        }
+        TextureData {
+            format: panorama.format,
+            width: face_size,
+            height: face_size,
+            levels: vec![texels],
+        }
+    };
+    Ok([face(0), face(1), face(2), face(3), face(4), face(5)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A panorama whose texels hold their own `[x, y]` position.
+    fn panorama(width: u32, height: u32) -> TextureData {
+        let texels = (0..height).flat_map(|y| (0..width).flat_map(move |x| [x as u8, y as u8, 0, 255])).collect();
+        TextureData { format: wgpu::TextureFormat::Rgba8Unorm, width, height, levels: vec![texels] }
     }
-}
\ No newline at end of file
+
+    /// The panorama texel at the center of a face with an odd size.
+    fn center(face: &TextureData) -> [u8; 2] {
+        let i = ((face.height / 2 * face.width + face.width / 2) * 4) as usize;
+        [face.levels[0][i], face.levels[0][i + 1]]
+    }
+
+    #[test]
+    fn face_centers_look_along_the_axes() {
+        let faces = equirectangular_to_cube(&panorama(8, 4), 3).unwrap();
+        for face in &faces {
+            assert_eq!((face.width, face.height, face.levels[0].len()), (3, 3, 36));
+        }
+        // the panorama's center is -Z, a quarter turn right of it is +X and one left is -X
+        assert_eq!(center(&faces[0]), [6, 2]);
+        assert_eq!(center(&faces[1]), [2, 2]);
+        assert_eq!(center(&faces[5]), [4, 2]);
+        // +Z is on the left and right edges
+        assert!(matches!(center(&faces[4]), [0, 2] | [7, 2]), "+Z is {:?}", center(&faces[4]));
+        // +Y is the top row, -Y the bottom row
+        assert_eq!(center(&faces[2])[1], 0);
+        assert_eq!(center(&faces[3])[1], 3);
+    }
+
+    #[test]
+    fn empty_panoramas_are_rejected() {
+        assert!(equirectangular_to_cube(&panorama(0, 4), 3).is_err());
+        assert!(equirectangular_to_cube(&panorama(8, 0), 3).is_err());
+        assert!(equirectangular_to_cube(&panorama(8, 4), 0).is_err());
+        let mut short = panorama(8, 4);
+        short.levels[0].truncate(8);
+        assert!(equirectangular_to_cube(&short, 3).is_err());
+    }
+}
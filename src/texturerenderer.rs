@@ -1,5 +1,8 @@
+use crate::pipelines::PipelineTarget;
 use crate::programhook::ProgramHook;
 use crate::renderobj::RenderObject;
+use crate::renderqueue::RenderQueue;
+use crate::sampler::SamplerOptions;
 use crate::texture::Texture;
 use crate::timing::{CallStatus, Timing};
 use crate::transform2d::Transform2D;
@@ -28,9 +31,29 @@ pub struct TextureRenderer {
     /// Misc storage for separate objects that can be accessed at rendertime.
     pub my_objects: Vec<RenderObject>,
 
+    /// Objects submitted here during `ProgramHook::render` are sorted and drawn on `RenderQueue::flush`.
+    pub render_queue: RenderQueue,
+
     // Other `TextureRenderer`s representing subprograms.
     // pub owned_elements: Vec<TextureRenderer>,
 
+    /// The size of this renderer's texture in logical pixels.
+    /// When set, the texture is recreated at the matching physical size whenever the scale factor changes.
+    pub logical_size: Option<winit::dpi::LogicalSize<f64>>,
+
+    /// The index of the `Camera` in the `State` used when drawing this renderer.
+    pub camera: usize,
+
+    /// Samples per pixel, see `set_sample_count`.
+    pub sample_count: u32,
+    /// The format of the pass's depth buffer, see `set_depth`.
+    pub depth_format: Option<wgpu::TextureFormat>,
+    /// `State::textures` holding the multisampled color buffer and the depth buffer, kept at the target's size.
+    msaa_texture: Option<usize>,
+    depth_texture: Option<usize>,
+    /// The sample count the attachments were created with.
+    attachment_samples: u32,
+
     /// The index of the `ProgramHook` associated with this `TextureRenderer`
     pub program_id: Option<usize>,
 
@@ -46,20 +69,28 @@ pub struct TextureRenderer {
 
 impl TextureRenderer {
     /// Creates a `TextureRenderer` that uses the given routine to draw elements.
-    pub fn new(nametag: &str, program_id: Option<usize>, tex_index: TextureIndex) -> Self {
+    pub fn new(state: &mut State, nametag: &str, program_id: Option<usize>, tex_index: TextureIndex) -> Self {
 
         // create texture
         // create uniforms
         // create bindgroup for both
 
         // by default the textured rect is added as a placeholder
-        let mut this_object = RenderObject::new_placeholder_rect();
+        let mut this_object = RenderObject::new_placeholder_rect(state);
 
         Self {
             name: String::from(nametag),
             texture: tex_index,
             clear_or_load: wgpu::LoadOp::Load,
             my_objects: vec![this_object],
+            render_queue: RenderQueue::new(),
+            camera: 0,
+            sample_count: 1,
+            depth_format: None,
+            msaa_texture: None,
+            depth_texture: None,
+            attachment_samples: 1,
+            logical_size: None,
             //owned_elements: vec![],
             program_id,
             // drawf,
@@ -174,6 +205,105 @@ impl TextureRenderer {
         }
     }
 
+    /// Keep this renderer's texture at the given logical size across scale factor changes.
+    pub fn set_logical_size(&mut self, size: winit::dpi::LogicalSize<f64>) {
+        self.logical_size = Some(size);
+    }
+
+    /// Recreate the target texture if it is sized in logical pixels and no longer matches the scale factor.
+    pub fn rescale_target(&self, state: &mut State) {
+        if let (Some(size), TextureIndex::Index(i)) = (self.logical_size, &self.texture) {
+            let physical = size.to_physical::<u32>(state.scale_factor);
+            let current = state.textures[*i].size;
+            if current.width != physical.width || current.height != physical.height {
+                state.resize_render_target(*i, physical);
+            }
+        }
+    }
+
+    /// The size of the texture this renderer draws into, in physical pixels.
+    pub fn target_size(&self, state: &State) -> winit::dpi::PhysicalSize<u32> {
+        match self.texture {
+            TextureIndex::Surface => state.size,
+            TextureIndex::Index(i) => {
+                let size = state.textures[i].size;
+                winit::dpi::PhysicalSize::new(size.width, size.height)
+            }
+        }
+    }
+
+    /// Draw this renderer through the `State` camera at the given index.
+    /// * When drawing into a texture, turn off the camera's `fit_surface` and set it's viewport to `target_size`.
+    pub fn set_camera(&mut self, camera: usize) {
+        self.camera = camera;
+    }
+
+    /// Draw with multisampling, into a color buffer that is resolved into the target. `1` turns it off.
+    /// * `4` works with every format that can be rendered to.
+    pub fn set_sample_count(&mut self, sample_count: u32) {
+        self.sample_count = sample_count.max(1);
+    }
+
+    /// Give the pass a depth buffer, cleared every frame, e.g. `Some(wgpu::TextureFormat::Depth32Float)`.
+    pub fn set_depth(&mut self, format: Option<wgpu::TextureFormat>) {
+        self.depth_format = format;
+    }
+
+    /// The attachments of this renderer's pass, which the pipelines drawing in it are built for.
+    pub fn pipeline_target(&self, state: &State) -> PipelineTarget {
+        let format = match self.texture {
+            TextureIndex::Surface => state.config.format,
+            TextureIndex::Index(i) => state.textures[i].format,
+        };
+        PipelineTarget {
+            color_formats: vec![format],
+            depth_format: self.depth_format,
+            sample_count: self.sample_count,
+        }
+    }
+
+    /// Create or resize the multisampled color and depth buffers to match the target, called before each pass.
+    pub fn prepare_attachments(&mut self, state: &mut State) {
+        let size = self.target_size(state);
+        let target = self.pipeline_target(state);
+        let resample = self.attachment_samples != target.sample_count;
+        self.attachment_samples = target.sample_count;
+        if target.sample_count > 1 {
+            self.msaa_texture = Some(Self::attachment(state, self.msaa_texture, target.color_formats[0], size, target.sample_count, resample));
+        }
+        if let Some(format) = target.depth_format {
+            self.depth_texture = Some(Self::attachment(state, self.depth_texture, format, size, target.sample_count, resample));
+        }
+    }
+
+    /// Reuse or replace an attachment's slot in `State::textures`.
+    fn attachment(state: &mut State, slot: Option<usize>, format: wgpu::TextureFormat, size: winit::dpi::PhysicalSize<u32>, sample_count: u32, resample: bool) -> usize {
+        if let Some(i) = slot {
+            let current = &state.textures[i];
+            if !resample && current.format == format && current.size.width == size.width.max(1) && current.size.height == size.height.max(1) {
+                return i;
+            }
+        }
+        let sampler = state.sampler(&SamplerOptions::default());
+        let texture = Texture::new_attachment(&state.device, size.width, size.height, format, sample_count, sampler);
+        match slot {
+            Some(i) => {
+                state.textures[i] = texture;
+                i
+            }
+            None => state.add_texture(texture),
+        }
+    }
+
+    /// Views of the multisampled color buffer and the depth buffer, if this renderer uses them.
+    pub fn attachment_views(&self, state: &State) -> (Option<wgpu::TextureView>, Option<wgpu::TextureView>) {
+        let view = |i: usize| state.textures[i].texture.create_view(&wgpu::TextureViewDescriptor::default());
+        (
+            self.msaa_texture.filter(|_| self.sample_count > 1).map(view),
+            self.depth_texture.filter(|_| self.depth_format.is_some()).map(view),
+        )
+    }
+
     pub fn hook_program(&mut self, program_id: usize) {
         self.program_id = Some(program_id);
     }
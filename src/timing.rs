@@ -33,6 +33,15 @@ impl Timing {
             Timing::Never => {}
         }
     }
+
+    /// The time since the function was last called, if this `Timing` keeps track of it.
+    pub fn elapsed(&self) -> Option<Duration> {
+        match self {
+            Timing::Framerate { last_rendered_at, .. } => Some(last_rendered_at.elapsed()),
+            Timing::SpecificTime { last_rendered_at, .. } => Some(last_rendered_at.elapsed()),
+            _ => None,
+        }
+    }
 }
 
 
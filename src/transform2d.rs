@@ -1,17 +1,245 @@
+use cgmath::{Matrix3, Rad, SquareMatrix, Vector3};
+use crate::rect::WorldPoint;
 
-use bytemuck::*;
-#[repr(C)]
-#[derive(Debug,Copy,Clone,bytemuck::Pod,bytemuck::Zeroable)]
+/// A 2D affine transform: any combination of translation, rotation, non-uniform scale and shear.
+/// * `depth` is carried separately and becomes the Z translation of the uploaded matrix.
+///
+/// Transform vs WorldPosition:
+/// The difference is that Transform2D represents the Scale, Rotation, and Trasformation through a matrix.
+#[derive(Debug,Copy,Clone,PartialEq)]
 pub struct Transform2D {
-    pub(crate) pos: [f32; 3]
+    /// Column-major homogeneous 2D matrix, the third column holds the translation.
+    pub matrix: Matrix3<f32>,
+    pub depth: f32,
 }
 
-/// Transform vs WorldPosition:
-/// The difference is that Transform2D represents the Scale, Rotation, and Trasformation through a matrix.
 impl Transform2D {
+    /// A pure translation, the Z coordinate is used as depth.
     pub fn new(x: f32, y: f32, z: f32) -> Self {
         Self {
-            pos: [x,y,z]
+            matrix: Matrix3::from_translation(cgmath::vec2(x, y)),
+            depth: z
         }
     }
-}
\ No newline at end of file
+
+    pub fn identity() -> Self {
+        Self {
+            matrix: Matrix3::identity(),
+            depth: 0.0
+        }
+    }
+
+    pub fn from_translation(x: f32, y: f32) -> Self {
+        Self::new(x, y, 0.0)
+    }
+
+    /// Counter-clockwise rotation around the origin, in radians.
+    pub fn from_rotation(radians: f32) -> Self {
+        Self {
+            matrix: Matrix3::from_angle_z(Rad(radians)),
+            depth: 0.0
+        }
+    }
+
+    pub fn from_scale(sx: f32, sy: f32) -> Self {
+        Self {
+            matrix: Matrix3::from_nonuniform_scale(sx, sy),
+            depth: 0.0
+        }
+    }
+
+    /// Scale, then rotate around `pivot`, then translate.
+    /// The pivot is given in the object's local coordinates, so a rect model rotates around `(0,0)` by default.
+    pub fn from_parts(translation: [f32; 2], radians: f32, scale: [f32; 2], pivot: [f32; 2]) -> Self {
+        Self::from_translation(-pivot[0], -pivot[1])
+            .then(&Self::from_scale(scale[0], scale[1]))
+            .then(&Self::from_rotation(radians))
+            .then(&Self::from_translation(pivot[0] + translation[0], pivot[1] + translation[1]))
+    }
+
+    /// Returns the transform that applies `self` first, then `next`.
+    pub fn then(&self, next: &Transform2D) -> Self {
+        Self {
+            matrix: next.matrix * self.matrix,
+            depth: self.depth + next.depth
+        }
+    }
+
+    /// The transform that undoes this one, or `None` if it collapses an axis (zero scale).
+    pub fn inverse(&self) -> Option<Self> {
+        self.matrix.invert().map(|matrix| Self {
+            matrix,
+            depth: -self.depth
+        })
+    }
+
+    pub fn translation(&self) -> [f32; 2] {
+        [self.matrix.z.x, self.matrix.z.y]
+    }
+
+    /// The rotation in radians, assuming the transform has no shear.
+    pub fn rotation(&self) -> f32 {
+        self.matrix.x.y.atan2(self.matrix.x.x)
+    }
+
+    /// The scale along the object's own axes, assuming the transform has no shear.
+    pub fn scale_xy(&self) -> [f32; 2] {
+        let m = &self.matrix;
+        let sx = (m.x.x * m.x.x + m.x.y * m.x.y).sqrt();
+        let sy = (m.y.x * m.y.x + m.y.y * m.y.y).sqrt();
+        // a mirrored transform has a negative determinant
+        let det = m.x.x * m.y.y - m.x.y * m.y.x;
+        [sx, if det < 0.0 { -sy } else { sy }]
+    }
+
+    pub fn set_translation(&mut self, x: f32, y: f32) {
+        self.matrix.z.x = x;
+        self.matrix.z.y = y;
+    }
+
+    /// Translate in world space, after the existing transform.
+    pub fn translate(&mut self, dx: f32, dy: f32) {
+        *self = self.then(&Self::from_translation(dx, dy));
+    }
+
+    /// Rotate around the world origin, after the existing transform.
+    pub fn rotate(&mut self, radians: f32) {
+        *self = self.then(&Self::from_rotation(radians));
+    }
+
+    /// Scale around the world origin, after the existing transform.
+    pub fn scale(&mut self, sx: f32, sy: f32) {
+        *self = self.then(&Self::from_scale(sx, sy));
+    }
+
+    /// Maps a point from local space into world space.
+    pub fn transform_point(&self, p: &WorldPoint) -> WorldPoint {
+        let v = self.matrix * Vector3::new(p.x(), p.y(), 1.0);
+        WorldPoint::new(v.x, v.y, p.z() + self.depth)
+    }
+
+    /// Maps a direction, ignoring translation.
+    pub fn transform_vector(&self, x: f32, y: f32) -> [f32; 2] {
+        let v = self.matrix * Vector3::new(x, y, 0.0);
+        [v.x, v.y]
+    }
+
+    /// Maps a point from world space into local space, useful for hit-testing against a model's bounds.
+    pub fn inverse_transform_point(&self, p: &WorldPoint) -> Option<WorldPoint> {
+        self.inverse().map(|inv| inv.transform_point(p))
+    }
+
+    /// The transform as a WGSL `mat4x4<f32>`, column-major.
+    pub fn to_mat4(&self) -> [[f32; 4]; 4] {
+        let m = &self.matrix;
+        [
+            [m.x.x, m.x.y, 0.0, 0.0],
+            [m.y.x, m.y.y, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [m.z.x, m.z.y, self.depth, 1.0],
+        ]
+    }
+
+    /// The transform as a WGSL `mat3x3<f32>`, column-major with each column padded to 16 bytes.
+    pub fn to_mat3(&self) -> [[f32; 4]; 3] {
+        let m = &self.matrix;
+        [
+            [m.x.x, m.x.y, m.x.z, 0.0],
+            [m.y.x, m.y.y, m.y.z, 0.0],
+            [m.z.x, m.z.y, m.z.z, 0.0],
+        ]
+    }
+}
+
+impl Default for Transform2D {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+/// `a * b` applies `b` first, then `a`, like matrix multiplication.
+impl std::ops::Mul for Transform2D {
+    type Output = Self;
+    fn mul(self, rhs: Self) -> Self::Output {
+        rhs.then(&self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    const EPS: f32 = 1e-3;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() <= EPS * (1.0 + a.abs().max(b.abs()))
+    }
+
+    fn close_points(a: &WorldPoint, b: &WorldPoint) -> bool {
+        close(a.x(), b.x()) && close(a.y(), b.y()) && close(a.z(), b.z())
+    }
+
+    fn scale() -> impl Strategy<Value = f32> {
+        (0.25f32..4.0, any::<bool>()).prop_map(|(s, mirrored)| if mirrored { -s } else { s })
+    }
+
+    /// Invertible transforms, with scales kept away from zero.
+    fn transform() -> impl Strategy<Value = Transform2D> {
+        ((-10.0f32..10.0, -10.0f32..10.0), -3.0f32..3.0, (scale(), scale()), (-1.0f32..1.0, -1.0f32..1.0), -1.0f32..1.0)
+            .prop_map(|(t, radians, s, pivot, depth)| {
+                let mut transform = Transform2D::from_parts([t.0, t.1], radians, [s.0, s.1], [pivot.0, pivot.1]);
+                transform.depth = depth;
+                transform
+            })
+    }
+
+    fn point() -> impl Strategy<Value = WorldPoint> {
+        (-10.0f32..10.0, -10.0f32..10.0, -1.0f32..1.0).prop_map(|(x, y, z)| WorldPoint::new(x, y, z))
+    }
+
+    #[test]
+    fn parts() {
+        let t = Transform2D::from_parts([3.0, -2.0], std::f32::consts::FRAC_PI_2, [2.0, 1.0], [0.0, 0.0]);
+        let p = t.transform_point(&WorldPoint::new(1.0, 0.0, 0.0));
+        // scaled to (2, 0), rotated to (0, 2), then moved
+        assert!(close_points(&p, &WorldPoint::new(3.0, 0.0, 0.0)), "{:?}", p);
+        assert!(close(t.rotation(), std::f32::consts::FRAC_PI_2));
+        assert_eq!(t.translation(), [3.0, -2.0]);
+        assert!(Transform2D::from_scale(0.0, 1.0).inverse().is_none());
+    }
+
+    proptest! {
+        #[test]
+        fn inverse_round_trip(t in transform(), p in point()) {
+            let back = t.inverse_transform_point(&t.transform_point(&p)).unwrap();
+            prop_assert!(close_points(&back, &p), "{:?} -> {:?}", p, back);
+            let identity = t.then(&t.inverse().unwrap());
+            prop_assert!(close_points(&identity.transform_point(&p), &p));
+        }
+
+        #[test]
+        fn composition_applies_in_order(a in transform(), b in transform(), p in point()) {
+            let both = a.then(&b).transform_point(&p);
+            let one_by_one = b.transform_point(&a.transform_point(&p));
+            prop_assert!(close_points(&both, &one_by_one), "{:?} != {:?}", both, one_by_one);
+            prop_assert_eq!((b * a).matrix, a.then(&b).matrix);
+        }
+
+        #[test]
+        fn vectors_ignore_translation(t in transform(), p in point(), q in point()) {
+            let (tp, tq) = (t.transform_point(&p), t.transform_point(&q));
+            let v = t.transform_vector(q.x() - p.x(), q.y() - p.y());
+            prop_assert!(close(v[0], tq.x() - tp.x()) && close(v[1], tq.y() - tp.y()));
+        }
+
+        #[test]
+        fn parts_round_trip(t in (-10.0f32..10.0, -10.0f32..10.0), radians in -3.0f32..3.0, s in (0.25f32..4.0, scale())) {
+            let transform = Transform2D::from_parts([t.0, t.1], radians, [s.0, s.1], [0.0, 0.0]);
+            prop_assert!(close(transform.translation()[0], t.0) && close(transform.translation()[1], t.1));
+            prop_assert!(close(transform.rotation(), radians));
+            let [sx, sy] = transform.scale_xy();
+            prop_assert!(close(sx, s.0) && close(sy, s.1), "{:?} != {:?}", [sx, sy], s);
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use crate::transform2d::Transform2D;
+
+/// The per-object data uploaded for every `RenderObject`.
+/// This is bound at `group(1), binding(0)` by the default pipelines, using a dynamic offset.
+#[repr(C)]
+#[derive(Debug,Copy,Clone,bytemuck::Pod,bytemuck::Zeroable)]
+pub struct ObjectUniform {
+    /// The object's `Transform2D` as a `mat4x4<f32>`.
+    pub model: [[f32; 4]; 4],
+    /// The part of the texture mapped onto the model, as `[u, v, width, height]`.
+    pub uv_rect: [f32; 4],
+    /// Multiplied with the sampled texture color.
+    pub color: [f32; 4],
+}
+
+impl ObjectUniform {
+    /// The whole texture is mapped onto the model.
+    pub const FULL_UV: [f32; 4] = [0.0, 0.0, 1.0, 1.0];
+
+    pub const WHITE: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+
+    pub fn new(t: &Transform2D, uv_rect: [f32; 4], color: [f32; 4]) -> Self {
+        Self {
+            model: t.to_mat4(),
+            uv_rect,
+            color
+        }
+    }
+
+    pub fn from_transform(t: &Transform2D) -> Self {
+        Self::new(t, Self::FULL_UV, Self::WHITE)
+    }
+}
+
+/// A single uniform buffer holding the `ObjectUniform` of every `RenderObject`.
+/// Each object owns a slot, which is selected with a dynamic offset when the object is drawn,
+/// so objects sharing a `Model` can still be placed independently.
+pub struct TransformBuffer {
+    pub buffer: wgpu::Buffer,
+    pub bind_group_layout: wgpu::BindGroupLayout,
+    pub bind_group: wgpu::BindGroup,
+    /// Distance in bytes between two slots, respecting `min_uniform_buffer_offset_alignment`.
+    stride: wgpu::BufferAddress,
+    capacity: usize,
+    len: usize,
+    free: Vec<usize>,
+}
+
+impl TransformBuffer {
+    const INITIAL_CAPACITY: usize = 64;
+
+    pub fn new(device: &wgpu::Device) -> Self {
+        let align = device.limits().min_uniform_buffer_offset_alignment as wgpu::BufferAddress;
+        let size = std::mem::size_of::<ObjectUniform>() as wgpu::BufferAddress;
+        let stride = size.div_ceil(align) * align;
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("panels:transforms"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: true,
+                        min_binding_size: wgpu::BufferSize::new(size),
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let buffer = Self::create_buffer(device, stride * Self::INITIAL_CAPACITY as wgpu::BufferAddress);
+        let bind_group = Self::create_bind_group(device, &bind_group_layout, &buffer);
+
+        Self {
+            buffer,
+            bind_group_layout,
+            bind_group,
+            stride,
+            capacity: Self::INITIAL_CAPACITY,
+            len: 0,
+            free: vec![],
+        }
+    }
+
+    fn create_buffer(device: &wgpu::Device, size: wgpu::BufferAddress) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("panels:transforms"),
+            size,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        })
+    }
+
+    fn create_bind_group(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, buffer: &wgpu::Buffer) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("panels:transforms"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer,
+                        offset: 0,
+                        size: wgpu::BufferSize::new(std::mem::size_of::<ObjectUniform>() as wgpu::BufferAddress),
+                    }),
+                },
+            ],
+        })
+    }
+
+    /// Reserve a slot and write its initial value, growing the buffer if it is full.
+    pub fn allocate(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, value: &ObjectUniform) -> usize {
+        let slot = match self.free.pop() {
+            Some(slot) => slot,
+            None => {
+                if self.len == self.capacity {
+                    self.grow(device, queue);
+                }
+                self.len += 1;
+                self.len - 1
+            }
+        };
+        self.write(queue, slot, value);
+        slot
+    }
+
+    /// Return a slot so it can be reused by another object.
+    pub fn release(&mut self, slot: usize) {
+        self.free.push(slot);
+    }
+
+    /// Upload a new value for the slot.
+    pub fn write(&self, queue: &wgpu::Queue, slot: usize, value: &ObjectUniform) {
+        queue.write_buffer(&self.buffer, self.stride * slot as wgpu::BufferAddress, bytemuck::cast_slice(&[*value]));
+    }
+
+    /// The dynamic offset to pass to `set_bind_group` for this slot.
+    pub fn offset(&self, slot: usize) -> wgpu::DynamicOffset {
+        (self.stride * slot as wgpu::BufferAddress) as wgpu::DynamicOffset
+    }
+
+    fn grow(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        let old_size = self.stride * self.capacity as wgpu::BufferAddress;
+        self.capacity *= 2;
+        let buffer = Self::create_buffer(device, self.stride * self.capacity as wgpu::BufferAddress);
+
+        // carry over the existing slots, including writes that are still queued
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("panels:transforms:grow") });
+        encoder.copy_buffer_to_buffer(&self.buffer, 0, &buffer, 0, old_size);
+        queue.submit(std::iter::once(encoder.finish()));
+
+        self.bind_group = Self::create_bind_group(device, &self.bind_group_layout, &buffer);
+        self.buffer = buffer;
+    }
+}
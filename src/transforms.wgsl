@@ -0,0 +1,20 @@
+// The per-object transforms and the camera, as bound by `default:pipe:textured`.
+
+[[block]]
+struct ObjectUniform {
+    model: mat4x4<f32>;
+    // xy: offset, zw: size, in texture coordinates
+    uv_rect: vec4<f32>;
+    color: vec4<f32>;
+};
+
+[[group(1), binding(0)]]
+var<uniform> object: ObjectUniform;
+
+[[block]]
+struct CameraUniform {
+    view_proj: mat4x4<f32>;
+};
+
+[[group(2), binding(0)]]
+var<uniform> camera: CameraUniform;
@@ -0,0 +1,471 @@
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+use std::time::Instant;
+use crate::appmgmt::EventLoopAction;
+use crate::rect::WorldPoint;
+use crate::renderobj::RenderObject;
+use crate::timing::Timing;
+use crate::transform2d::Transform2D;
+use crate::wgpustate::State;
+
+/// Maps linear progress in `0..1` to eased progress.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Easing {
+    Linear,
+    QuadIn,
+    QuadOut,
+    QuadInOut,
+    CubicIn,
+    CubicOut,
+    CubicInOut,
+    SineIn,
+    SineOut,
+    SineInOut,
+    ExpoIn,
+    ExpoOut,
+    /// Overshoots the start before moving.
+    BackIn,
+    /// Overshoots the end before settling.
+    BackOut,
+    ElasticOut,
+    BounceOut,
+}
+
+impl Easing {
+    pub fn apply(&self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::QuadIn => t * t,
+            Easing::QuadOut => 1.0 - (1.0 - t) * (1.0 - t),
+            Easing::QuadInOut => if t < 0.5 { 2.0 * t * t } else { 1.0 - (-2.0 * t + 2.0).powi(2) / 2.0 },
+            Easing::CubicIn => t * t * t,
+            Easing::CubicOut => 1.0 - (1.0 - t).powi(3),
+            Easing::CubicInOut => if t < 0.5 { 4.0 * t * t * t } else { 1.0 - (-2.0 * t + 2.0).powi(3) / 2.0 },
+            Easing::SineIn => 1.0 - (t * PI / 2.0).cos(),
+            Easing::SineOut => (t * PI / 2.0).sin(),
+            Easing::SineInOut => -((PI * t).cos() - 1.0) / 2.0,
+            Easing::ExpoIn => if t == 0.0 { 0.0 } else { 2f32.powf(10.0 * t - 10.0) },
+            Easing::ExpoOut => if t == 1.0 { 1.0 } else { 1.0 - 2f32.powf(-10.0 * t) },
+            Easing::BackIn => {
+                let c = 1.70158;
+                (c + 1.0) * t * t * t - c * t * t
+            }
+            Easing::BackOut => {
+                let c = 1.70158;
+                1.0 + (c + 1.0) * (t - 1.0).powi(3) + c * (t - 1.0).powi(2)
+            }
+            Easing::ElasticOut => {
+                if t == 0.0 || t == 1.0 {
+                    t
+                } else {
+                    2f32.powf(-10.0 * t) * ((t * 10.0 - 0.75) * (2.0 * PI / 3.0)).sin() + 1.0
+                }
+            }
+            Easing::BounceOut => {
+                let (n, d) = (7.5625, 2.75);
+                if t < 1.0 / d {
+                    n * t * t
+                } else if t < 2.0 / d {
+                    let t = t - 1.5 / d;
+                    n * t * t + 0.75
+                } else if t < 2.5 / d {
+                    let t = t - 2.25 / d;
+                    n * t * t + 0.9375
+                } else {
+                    let t = t - 2.625 / d;
+                    n * t * t + 0.984375
+                }
+            }
+        }
+    }
+}
+
+/// The value a tween moves a property to.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Property {
+    /// The object's translation and depth, keeping it's rotation and scale.
+    Position(WorldPoint),
+    Scale([f32; 2]),
+    /// Radians.
+    Rotation(f32),
+    /// The object's tint, see `RenderObject::set_color`.
+    Color([f32; 4]),
+    /// `f32`s in `State::uniform_buffers[buffer]`, starting `offset` bytes in.
+    /// Uniform buffers can't be read back, so the start value has to be given.
+    Uniform { buffer: usize, offset: wgpu::BufferAddress, from: Vec<f32>, to: Vec<f32> },
+}
+
+impl Property {
+    fn target(&self) -> Vec<f32> {
+        match self {
+            Property::Position(p) => vec![p.x, p.y, p.z],
+            Property::Scale(s) => s.to_vec(),
+            Property::Rotation(r) => vec![*r],
+            Property::Color(c) => c.to_vec(),
+            Property::Uniform { to, .. } => to.clone(),
+        }
+    }
+
+    fn current(&self, object: &RenderObject) -> Vec<f32> {
+        match self {
+            Property::Position(_) => vec![object.position.x, object.position.y, object.position.z],
+            Property::Scale(_) => object.transform.scale_xy().to_vec(),
+            Property::Rotation(_) => vec![object.transform.rotation()],
+            Property::Color(_) => object.color.to_vec(),
+            Property::Uniform { from, .. } => from.clone(),
+        }
+    }
+
+    fn write(&self, values: &[f32], state: &State, object: &mut RenderObject) {
+        match self {
+            Property::Position(_) => object.set_position(state, WorldPoint::new(values[0], values[1], values[2])),
+            Property::Scale(_) => {
+                let t = &object.transform;
+                let transform = Transform2D { depth: t.depth, ..Transform2D::from_parts(t.translation(), t.rotation(), [values[0], values[1]], [0.0, 0.0]) };
+                object.set_transform(state, transform);
+            }
+            Property::Rotation(_) => {
+                let t = &object.transform;
+                let transform = Transform2D { depth: t.depth, ..Transform2D::from_parts(t.translation(), values[0], t.scale_xy(), [0.0, 0.0]) };
+                object.set_transform(state, transform);
+            }
+            Property::Color(_) => object.set_color(state, [values[0], values[1], values[2], values[3]]),
+            Property::Uniform { buffer, offset, .. } => {
+                state.queue.write_buffer(&state.uniform_buffers[*buffer], *offset, bytemuck::cast_slice(values));
+            }
+        }
+    }
+}
+
+/// Moves one property of one object over time.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tween {
+    /// The index of the object in the slice given to `TweenManager::update`.
+    pub object: usize,
+    pub property: Property,
+    /// Seconds.
+    pub duration: f32,
+    pub easing: Easing,
+    /// Seconds to wait before starting.
+    pub delay: f32,
+    /// Read from the object when the tween starts.
+    from: Option<Vec<f32>>,
+    done: bool,
+}
+
+impl Tween {
+    pub fn new(object: usize, property: Property, duration: f32) -> Self {
+        Self {
+            object,
+            property,
+            duration,
+            easing: Easing::Linear,
+            delay: 0.0,
+            from: None,
+            done: false,
+        }
+    }
+
+    pub fn with_easing(mut self, easing: Easing) -> Self {
+        self.easing = easing;
+        self
+    }
+
+    pub fn with_delay(mut self, delay: f32) -> Self {
+        self.delay = delay;
+        self
+    }
+}
+
+/// A tween, or tweens combined to run one after another or together.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Animation {
+    Tween(Tween),
+    /// Waits, e.g. between the steps of a `Sequence`.
+    Delay(f32),
+    Sequence(Vec<Animation>),
+    Parallel(Vec<Animation>),
+}
+
+impl From<Tween> for Animation {
+    fn from(t: Tween) -> Self {
+        Animation::Tween(t)
+    }
+}
+
+impl Animation {
+    /// Total length in seconds.
+    pub fn duration(&self) -> f32 {
+        match self {
+            Animation::Tween(t) => t.delay + t.duration,
+            Animation::Delay(d) => *d,
+            Animation::Sequence(a) => a.iter().map(|a| a.duration()).sum(),
+            Animation::Parallel(a) => a.iter().map(|a| a.duration()).fold(0.0, f32::max),
+        }
+    }
+
+    /// Whether any tween in the animation moves this object.
+    pub fn touches(&self, object: usize) -> bool {
+        match self {
+            Animation::Tween(t) => t.object == object,
+            Animation::Delay(_) => false,
+            Animation::Sequence(a) | Animation::Parallel(a) => a.iter().any(|a| a.touches(object)),
+        }
+    }
+
+    /// Writes every started tween's value at `time` seconds into the animation.
+    fn apply(&mut self, time: f32, state: &State, objects: &mut [RenderObject]) {
+        match self {
+            Animation::Tween(t) => {
+                if t.done || time < t.delay {
+                    return;
+                }
+                let object = match objects.get_mut(t.object) {
+                    Some(o) => o,
+                    None => return,
+                };
+                let from = t.from.get_or_insert_with(|| t.property.current(object));
+                let to = t.property.target();
+                let progress = if t.duration > 0.0 { (time - t.delay) / t.duration } else { 1.0 };
+                let e = t.easing.apply(progress);
+                let values: Vec<f32> = from.iter().zip(to.iter()).map(|(a, b)| a + (b - a) * e).collect();
+                t.property.write(&values, state, object);
+                t.done = progress >= 1.0;
+            }
+            Animation::Delay(_) => {}
+            Animation::Sequence(children) => {
+                let mut start = 0.0;
+                for c in children {
+                    if time < start {
+                        break;
+                    }
+                    c.apply(time - start, state, objects);
+                    start += c.duration();
+                }
+            }
+            Animation::Parallel(children) => {
+                for c in children {
+                    c.apply(time, state, objects);
+                }
+            }
+        }
+    }
+}
+
+/// Identifies an animation started by a `TweenManager`.
+pub type TweenId = usize;
+
+struct Running<M> {
+    id: TweenId,
+    animation: Animation,
+    time: f32,
+    on_complete: Option<M>,
+}
+
+/// Runs animations on a program's `RenderObject`s.
+/// * Call `update` from `ProgramHook::update` with the objects the tweens refer to.
+/// * When an animation finishes, it's completion message is returned as `EventLoopAction::MSG`.
+///   One message is returned per call; the rest follow on the next updates.
+pub struct TweenManager<M> {
+    running: Vec<Running<M>>,
+    next_id: TweenId,
+    pending: VecDeque<M>,
+    /// Used to measure time when the renderer's update `Timing` doesn't keep track of it.
+    last_update: Option<Instant>,
+}
+
+impl<M> Default for TweenManager<M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<M> TweenManager<M> {
+    pub fn new() -> Self {
+        Self {
+            running: vec![],
+            next_id: 0,
+            pending: VecDeque::new(),
+            last_update: None,
+        }
+    }
+
+    /// Start an animation. `on_complete` is sent to the conductor when it finishes, but not if it is cancelled.
+    pub fn start(&mut self, animation: impl Into<Animation>, on_complete: Option<M>) -> TweenId {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.running.push(Running {
+            id,
+            animation: animation.into(),
+            time: 0.0,
+            on_complete,
+        });
+        id
+    }
+
+    /// Stop an animation, leaving it's properties where they are. Returns `false` if it wasn't running.
+    pub fn cancel(&mut self, id: TweenId) -> bool {
+        let before = self.running.len();
+        self.running.retain(|r| r.id != id);
+        self.running.len() != before
+    }
+
+    /// Stop every animation that moves the object.
+    pub fn cancel_object(&mut self, object: usize) {
+        self.running.retain(|r| !r.animation.touches(object));
+    }
+
+    pub fn cancel_all(&mut self) {
+        self.running.clear();
+    }
+
+    pub fn is_running(&self, id: TweenId) -> bool {
+        self.running.iter().any(|r| r.id == id)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.running.is_empty()
+    }
+
+    /// Advance every animation by `dt` seconds.
+    pub fn advance(&mut self, dt: f32, state: &State, objects: &mut [RenderObject]) -> EventLoopAction<M> {
+        for r in self.running.iter_mut() {
+            r.time += dt;
+            r.animation.apply(r.time, state, objects);
+        }
+        self.finish()
+    }
+
+    /// Drop the animations that have run their full duration, and return the next completion message.
+    fn finish(&mut self) -> EventLoopAction<M> {
+        let mut i = 0;
+        while i < self.running.len() {
+            if self.running[i].time >= self.running[i].animation.duration() {
+                let finished = self.running.remove(i);
+                if let Some(m) = finished.on_complete {
+                    self.pending.push_back(m);
+                }
+            } else {
+                i += 1;
+            }
+        }
+
+        match self.pending.pop_front() {
+            Some(m) => EventLoopAction::MSG(m),
+            None => EventLoopAction::None,
+        }
+    }
+
+    /// Advance by the time since the last update.
+    /// * `timing` is the renderer's `updatef_status`, e.g. `tweens.update(&renderer.updatef_status, state, &mut renderer.my_objects)`.
+    pub fn update(&mut self, timing: &Timing, state: &State, objects: &mut [RenderObject]) -> EventLoopAction<M> {
+        let now = Instant::now();
+        let dt = match (timing.elapsed(), self.last_update) {
+            (Some(elapsed), _) => elapsed.as_secs_f32(),
+            (None, Some(last)) => (now - last).as_secs_f32(),
+            (None, None) => 0.0,
+        };
+        self.last_update = Some(now);
+        self.advance(dt, state, objects)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [Easing; 16] = [
+        Easing::Linear, Easing::QuadIn, Easing::QuadOut, Easing::QuadInOut,
+        Easing::CubicIn, Easing::CubicOut, Easing::CubicInOut,
+        Easing::SineIn, Easing::SineOut, Easing::SineInOut,
+        Easing::ExpoIn, Easing::ExpoOut, Easing::BackIn, Easing::BackOut,
+        Easing::ElasticOut, Easing::BounceOut,
+    ];
+
+    #[test]
+    fn easing_endpoints() {
+        for e in ALL {
+            assert!(e.apply(0.0).abs() < 1e-3, "{:?} starts at {}", e, e.apply(0.0));
+            assert!((e.apply(1.0) - 1.0).abs() < 1e-3, "{:?} ends at {}", e, e.apply(1.0));
+            // progress outside 0..1 is clamped
+            assert_eq!(e.apply(-1.0), e.apply(0.0));
+            assert_eq!(e.apply(2.0), e.apply(1.0));
+        }
+    }
+
+    #[test]
+    fn easing_is_monotonic() {
+        // the back, elastic and bounce easings overshoot on purpose
+        let steady = ALL.iter().filter(|e| !matches!(e, Easing::BackIn | Easing::BackOut | Easing::ElasticOut | Easing::BounceOut));
+        for e in steady {
+            let mut last = e.apply(0.0);
+            for i in 1..=1000 {
+                let v = e.apply(i as f32 / 1000.0);
+                assert!(v >= last - 1e-6, "{:?} goes back at {}", e, i);
+                last = v;
+            }
+        }
+        assert!(Easing::BackOut.apply(0.8) > 1.0);
+        assert!(Easing::BackIn.apply(0.2) < 0.0);
+    }
+
+    fn tween(object: usize, duration: f32) -> Animation {
+        Tween::new(object, Property::Rotation(1.0), duration).into()
+    }
+
+    #[test]
+    fn animation_durations() {
+        let a = Animation::Sequence(vec![
+            tween(0, 1.0),
+            Animation::Delay(0.5),
+            Animation::Parallel(vec![tween(1, 2.0), Tween::new(2, Property::Scale([2.0, 2.0]), 1.0).with_delay(1.5).into()]),
+        ]);
+        assert_eq!(a.duration(), 4.0);
+        assert!(a.touches(2));
+        assert!(!a.touches(3));
+        assert_eq!(Animation::Parallel(vec![]).duration(), 0.0);
+    }
+
+    fn message<M>(action: EventLoopAction<M>) -> Option<M> {
+        match action {
+            EventLoopAction::MSG(m) => Some(m),
+            _ => None,
+        }
+    }
+
+    fn step(tweens: &mut TweenManager<&'static str>, dt: f32) -> Option<&'static str> {
+        for r in tweens.running.iter_mut() {
+            r.time += dt;
+        }
+        message(tweens.finish())
+    }
+
+    #[test]
+    fn completes_in_order() {
+        let mut tweens = TweenManager::new();
+        let short = tweens.start(tween(0, 1.0), Some("short"));
+        let long = tweens.start(tween(1, 2.0), Some("long"));
+        let silent = tweens.start(tween(2, 1.0), None);
+        let cancelled = tweens.start(tween(3, 1.0), Some("cancelled"));
+        assert!(tweens.cancel(cancelled));
+        assert!(!tweens.cancel(cancelled));
+
+        assert_eq!(step(&mut tweens, 0.5), None);
+        assert_eq!(step(&mut tweens, 0.5), Some("short"));
+        assert!(!tweens.is_running(short) && !tweens.is_running(silent));
+        assert!(tweens.is_running(long));
+        assert_eq!(step(&mut tweens, 1.0), Some("long"));
+        assert!(tweens.is_empty());
+        assert_eq!(step(&mut tweens, 1.0), None);
+
+        // several finishing at once come out one per update
+        tweens.start(tween(0, 1.0), Some("a"));
+        tweens.start(tween(1, 1.0), Some("b"));
+        tweens.cancel_object(1);
+        tweens.start(tween(2, 1.0), Some("c"));
+        assert_eq!(step(&mut tweens, 1.0), Some("a"));
+        assert_eq!(step(&mut tweens, 0.0), Some("c"));
+        assert_eq!(step(&mut tweens, 0.0), None);
+    }
+}
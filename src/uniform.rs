@@ -0,0 +1,329 @@
+use std::any::Any;
+use std::marker::PhantomData;
+use anyhow::*;
+use wgpu::util::DeviceExt;
+
+/// The WGSL type of a uniform struct member, used to check a Rust struct's layout against WGSL's.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum UniformField {
+    /// `f32`, `i32` or `u32`.
+    Scalar,
+    Vec2,
+    /// Aligned to 16 bytes but only 12 long, so a scalar may follow it.
+    Vec3,
+    Vec4,
+    Mat2x2,
+    Mat3x3,
+    Mat4x4,
+    /// An array of `count` 16-byte aligned elements, e.g. `array<vec4<f32>, 8>`.
+    Array { count: usize, stride: usize },
+}
+
+impl UniformField {
+    pub fn align(&self) -> usize {
+        match self {
+            UniformField::Scalar => 4,
+            UniformField::Vec2 | UniformField::Mat2x2 => 8,
+            _ => 16,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        match self {
+            UniformField::Scalar => 4,
+            UniformField::Vec2 => 8,
+            UniformField::Vec3 => 12,
+            UniformField::Vec4 | UniformField::Mat2x2 => 16,
+            UniformField::Mat3x3 => 48,
+            UniformField::Mat4x4 => 64,
+            UniformField::Array { count, stride } => count * stride,
+        }
+    }
+}
+
+/// Check that a type can be a WGSL uniform struct: a non-zero size that is a multiple of 16.
+pub fn check_uniform_size<T: bytemuck::Pod>() -> Result<()> {
+    let size = std::mem::size_of::<T>();
+    if size == 0 || !size.is_multiple_of(16) {
+        bail!("{} is {} bytes; uniform structs must be a non-zero multiple of 16 bytes, add padding", std::any::type_name::<T>(), size);
+    }
+    Ok(())
+}
+
+/// Check a type's member offsets against the WGSL uniform layout rules, e.g.
+/// `check_uniform_fields::<Globals>(&[("time", offset_of!(Globals, time), UniformField::Scalar), ...])`.
+/// * Catches the usual mistakes: a `vec3`/matrix member that isn't 16-byte aligned, and arrays with a stride that isn't a multiple of 16.
+pub fn check_uniform_fields<T: bytemuck::Pod>(fields: &[(&str, usize, UniformField)]) -> Result<()> {
+    check_uniform_size::<T>()?;
+    let size = std::mem::size_of::<T>();
+    let mut end = 0;
+    for (name, offset, field) in fields {
+        if !offset.is_multiple_of(field.align()) {
+            bail!("`{}` is at offset {}, but WGSL aligns a {:?} to {} bytes", name, offset, field, field.align());
+        }
+        if let UniformField::Array { stride, .. } = field {
+            if !stride.is_multiple_of(16) {
+                bail!("`{}` has a stride of {}; uniform array strides must be a multiple of 16", name, stride);
+            }
+        }
+        if *offset < end {
+            bail!("`{}` at offset {} overlaps the member before it", name, offset);
+        }
+        end = offset + field.size();
+        if end > size {
+            bail!("`{}` ends at {}, past the end of the {} byte struct", name, end, size);
+        }
+    }
+    Ok(())
+}
+
+/// A value mirrored in a uniform buffer, uploaded only when it changed.
+pub struct Uniform<T: bytemuck::Pod> {
+    value: T,
+    dirty: bool,
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+impl<T: bytemuck::Pod> Uniform<T> {
+    /// Creates the buffer and a bind group with it at binding 0. The size is checked with `check_uniform_size`.
+    pub fn new(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, value: T) -> Result<Self> {
+        check_uniform_size::<T>()?;
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(std::any::type_name::<T>()),
+            contents: bytemuck::bytes_of(&value),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(std::any::type_name::<T>()),
+            layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+        Ok(Self {
+            value,
+            dirty: false,
+            buffer,
+            bind_group,
+        })
+    }
+
+    pub fn get(&self) -> &T {
+        &self.value
+    }
+
+    pub fn set(&mut self, value: T) {
+        self.value = value;
+        self.dirty = true;
+    }
+
+    /// Marks the value as changed.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.dirty = true;
+        &mut self.value
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+}
+
+/// A `Uniform` of any type, so `State` can keep them in one list.
+pub trait UniformSlot: Any {
+    /// Write the value if it changed. Returns whether it did.
+    fn upload(&mut self, queue: &wgpu::Queue) -> bool;
+    fn bind_group(&self) -> &wgpu::BindGroup;
+    fn buffer(&self) -> &wgpu::Buffer;
+    /// The size of the value in bytes.
+    fn size(&self) -> u64;
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: bytemuck::Pod> UniformSlot for Uniform<T> {
+    fn upload(&mut self, queue: &wgpu::Queue) -> bool {
+        if !self.dirty {
+            return false;
+        }
+        queue.write_buffer(&self.buffer, 0, bytemuck::bytes_of(&self.value));
+        self.dirty = false;
+        true
+    }
+
+    fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+
+    fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+
+    fn size(&self) -> u64 {
+        std::mem::size_of::<T>() as u64
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// A typed handle to a `Uniform` stored in `State::uniforms`.
+pub struct UniformId<T> {
+    index: usize,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for UniformId<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for UniformId<T> {}
+
+impl<T> std::fmt::Debug for UniformId<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "UniformId({})", self.index)
+    }
+}
+
+impl<T> UniformId<T> {
+    /// The index in `Uniforms::slots`, e.g. for `RenderObject::uniforms`.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+}
+
+/// Every `Uniform` owned by `State`, sharing one bind group layout: a uniform buffer at binding 0,
+/// visible to vertex, fragment and compute shaders.
+pub struct Uniforms {
+    pub layout: wgpu::BindGroupLayout,
+    pub slots: Vec<Box<dyn UniformSlot>>,
+}
+
+impl Uniforms {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("panels:uniforms"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        Self {
+            layout,
+            slots: vec![],
+        }
+    }
+
+    pub fn add<T: bytemuck::Pod>(&mut self, device: &wgpu::Device, value: T) -> Result<UniformId<T>> {
+        let uniform = Uniform::new(device, &self.layout, value)?;
+        self.slots.push(Box::new(uniform));
+        Ok(UniformId {
+            index: self.slots.len() - 1,
+            marker: PhantomData,
+        })
+    }
+
+    pub fn get<T: bytemuck::Pod>(&self, id: UniformId<T>) -> &Uniform<T> {
+        self.slots[id.index].as_any().downcast_ref().expect("UniformId used with another State")
+    }
+
+    pub fn get_mut<T: bytemuck::Pod>(&mut self, id: UniformId<T>) -> &mut Uniform<T> {
+        self.slots[id.index].as_any_mut().downcast_mut().expect("UniformId used with another State")
+    }
+
+    /// Write every changed value, and return how many were written.
+    pub fn upload(&mut self, queue: &wgpu::Queue) -> usize {
+        self.slots.iter_mut().map(|u| u.upload(queue)).filter(|written| *written).count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::mem::offset_of;
+
+    #[repr(C)]
+    #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+    struct Light {
+        color: [f32; 3],
+        // a scalar fits in the 4 bytes after a vec3
+        intensity: f32,
+        direction: [f32; 2],
+        _padding: [f32; 2],
+        colors: [[f32; 4]; 2],
+    }
+
+    #[repr(C)]
+    #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+    struct Odd {
+        time: f32,
+        size: [f32; 2],
+    }
+
+    #[test]
+    fn field_layout() {
+        let table = [
+            (UniformField::Scalar, 4, 4),
+            (UniformField::Vec2, 8, 8),
+            (UniformField::Vec3, 16, 12),
+            (UniformField::Vec4, 16, 16),
+            (UniformField::Mat2x2, 8, 16),
+            (UniformField::Mat3x3, 16, 48),
+            (UniformField::Mat4x4, 16, 64),
+            (UniformField::Array { count: 3, stride: 16 }, 16, 48),
+        ];
+        for (field, align, size) in table {
+            assert_eq!((field.align(), field.size()), (align, size), "{:?}", field);
+        }
+    }
+
+    #[test]
+    fn uniform_size() {
+        assert!(check_uniform_size::<Light>().is_ok());
+        assert!(check_uniform_size::<[f32; 4]>().is_ok());
+        let err = check_uniform_size::<Odd>().unwrap_err().to_string();
+        assert!(err.contains("is 12 bytes"), "{}", err);
+        assert!(check_uniform_size::<()>().is_err());
+    }
+
+    #[test]
+    fn uniform_fields() {
+        let fields = [
+            ("color", offset_of!(Light, color), UniformField::Vec3),
+            ("intensity", offset_of!(Light, intensity), UniformField::Scalar),
+            ("direction", offset_of!(Light, direction), UniformField::Vec2),
+            ("colors", offset_of!(Light, colors), UniformField::Array { count: 2, stride: 16 }),
+        ];
+        check_uniform_fields::<Light>(&fields).unwrap();
+
+        let message = |fields: &[(&str, usize, UniformField)]| check_uniform_fields::<Light>(fields).unwrap_err().to_string();
+        // the padding after the vec2 is only 8-byte aligned
+        assert!(message(&[("_padding", offset_of!(Light, _padding), UniformField::Vec3)]).contains("aligns a Vec3 to 16"));
+        // a vec4 in place of the vec3 leaves no room for the scalar
+        assert!(message(&[("color", 0, UniformField::Vec4), ("intensity", 12, UniformField::Scalar)]).contains("overlaps"));
+        assert!(message(&[("colors", 32, UniformField::Array { count: 2, stride: 8 })]).contains("stride of 8"));
+        assert!(message(&[("colors", 32, UniformField::Array { count: 3, stride: 16 })]).contains("past the end"));
+    }
+}
@@ -14,16 +14,42 @@ use std::time::{Duration, Instant};
 use wgpu::util::DeviceExt;
 use wgpu::{SurfaceTexture, TextureView};
 use winit::{event::*, window::*};
-use crate::texture::Texture;
+use crate::texture::{Texture, TextureOptions};
+use crate::mipmap::MipmapGenerator;
+use crate::uniform::{Uniform, UniformId, Uniforms};
+use crate::hotreload::{self, ShaderPipelineFn, ShaderWatcher};
+use crate::pipelinedesc::{PipelineDesc, ShaderSource, ShaderStage};
+use crate::preprocess::{Preprocessor, ProcessedShader};
+use crate::reflect::{BindResource, ShaderReflection};
+use std::path::Path;
+use anyhow::Context;
+use std::sync::Mutex;
+use crate::sampler::{SamplerCache, SamplerOptions};
+use std::sync::Arc;
 // use crate::pipelines::Pipeline;
-use std::collections::HashMap;
-use crate::pipelines::Pipeline;
-use crate::rect::WorldPoint;
+use std::collections::{HashMap, HashSet};
+use crate::pipelines::{Pipeline, PipelineRecipe, PipelineTarget};
+use crate::rect::{LogicalRectangle, ScreenRectangle, WorldPoint, WorldRectangle};
+use crate::transformbuffer::TransformBuffer;
+use crate::camera::{Camera, CameraBinding};
+use crate::text::{Font, TextBatch, TextSection, TextSystem};
+use crate::shapes::{ShapeBatch, ShapeBuilder, ShapeRenderer};
+use crate::sprites::{SpriteBatch, SpriteBuilder, SpriteRenderer};
 
 /// The render function for the WGPU `State`, defined by the user and called in the EventLoop
 /// The `bool` parameter indicates a forced surface redraw request.
 pub type StateRenderFunction = dyn Fn(&mut State, bool) -> Result<(), wgpu::SurfaceError>;
 
+/// Information about the target of the `RenderPass` being recorded.
+pub struct PassContext {
+    /// The index of the camera the renderer draws through.
+    pub camera: usize,
+    /// The size of the texture being drawn into, in physical pixels.
+    pub target_size: winit::dpi::PhysicalSize<u32>,
+    /// The attachments being drawn into, which pipelines are built for. See `State::render_pipeline`.
+    pub target: PipelineTarget,
+}
+
 pub struct State {
     // pub renderf: Box<StateRenderFunction>,
     pub surface: wgpu::Surface,
@@ -31,18 +57,63 @@ pub struct State {
     pub queue: wgpu::Queue,
     pub config: wgpu::SurfaceConfiguration,
     pub size: winit::dpi::PhysicalSize<u32>,
+    /// The window's DPI scale factor, physical pixels per logical pixel.
+    pub scale_factor: f64,
     pub render_pipelines: Vec<wgpu::RenderPipeline>,
+    pub compute_pipelines: Vec<wgpu::ComputePipeline>,
+    /// Buffers that compute shaders read and write, see `State::create_storage_buffer`.
+    pub storage_buffers: Vec<wgpu::Buffer>,
     /// A map for each pipeline.
     // pub pipeline_map: HashMap<&str,Pipeline>,
     pub models: Vec<Model>,
+    /// Raw buffers, e.g. for `tween::Property::Uniform`. Typed values are better kept in `uniforms`.
     pub uniform_buffers: Vec<wgpu::Buffer>,
+    /// Typed uniform values, uploaded when changed before each frame is encoded. See `State::add_uniform`.
+    pub uniforms: Uniforms,
     pub bindgroup_layouts: Vec<wgpu::BindGroupLayout>,
     pub bind_groups: Vec<wgpu::BindGroup>,
+    /// The bind groups made by `texture_bind_group_for_layout` for each texture, with their layouts,
+    /// so `resize_render_target` can rebuild them.
+    texture_bind_groups: HashMap<usize, Vec<(usize, usize)>>,
+
+    /// Per-object uniforms for every `RenderObject`, bound at `group(1)` by the default pipelines.
+    pub transforms: TransformBuffer,
+
+    /// All cameras, bound at `group(2)` by the default pipelines. Camera `0` is the default `Camera::ndc()`.
+    pub cameras: Vec<CameraBinding>,
+    pub camera_bind_group_layout: wgpu::BindGroupLayout,
+    /// Describes the `TextureRenderer` currently being drawn, set before each `ProgramHook::render`.
+    pub pass: PassContext,
+
+    /// Loaded fonts and the glyph atlas, see `State::prepare_text`.
+    pub text: TextSystem,
+    /// The pipeline and buffers for `ShapeBuilder` geometry, see `State::prepare_shapes`.
+    pub shapes: ShapeRenderer,
+    /// The pipeline and buffers for `SpriteBuilder` quads, see `State::prepare_sprites`.
+    pub sprites: SpriteRenderer,
+    /// Builds mip chains for `Texture::from_image_with`.
+    pub mipmaps: MipmapGenerator,
+    /// Samplers shared between textures, see `State::sampler`.
+    pub samplers: SamplerCache,
 
     /// All textures stored in this state.
     pub textures: Vec<texture::Texture>,
 
     pub pipeline_map: HashMap<String, Pipeline>,
+    pub compute_pipeline_map: HashMap<String, Pipeline>,
+    /// Pipelines built from a `PipelineDesc`, so identical descriptions share one pipeline.
+    /// * Keyed with the shader expanded to `ShaderSource::Wgsl`, so file contents and defines count.
+    pub pipeline_cache: HashMap<PipelineDesc, Pipeline>,
+    /// How each pipeline in `render_pipelines` was built, so it can be built for other targets.
+    pub pipeline_recipes: HashMap<usize, PipelineRecipe>,
+    /// The versions of each pipeline in `render_pipelines`, by the target they were built for.
+    pub pipeline_variants: HashMap<usize, HashMap<PipelineTarget, usize>>,
+    /// Targets every recipe has been built for.
+    prepared_targets: HashSet<PipelineTarget>,
+    /// Shader files whose pipelines are rebuilt when they change.
+    pub shader_watcher: ShaderWatcher,
+    /// Expands `#include`, `#define` and `#ifdef` in shader files and pipeline descriptions.
+    pub preprocessor: Preprocessor,
     pub model_map: HashMap<String, usize>,
     pub bindgroup_map: HashMap<String, usize>,
 
@@ -68,6 +139,7 @@ pub struct State {
 impl State {
     pub async fn new(window: &Window) -> Self {
         let size = window.inner_size();
+        let scale_factor = window.scale_factor();
 
         let instance = wgpu::Instance::new(wgpu::Backends::all());
         let surface = unsafe { instance.create_surface(window) };
@@ -90,8 +162,12 @@ impl State {
             .request_device(
                 &wgpu::DeviceDescriptor {
                     label: None,
-                    features: wgpu::Features::empty(),
-                    limits: wgpu::Limits::default(),
+                    // compressed textures and push constants are used when the adapter has them
+                    features: adapter.features() & (wgpu::Features::TEXTURE_COMPRESSION_BC | wgpu::Features::PUSH_CONSTANTS),
+                    limits: wgpu::Limits {
+                        max_push_constant_size: adapter.limits().max_push_constant_size,
+                        ..wgpu::Limits::default()
+                    },
                 },
                 None,
             )
@@ -108,6 +184,20 @@ impl State {
 
         surface.configure(&device, &config);
 
+        let transforms = TransformBuffer::new(&device);
+
+        let camera_bind_group_layout = CameraBinding::create_bind_group_layout(&device);
+        let mut default_camera = Camera::ndc();
+        default_camera.set_viewport(size.width, size.height);
+        let cameras = vec![CameraBinding::new(&device, &camera_bind_group_layout, default_camera)];
+
+        let surface_target = PipelineTarget::new(config.format);
+        let text = TextSystem::new(&device, &surface_target, &camera_bind_group_layout);
+        let shapes = ShapeRenderer::new(&device, &surface_target, &camera_bind_group_layout);
+        let sprites = SpriteRenderer::new(&device, &surface_target, &camera_bind_group_layout);
+        let mipmaps = MipmapGenerator::new(&device);
+        let uniforms = Uniforms::new(&device);
+
         let mut state = Self {
             // renderf,
             surface,
@@ -115,12 +205,30 @@ impl State {
             queue,
             config,
             size,
+            scale_factor,
             render_pipelines: vec![],
+            compute_pipelines: vec![],
+            storage_buffers: vec![],
             // pipeline_map: HashMap::new(),
             models: vec![],
             uniform_buffers: vec![],
+            uniforms,
             bindgroup_layouts: vec![],
             bind_groups: vec![],
+            texture_bind_groups: HashMap::new(),
+            transforms,
+            cameras,
+            camera_bind_group_layout,
+            pass: PassContext {
+                camera: 0,
+                target_size: size,
+                target: surface_target,
+            },
+            text,
+            shapes,
+            sprites,
+            mipmaps,
+            samplers: SamplerCache::new(),
             textures: vec![],
             // texture_renderers: vec![],
             // objects: vec![],
@@ -128,16 +236,20 @@ impl State {
             // bglayout_map: HashMap::new(),
             // bindgroup_map: HashMap::new(),
             pipeline_map: HashMap::new(),
+            compute_pipeline_map: HashMap::new(),
+            pipeline_cache: HashMap::new(),
+            pipeline_recipes: HashMap::new(),
+            pipeline_variants: HashMap::new(),
+            prepared_targets: HashSet::new(),
+            shader_watcher: ShaderWatcher::new(),
+            preprocessor: Preprocessor::new(),
             model_map: HashMap::new(),
             bindgroup_map: HashMap::new(),
             loop_fps: None
         };
 
-        state.add_texture(texture::Texture::from_bytes(&state.device, &state.queue, diffuse_bytes).unwrap());
-
-        // uniform buffer setup
-
-        let pos_unif = WorldPoint::new(0.0,0.0,0.0);
+        let diffuse = texture::Texture::from_bytes(&state.device, &state.queue, diffuse_bytes, &mut state.samplers).unwrap();
+        state.add_texture(diffuse);
 
         // models setup
 
@@ -162,11 +274,6 @@ impl State {
             vertex_buffer,
             index_buffer,
             index_format: wgpu::IndexFormat::Uint16,
-            offset_buffer: state.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: None,
-                contents: bytemuck::cast_slice(&[pos_unif]),
-                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-            }),
             num_indices,
         });
 
@@ -175,93 +282,15 @@ impl State {
         // state.uniform_buffers.push());
 
         state.add_pipeline("default:pipe:textured", |s| {
-
             // render pipeline setup
             let shader = s.device.create_shader_module(&wgpu::ShaderModuleDescriptor {
                 label: None,
-                source: wgpu::ShaderSource::Wgsl(include_str!("shader.wgsl").into()),
+                source: wgpu::ShaderSource::Wgsl(default_shader().source.into()),
             });
-
-            let panel_bind_group_layout = s.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                label: None,
-                entries: &[
-                    // texture
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Texture {
-                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                            view_dimension: wgpu::TextureViewDimension::D2,
-                            multisampled: false,
-                        },
-                        count: None,
-                    },
-                    // sampler
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 1,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Sampler {
-                            filtering: true,
-                            comparison: false,
-                        },
-                        count: None,
-                    },
-                    // uniform position
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 2,
-                        visibility: wgpu::ShaderStages::VERTEX,
-                        ty: wgpu::BindingType::Buffer {
-                            ty: wgpu::BufferBindingType::Uniform,
-                            has_dynamic_offset: false,
-                            min_binding_size: None,
-                        },
-                        count: None,
-                    },
-                ],
-            });
-
-            let render_pipeline_layout =
-                s.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                    label: None,
-                    bind_group_layouts: &[&panel_bind_group_layout],
-                    push_constant_ranges: &[],
-                });
-            (s.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                label: None,
-                layout: Some(&render_pipeline_layout),
-                vertex: wgpu::VertexState {
-                    module: &shader,
-                    entry_point: "main",
-                    buffers: &[Vertex::desc()],
-                },
-                fragment: Some(wgpu::FragmentState {
-                    module: &shader,
-                    entry_point: "main",
-                    targets: &[wgpu::ColorTargetState {
-                        format: s.config.format,
-                        blend: Some(wgpu::BlendState::REPLACE),
-                        write_mask: wgpu::ColorWrites::ALL,
-                    }],
-                }),
-                primitive: wgpu::PrimitiveState {
-                    topology: wgpu::PrimitiveTopology::TriangleList,
-                    strip_index_format: None,
-                    front_face: wgpu::FrontFace::Ccw,
-                    cull_mode: Some(wgpu::Face::Back),
-                    clamp_depth: false,
-                    polygon_mode: wgpu::PolygonMode::Fill,
-                    conservative: false,
-                },
-                depth_stencil: None,
-                multisample: wgpu::MultisampleState {
-                    count: 1,
-                    mask: !0,
-                    alpha_to_coverage_enabled: false,
-                },
-            }),
-                panel_bind_group_layout
-            )
+            State::build_textured_pipeline(s, &shader)
         });
+        let reflection = ShaderReflection::from_wgsl(&default_shader().source).expect("the default shader is valid");
+        state.pipeline_map.get_mut("default:pipe:textured").unwrap().reflection = Some(Arc::new(reflection));
 
         state.create_bindgroup("default_textured", |s|{
             vec![
@@ -273,10 +302,6 @@ impl State {
                     binding: 1,
                     resource: wgpu::BindingResource::Sampler(&s.textures[0].sampler),
                 },
-                wgpu::BindGroupEntry {
-                    binding: 2,
-                    resource: s.models[0].offset_buffer.as_entire_binding(),
-                },
             ]
         });
 
@@ -284,6 +309,71 @@ impl State {
 
     }
 
+    /// Builds `default:pipe:textured` from a shader with it's entry points and bindings, for `s.pass.target`,
+    /// e.g. to reload it with `State::watch_shader`.
+    pub fn build_textured_pipeline(s: &State, shader: &wgpu::ShaderModule) -> (wgpu::RenderPipeline, wgpu::BindGroupLayout) {
+        let panel_bind_group_layout = s.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                // texture
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                // sampler
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler {
+                        filtering: true,
+                        comparison: false,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let render_pipeline_layout =
+            s.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[&panel_bind_group_layout, &s.transforms.bind_group_layout, &s.camera_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        (s.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: None,
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: "main",
+                buffers: &[Vertex::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: "main",
+                targets: &s.pass.target.color_targets(Some(wgpu::BlendState::REPLACE)),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: Some(wgpu::Face::Back),
+                clamp_depth: false,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                conservative: false,
+            },
+            depth_stencil: s.pass.target.depth_stencil_state(true, wgpu::CompareFunction::LessEqual),
+            multisample: s.pass.target.multisample_state(),
+        }),
+            panel_bind_group_layout
+        )
+    }
+
     pub fn get_pipeline(&self, name: &str) -> &Pipeline {
         self.pipeline_map.get(&String::from(name)).unwrap()
     }
@@ -313,19 +403,11 @@ impl State {
             usage: wgpu::BufferUsages::INDEX
         });
 
-
-        let ob = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: None,
-            contents: bytemuck::cast_slice(&[WorldPoint::new(0.0,0.0,0.0)]),
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST
-        });
-
         let model_id = self.models.len();
         self.models.push(Model{
             vertex_buffer: vb,
             index_buffer: ib,
             index_format: wgpu::IndexFormat::Uint16,
-            offset_buffer: ob,
             num_indices: 0
         });
 
@@ -349,37 +431,541 @@ impl State {
         bgid
     }
 
+    /// Create a bind group for `group(group)` of a pipeline with several bind group layouts, see `Pipeline::bindgrouplayouts`.
+    pub fn create_bindgroup_for_group(&mut self, pipeline: &str, group: usize, buildf: fn(&State) -> Vec<wgpu::BindGroupEntry>) -> usize {
+        let entries = buildf(self);
+        let layout = self.get_pipeline(pipeline).bindgrouplayouts[group];
+        let bg = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &self.bindgroup_layouts[layout],
+            entries: entries.as_slice()
+        });
+        self.bind_groups.push(bg);
+        self.bind_groups.len() - 1
+    }
+
+    /// Create a bind group for `group(group)` of a pipeline, addressing bindings by their variable name in the shader, e.g.
+    /// `state.create_bindgroup_named("sprites", 0, &[("t_diffuse", BindResource::Texture(t)), ("s_diffuse", BindResource::Sampler(t))])`.
+    /// * Every binding the shader declares in the group has to be given, and each resource is checked against it's declaration.
+    /// * Needs a pipeline with a known shader, see `Pipeline::reflection`.
+    pub fn create_bindgroup_named(&mut self, pipeline: &str, group: u32, bindings: &[(&str, BindResource)]) -> anyhow::Result<usize> {
+        let p = self.pipeline_map.get(pipeline).with_context(|| format!("no pipeline named \"{}\"", pipeline))?;
+        let reflection = p.reflection.as_ref().with_context(|| format!("pipeline \"{}\" has no shader reflection, use `create_bindgroup`", pipeline))?;
+        let layout = *p.bindgrouplayouts.get(group as usize).with_context(|| format!("pipeline \"{}\" has no layout for group({})", pipeline, group))?;
+
+        let mut entries = vec![];
+        for (name, resource) in bindings {
+            let declared = reflection.binding(name).with_context(|| {
+                let names: Vec<&str> = reflection.group(group).map(|b| b.name.as_str()).collect();
+                format!("the shader of \"{}\" has no binding named `{}`, group({}) has {:?}", pipeline, name, group, names)
+            })?;
+            if declared.group != group {
+                anyhow::bail!("`{}` is in group({}), not group({})", name, declared.group, group);
+            }
+            if bindings.iter().filter(|(n, _)| n == name).count() > 1 {
+                anyhow::bail!("`{}` is given more than once", name);
+            }
+            let expected = declared.to_binding_desc().to_wgpu().ty;
+            entries.push(wgpu::BindGroupEntry {
+                binding: declared.binding,
+                resource: self.check_bind_resource(name, &expected, resource)?,
+            });
+        }
+        if let Some(missing) = reflection.group(group).find(|b| !bindings.iter().any(|(n, _)| *n == b.name)) {
+            anyhow::bail!("`{}` at group({}) binding({}) is missing", missing.name, group, missing.binding);
+        }
+
+        let bg = self.capture_errors(|| self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(pipeline),
+            layout: &self.bindgroup_layouts[layout],
+            entries: &entries,
+        }))?;
+        self.bind_groups.push(bg);
+        Ok(self.bind_groups.len() - 1)
+    }
+
+    /// Resolve a `BindResource`, failing if it can't be bound where the shader expects `expected`.
+    fn check_bind_resource<'a>(&'a self, name: &str, expected: &wgpu::BindingType, resource: &BindResource<'a>) -> anyhow::Result<wgpu::BindingResource<'a>> {
+        let mismatch = |given: &str| anyhow::anyhow!("`{}` is a {} in the shader, but was given {}", name, crate::reflect::describe(expected), given);
+        match (resource, expected) {
+            (BindResource::Texture(i), wgpu::BindingType::Texture { sample_type, view_dimension, multisampled }) => {
+                let t = self.textures.get(*i).with_context(|| format!("`{}` was given texture {}, which doesn't exist", name, i))?;
+                let given = texture::sample_type(t.format);
+                if std::mem::discriminant(&given) != std::mem::discriminant(sample_type) || t.view_dimension != *view_dimension || *multisampled {
+                    return Err(mismatch(&format!("a {:?} texture of {:?}", t.view_dimension, given)));
+                }
+                Ok(wgpu::BindingResource::TextureView(&t.view))
+            }
+            (BindResource::Sampler(i), wgpu::BindingType::Sampler { comparison: false, .. }) => {
+                let t = self.textures.get(*i).with_context(|| format!("`{}` was given the sampler of texture {}, which doesn't exist", name, i))?;
+                Ok(wgpu::BindingResource::Sampler(&t.sampler))
+            }
+            (BindResource::Uniform(i), wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Uniform, min_binding_size, .. }) => {
+                let u = self.uniforms.slots.get(*i).with_context(|| format!("`{}` was given uniform {}, which doesn't exist", name, i))?;
+                if let Some(size) = min_binding_size {
+                    if u.size() < size.get() {
+                        anyhow::bail!("`{}` is {} bytes in the shader, but the uniform given is only {} bytes", name, size, u.size());
+                    }
+                }
+                Ok(u.buffer().as_entire_binding())
+            }
+            (BindResource::Storage(i), wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Storage { .. }, .. }) => {
+                let b = self.storage_buffers.get(*i).with_context(|| format!("`{}` was given storage buffer {}, which doesn't exist", name, i))?;
+                Ok(b.as_entire_binding())
+            }
+            (BindResource::Resource(r), _) => {
+                let fits = matches!(
+                    (r, expected),
+                    (wgpu::BindingResource::Buffer(_) | wgpu::BindingResource::BufferArray(_), wgpu::BindingType::Buffer { .. })
+                        | (wgpu::BindingResource::Sampler(_), wgpu::BindingType::Sampler { .. })
+                        | (wgpu::BindingResource::TextureView(_) | wgpu::BindingResource::TextureViewArray(_), wgpu::BindingType::Texture { .. } | wgpu::BindingType::StorageTexture { .. })
+                );
+                if !fits {
+                    return Err(mismatch("a different kind of resource"));
+                }
+                Ok(r.clone())
+            }
+            (BindResource::Texture(_), _) => Err(mismatch("a texture")),
+            (BindResource::Sampler(_), _) => Err(mismatch("a texture's sampler")),
+            (BindResource::Uniform(_), _) => Err(mismatch("a uniform buffer")),
+            (BindResource::Storage(_), _) => Err(mismatch("a storage buffer")),
+        }
+    }
+
     // TODO: make separate add functions for BindGroupLayouts, BindGroups, etc.
     /// Load a `wgpu::RenderPipeline` and `wgpu::BindGroupLayout` into `State` memory.
+    /// * `buildf` is called again for each other target the pipeline is drawn into, so it should build
+    ///   for `s.pass.target`, like `State::build_textured_pipeline`.
     pub fn add_pipeline(&mut self, name: &str, buildf: fn(&State) -> (wgpu::RenderPipeline, wgpu::BindGroupLayout)) {
 
         let (p,bgl) = buildf(&self);
 
+        let pid = self.push_render_pipeline(p, PipelineRecipe::Fn(buildf));
+
+        let bglid = self.bindgroup_layouts.len();
+        self.bindgroup_layouts.push(bgl);
+
+        self.pipeline_map.insert(String::from(name), Pipeline::new(pid, bglid));
+
+    }
+
+    /// Store a pipeline built for `pass.target`, and how to build it for other targets.
+    fn push_render_pipeline(&mut self, pipeline: wgpu::RenderPipeline, recipe: PipelineRecipe) -> usize {
         let pid = self.render_pipelines.len();
-        self.render_pipelines.push(p);
+        self.render_pipelines.push(pipeline);
+        self.pipeline_variants.entry(pid).or_default().insert(self.pass.target.clone(), pid);
+        self.pipeline_recipes.insert(pid, recipe);
+        self.prepared_targets.clear();
+        pid
+    }
+
+    /// The version of a pipeline from `render_pipelines` built for the target being drawn into.
+    /// * `None` if it couldn't be built for the target, since another target's version fails validation in this pass.
+    pub fn render_pipeline(&self, pipeline: usize) -> Option<&wgpu::RenderPipeline> {
+        let variant = self.pipeline_variants.get(&pipeline)?.get(&self.pass.target)?;
+        self.render_pipelines.get(*variant)
+    }
+
+    /// Build every pipeline for `pass.target` that isn't built for it yet, called before each `TextureRenderer`'s pass.
+    /// * Includes the text, shape and sprite pipelines.
+    pub fn prepare_pipelines(&mut self) {
+        self.text.prepare_target(&self.device, &self.pass.target);
+        self.shapes.prepare_target(&self.device, &self.pass.target);
+        self.sprites.prepare_target(&self.device, &self.pass.target);
+        if !self.prepared_targets.insert(self.pass.target.clone()) {
+            return;
+        }
+        let target = self.pass.target.clone();
+        let mut missing: Vec<usize> = self.pipeline_recipes.keys()
+            .filter(|base| !self.pipeline_variants.get(base).is_some_and(|v| v.contains_key(&target)))
+            .copied()
+            .collect();
+        missing.sort_unstable();
+        for base in missing {
+            match self.build_recipe(base) {
+                Ok(p) => {
+                    self.render_pipelines.push(p);
+                    let id = self.render_pipelines.len() - 1;
+                    self.pipeline_variants.entry(base).or_default().insert(target.clone(), id);
+                }
+                Err(e) => log::error!("Could not build render pipeline {} for {:?}:\n{}", base, target, e),
+            }
+        }
+    }
+
+    /// Build a pipeline's recipe for `pass.target`.
+    fn build_recipe(&self, base: usize) -> anyhow::Result<wgpu::RenderPipeline> {
+        match self.pipeline_recipes.get(&base) {
+            Some(PipelineRecipe::Fn(buildf)) => self.capture_errors(|| buildf(self).0),
+            Some(PipelineRecipe::Shader { source, build }) => self.build_from_source(&format!("pipeline {}", base), source, *build).map(|(p, _)| p),
+            Some(PipelineRecipe::Desc { desc, shader }) => self.compile_desc(desc, shader).map(|(p, _, _)| p),
+            None => anyhow::bail!("render pipeline {} has no recipe to build it from", base),
+        }
+    }
+
+    /// The target a pipeline's own slot in `render_pipelines` was built for.
+    fn base_target(&self, base: usize) -> PipelineTarget {
+        self.pipeline_variants.get(&base)
+            .and_then(|v| v.iter().find(|(_, id)| **id == base))
+            .map(|(target, _)| target.clone())
+            .unwrap_or_else(|| self.pass.target.clone())
+    }
+
+    /// Rebuild the other-target versions of a pipeline in place, after it's recipe changed.
+    fn rebuild_variants(&mut self, base: usize) {
+        let variants: Vec<(PipelineTarget, usize)> = match self.pipeline_variants.get(&base) {
+            Some(v) => v.iter().filter(|(_, id)| **id != base).map(|(t, id)| (t.clone(), *id)).collect(),
+            None => return,
+        };
+        for (target, id) in variants {
+            let previous = std::mem::replace(&mut self.pass.target, target);
+            match self.build_recipe(base) {
+                Ok(p) => self.render_pipelines[id] = p,
+                Err(e) => log::error!("Could not rebuild render pipeline {} for {:?}:\n{}", base, self.pass.target, e),
+            }
+            self.pass.target = previous;
+        }
+    }
+
+    /// Load a render pipeline whose shader is read from a file, and rebuild it whenever the file, or a file it includes, changes.
+    /// * The shader is run through `preprocessor` first.
+    /// * Fails if the file can't be read or the shader doesn't compile; later compile errors are only logged.
+    pub fn add_pipeline_from_file(&mut self, name: &str, path: impl AsRef<Path>, build: ShaderPipelineFn) -> anyhow::Result<()> {
+        let ProcessedShader { source, files, .. } = self.read_shader(path.as_ref())?;
+        let (p, bgl) = self.build_from_source(name, &source, build)?;
+        let reflection = reflect_source(&source);
+
+        let pid = self.push_render_pipeline(p, PipelineRecipe::Shader { source, build });
 
         let bglid = self.bindgroup_layouts.len();
         self.bindgroup_layouts.push(bgl);
 
-        self.pipeline_map.insert(String::from(name), Pipeline{
+        let mut pipeline = Pipeline::new(pid, bglid);
+        pipeline.reflection = reflection;
+        self.pipeline_map.insert(String::from(name), pipeline);
+        self.shader_watcher.watch(name, path.as_ref().to_path_buf(), build);
+        self.shader_watcher.set_includes(name, &files);
+        Ok(())
+    }
+
+    /// Rebuild an existing pipeline from a shader file whenever it changes, e.g.
+    /// `state.watch_shader("default:pipe:textured", "src/shader.wgsl", State::build_textured_pipeline)`.
+    pub fn watch_shader(&mut self, name: &str, path: impl AsRef<Path>, build: ShaderPipelineFn) {
+        self.shader_watcher.watch(name, path.as_ref().to_path_buf(), build);
+        if let Ok(shader) = self.preprocessor.process_file(path) {
+            self.shader_watcher.set_includes(name, &shader.files);
+        }
+    }
+
+    /// Check watched shaders if `shader_watcher.interval` has passed, called before every frame.
+    pub fn poll_shaders(&mut self) {
+        if self.shader_watcher.due() {
+            self.reload_shaders();
+        }
+    }
+
+    /// Rebuild the pipelines of every changed shader file now, and return how many were replaced.
+    /// * Pipelines are swapped in place, so `RenderObject`s keep using them. A pipeline that fails
+    ///   to compile is logged and keeps it's previous version.
+    pub fn reload_shaders(&mut self) -> usize {
+        let mut reloaded = 0;
+        for i in self.shader_watcher.changed() {
+            let watched = &self.shader_watcher.shaders[i];
+            let (name, path, build) = (watched.pipeline.clone(), watched.path.clone(), watched.build);
+            let pipeline = match self.pipeline_map.get(&name) {
+                Some(p) => p.clone(),
+                None => {
+                    log::warn!("Shader {} is watched for pipeline \"{}\", which doesn't exist", path.display(), name);
+                    continue;
+                }
+            };
+            // the base slot has to be built for the target it was first built for, not whichever was drawn into last
+            let base_target = self.base_target(pipeline.pipeline);
+            let previous = std::mem::replace(&mut self.pass.target, base_target);
+            let built = self.read_shader(&path)
+                .and_then(|shader| self.build_from_source(&name, &shader.source, build).map(|built| (shader, built)));
+            self.pass.target = previous;
+            match built {
+                Ok((ProcessedShader { source, files, .. }, (p, bgl))) => {
+                    self.shader_watcher.set_includes(&name, &files);
+                    self.render_pipelines[pipeline.pipeline] = p;
+                    self.pipeline_map.get_mut(&name).unwrap().reflection = reflect_source(&source);
+                    // an identical layout is compatible with bind groups made from the old one
+                    self.bindgroup_layouts[pipeline.bindgrouplayout] = bgl;
+                    self.pipeline_recipes.insert(pipeline.pipeline, PipelineRecipe::Shader { source, build });
+                    self.rebuild_variants(pipeline.pipeline);
+                    log::info!("Reloaded pipeline \"{}\" from {}", name, path.display());
+                    reloaded += 1;
+                }
+                Err(e) => log::error!("Could not reload pipeline \"{}\" from {}:\n{}", name, path.display(), e),
+            }
+        }
+        reloaded
+    }
+
+    /// Preprocess a shader file and validate it, so errors point to the original files and lines.
+    fn read_shader(&self, path: &Path) -> anyhow::Result<ProcessedShader> {
+        let shader = self.preprocessor.process_file(path)?;
+        shader.parse()?;
+        Ok(shader)
+    }
+
+    /// Validate WGSL and build a pipeline from it, returning wgpu validation errors instead of panicking.
+    fn build_from_source(&self, name: &str, source: &str, build: ShaderPipelineFn) -> anyhow::Result<(wgpu::RenderPipeline, wgpu::BindGroupLayout)> {
+        hotreload::validate_wgsl(source)?;
+
+        self.capture_errors(|| {
+            let shader = self.device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+                label: Some(name),
+                source: wgpu::ShaderSource::Wgsl(source.into()),
+            });
+            build(self, &shader)
+        })
+    }
+
+    /// Run `f`, returning the wgpu validation errors it raised instead of panicking.
+    fn capture_errors<T>(&self, f: impl FnOnce() -> T) -> anyhow::Result<T> {
+        let errors = Arc::new(Mutex::new(Vec::<String>::new()));
+        let sink = errors.clone();
+        self.device.on_uncaptured_error(move |e| sink.lock().unwrap().push(e.to_string()));
+        let built = f();
+        // back to wgpu's default of treating errors as fatal
+        self.device.on_uncaptured_error(|e| {
+            log::error!("{}", e);
+            panic!("Handling wgpu errors as fatal by default");
+        });
+
+        let errors = errors.lock().unwrap();
+        if !errors.is_empty() {
+            anyhow::bail!(errors.join("\n"));
+        }
+        Ok(built)
+    }
+
+    /// Build a render pipeline from a description, or reuse the one built from an identical description,
+    /// and store it under `name`. Returns the index in `render_pipelines`.
+    /// * Bind group layouts are stored in `bindgroup_layouts`, one per `group(n)`, see `Pipeline::bindgrouplayouts`.
+    /// * It's built for `pass.target`, and again for each other target it's drawn into.
+    pub fn add_pipeline_desc(&mut self, name: &str, desc: &PipelineDesc) -> anyhow::Result<usize> {
+        let shader = self.process_desc_shader(desc)?;
+        // keyed on the expanded source, so an edited shader file or changed defines build a new pipeline
+        let key = PipelineDesc {
+            shader: ShaderSource::Wgsl(shader.source.clone()),
+            ..desc.clone()
+        };
+        let pipeline = match self.pipeline_cache.get(&key) {
+            Some(p) => p.clone(),
+            None => {
+                let p = self.build_pipeline_desc(desc, shader)?;
+                self.pipeline_cache.insert(key, p.clone());
+                p
+            }
+        };
+        let pid = pipeline.pipeline;
+        self.pipeline_map.insert(String::from(name), pipeline);
+        Ok(pid)
+    }
+
+    /// Load a `.ron` or `.json` pipeline description and add it with `add_pipeline_desc`.
+    pub fn load_pipeline(&mut self, name: &str, path: impl AsRef<Path>) -> anyhow::Result<usize> {
+        let desc = PipelineDesc::load(path)?;
+        self.add_pipeline_desc(name, &desc)
+    }
+
+    fn build_pipeline_desc(&mut self, desc: &PipelineDesc, shader: ProcessedShader) -> anyhow::Result<Pipeline> {
+        let (p, layouts, reflection) = self.compile_desc(desc, &shader)?;
+
+        let pid = self.push_render_pipeline(p, PipelineRecipe::Desc { desc: Box::new(desc.clone()), shader });
+        let first = self.bindgroup_layouts.len();
+        self.bindgroup_layouts.extend(layouts);
+        let bindgrouplayouts: Vec<usize> = (first..self.bindgroup_layouts.len()).collect();
+        Ok(Pipeline {
             pipeline: pid,
-            bindgrouplayout: bglid
+            // a pipeline without bind groups has an empty `group(0)`, so this is always valid
+            bindgrouplayout: match bindgrouplayouts.first() {
+                Some(l) => *l,
+                None => {
+                    self.bindgroup_layouts.push(self.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                        label: desc.label.as_deref(),
+                        entries: &[],
+                    }));
+                    self.bindgroup_layouts.len() - 1
+                }
+            },
+            bindgrouplayouts,
+            reflection: Some(Arc::new(reflection)),
+        })
+    }
+
+    /// A description's shader, expanded by `preprocessor`.
+    fn process_desc_shader(&self, desc: &PipelineDesc) -> anyhow::Result<ProcessedShader> {
+        match &desc.shader {
+            ShaderSource::File(path) => self.preprocessor.process_file(path),
+            ShaderSource::Wgsl(source) => self.preprocessor.process_str(desc.label.as_deref().unwrap_or("wgsl"), source, None),
+        }
+    }
+
+    /// Validate a description against it's expanded shader and build it for `pass.target`, with a layout for each of it's bind groups.
+    fn compile_desc(&self, desc: &PipelineDesc, shader: &ProcessedShader) -> anyhow::Result<(wgpu::RenderPipeline, Vec<wgpu::BindGroupLayout>, ShaderReflection)> {
+        let label = desc.label.as_deref();
+        if !desc.push_constants.is_empty() {
+            if !self.device.features().contains(wgpu::Features::PUSH_CONSTANTS) {
+                anyhow::bail!("{:?} uses push constants, which this adapter doesn't support", label);
+            }
+            let max = self.device.limits().max_push_constant_size;
+            if let Some(p) = desc.push_constants.iter().find(|p| p.end > max) {
+                anyhow::bail!("push constants {}..{} exceed the adapter's limit of {} bytes", p.start, p.end, max);
+            }
+        }
+
+        let (module, info) = shader.parse()?;
+        let reflection = ShaderReflection::from_module(&module, &info)?;
+        let source = &shader.source;
+        let vertex = reflection.check_entry_point(ShaderStage::Vertex, &desc.vertex_entry)?;
+        if let Some(entry) = &desc.fragment_entry {
+            reflection.check_entry_point(ShaderStage::Fragment, entry)?;
+        }
+
+        let mut bind_groups = desc.bind_groups.clone();
+        let mut vertex_buffers = desc.vertex_buffers.clone();
+        if desc.reflect {
+            bind_groups.extend(reflection.bind_groups().into_iter().skip(bind_groups.len()));
+            if vertex_buffers.is_empty() {
+                vertex_buffers.extend(reflection.vertex_buffer(&desc.vertex_entry));
+            }
+        }
+        if reflection.group_count() as usize > bind_groups.len() {
+            anyhow::bail!("the shader uses group({}), but the description only has {} bind groups", reflection.group_count() - 1, bind_groups.len());
+        }
+        for (g, group) in bind_groups.iter().enumerate() {
+            reflection.check_layout(g as u32, &group.layout_entries()).with_context(|| format!("bind group {} doesn't match the shader", g))?;
+        }
+        for input in &vertex.inputs {
+            if !vertex_buffers.iter().any(|b| b.attributes.iter().any(|a| a.location == input.location)) {
+                anyhow::bail!("vertex input `{}` at location({}) isn't in any vertex buffer", input.name, input.location);
+            }
+        }
+
+        let layouts: Vec<wgpu::BindGroupLayout> = bind_groups.iter().map(|group| {
+            self.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label,
+                entries: &group.layout_entries(),
+            })
+        }).collect();
+        let attributes: Vec<Vec<wgpu::VertexAttribute>> = vertex_buffers.iter().map(|b| b.attributes()).collect();
+        let buffers: Vec<wgpu::VertexBufferLayout> = vertex_buffers.iter().zip(&attributes).map(|(b, attributes)| {
+            wgpu::VertexBufferLayout {
+                array_stride: b.array_stride(),
+                step_mode: b.step_mode(),
+                attributes,
+            }
+        }).collect();
+        let targets = self.pass.target.color_targets(desc.blend.to_wgpu());
+
+        let p = self.capture_errors(|| {
+            let shader = self.device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+                label,
+                source: wgpu::ShaderSource::Wgsl(source.as_str().into()),
+            });
+            let layout = self.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label,
+                bind_group_layouts: &layouts.iter().collect::<Vec<_>>(),
+                push_constant_ranges: &desc.push_constant_ranges(),
+            });
+            self.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label,
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: &desc.vertex_entry,
+                    buffers: &buffers,
+                },
+                fragment: desc.fragment_entry.as_ref().map(|entry_point| wgpu::FragmentState {
+                    module: &shader,
+                    entry_point,
+                    targets: &targets,
+                }),
+                primitive: desc.primitive_state(),
+                depth_stencil: desc.depth_stencil_state(&self.pass.target),
+                multisample: self.pass.target.multisample_state(),
+            })
+        })?;
+        Ok((p, layouts, reflection))
+    }
+
+    /// Load a `wgpu::ComputePipeline` and the `wgpu::BindGroupLayout` of it's `group(0)` into `State` memory.
+    pub fn add_compute_pipeline(&mut self, name: &str, buildf: fn(&State) -> (wgpu::ComputePipeline, wgpu::BindGroupLayout)) {
+        let (p, bgl) = buildf(self);
+
+        let pid = self.compute_pipelines.len();
+        self.compute_pipelines.push(p);
+
+        let bglid = self.bindgroup_layouts.len();
+        self.bindgroup_layouts.push(bgl);
+
+        self.compute_pipeline_map.insert(String::from(name), Pipeline::new(pid, bglid));
+    }
+
+    pub fn get_compute_pipeline(&self, name: &str) -> &Pipeline {
+        self.compute_pipeline_map.get(name).unwrap()
+    }
+
+    /// Create a bind group for a compute pipeline's layout, like `create_bindgroup`.
+    pub fn create_compute_bindgroup(&mut self, pipeline: &str, buildf: fn(&State) -> Vec<wgpu::BindGroupEntry>) -> usize {
+        let entries = buildf(self);
+        let bg = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &self.bindgroup_layouts[self.get_compute_pipeline(pipeline).bindgrouplayout],
+            entries: entries.as_slice()
         });
+        self.bind_groups.push(bg);
+        self.bind_groups.len() - 1
+    }
 
+    /// Create a zeroed storage buffer and return it's index in `storage_buffers`.
+    /// * `usage` is added to `STORAGE | COPY_DST | COPY_SRC`, e.g. `VERTEX` to draw particles straight from it.
+    pub fn create_storage_buffer(&mut self, size: wgpu::BufferAddress, usage: wgpu::BufferUsages) -> usize {
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("panels:storage"),
+            size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC | usage,
+            mapped_at_creation: false,
+        });
+        self.storage_buffers.push(buffer);
+        self.storage_buffers.len() - 1
     }
 
-    pub fn create_renderobj(&self, pipeline: &str, model: usize, bind_group: usize) -> RenderObject {
-        let p = self.pipeline_map.get(&pipeline.to_string()).unwrap();
-        let m = &self.models[model];
+    /// Create a storage buffer holding `contents`, see `create_storage_buffer`.
+    pub fn create_storage_buffer_init(&mut self, contents: &[u8], usage: wgpu::BufferUsages) -> usize {
+        let buffer = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("panels:storage"),
+            contents,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC | usage,
+        });
+        self.storage_buffers.push(buffer);
+        self.storage_buffers.len() - 1
+    }
 
-        RenderObject{
-            position: WorldPoint::new(0.0,0.0,0.0),
-            pipeline: p.pipeline,
-            bind_group,
-            model,
-            uniforms: vec![]
+    /// Record a compute pass that runs a pipeline from `compute_pipelines` over `workgroups`,
+    /// with `bind_groups` (indices into `self.bind_groups`) set from `group(0)` on.
+    /// * Call from `ProgramHook::compute`.
+    pub fn dispatch(&self, encoder: &mut wgpu::CommandEncoder, pipeline: usize, bind_groups: &[usize], workgroups: [u32; 3]) {
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: None });
+        compute_pass.set_pipeline(&self.compute_pipelines[pipeline]);
+        for (i, bind_group) in bind_groups.iter().enumerate() {
+            compute_pass.set_bind_group(i as u32, &self.bind_groups[*bind_group], &[]);
         }
+        compute_pass.dispatch(workgroups[0], workgroups[1], workgroups[2]);
+    }
 
+    /// Create a `RenderObject` with its own transform slot.
+    pub fn create_renderobj(&mut self, pipeline: &str, model: usize, bind_group: usize) -> RenderObject {
+        let p = self.pipeline_map.get(&pipeline.to_string()).unwrap().pipeline;
+        let mut obj = RenderObject::new(self);
+        obj.pipeline = p;
+        obj.bind_group = bind_group;
+        obj.model = model;
+        obj
     }
 
     /// Load a `Texture` into `State` memory.
@@ -390,6 +976,47 @@ impl State {
         o
     }
 
+    /// Decode an image, HDR or compressed file into a `Texture` with the given options, and return it's index.
+    pub fn load_texture(&mut self, bytes: &[u8], options: &TextureOptions) -> anyhow::Result<usize> {
+        let tex = Texture::from_bytes_with(&self.device, &self.queue, bytes, options, &mut self.mipmaps, &mut self.samplers)?;
+        Ok(self.add_texture(tex))
+    }
+
+    /// The shared sampler for these options, e.g. `SamplerOptions::nearest()` for pixel art
+    /// or `SamplerOptions::repeat()` for tiling backgrounds.
+    pub fn sampler(&mut self, options: &SamplerOptions) -> Arc<wgpu::Sampler> {
+        self.samplers.get(&self.device, options)
+    }
+
+    /// Change how a texture is sampled.
+    /// * Bind groups already made from the texture keep the old sampler; make a new one with `State::texture_bind_group`.
+    pub fn set_texture_sampler(&mut self, texture: usize, options: &SamplerOptions) {
+        self.textures[texture].sampler = self.samplers.get(&self.device, options);
+    }
+
+    /// Create a bind group for `default:pipe:textured` showing a texture with it's own sampler, and return it's index.
+    pub fn texture_bind_group(&mut self, texture: usize) -> usize {
+        let layout = self.get_pipeline("default:pipe:textured").bindgrouplayout;
+        self.texture_bind_group_for_layout(texture, layout)
+    }
+
+    /// Store a bind group layout matching a texture's kind (array, cube, 3D, float...), and return it's index,
+    /// for use in a pipeline that samples such textures.
+    pub fn texture_layout(&mut self, texture: usize) -> usize {
+        let layout = self.textures[texture].create_bind_group_layout(&self.device);
+        self.bindgroup_layouts.push(layout);
+        self.bindgroup_layouts.len() - 1
+    }
+
+    /// Create a bind group showing a texture with it's own sampler, using a layout from `State::bindgroup_layouts`.
+    pub fn texture_bind_group_for_layout(&mut self, texture: usize, layout: usize) -> usize {
+        let bind_group = self.textures[texture].create_bind_group(&self.device, &self.bindgroup_layouts[layout]);
+        self.bind_groups.push(bind_group);
+        let id = self.bind_groups.len() - 1;
+        self.texture_bind_groups.entry(texture).or_default().push((id, layout));
+        id
+    }
+
     // pub fn add_pipeline(&mut self, name: &str, desc: &wgpu::RenderPipelineDescriptor){
     //     let o = self.render_pipelines.len();
     //     self.render_pipelines.push(&self.device.create_render_pipeline(desc));
@@ -408,6 +1035,87 @@ impl State {
     //     self.bglayout_map.insert(name, o);
     // }
 
+    /// Store a `Camera` and return it's index, for use with `TextureRenderer::set_camera`.
+    pub fn add_camera(&mut self, camera: Camera) -> usize {
+        let id = self.cameras.len();
+        self.cameras.push(CameraBinding::new(&self.device, &self.camera_bind_group_layout, camera));
+        id
+    }
+
+    pub fn camera(&self, id: usize) -> &Camera {
+        &self.cameras[id].camera
+    }
+
+    /// Changes made here are uploaded before the next frame is drawn.
+    pub fn camera_mut(&mut self, id: usize) -> &mut Camera {
+        &mut self.cameras[id].camera
+    }
+
+    /// Write every camera's matrix to it's uniform buffer.
+    pub fn upload_cameras(&self) {
+        for c in &self.cameras {
+            c.upload(&self.queue);
+        }
+    }
+
+    /// Store a uniform value and return it's handle. It's bind group uses `self.uniforms.layout`.
+    /// * Fails if `T` isn't a valid WGSL uniform size; see `uniform::check_uniform_fields` to check it's members too.
+    pub fn add_uniform<T: bytemuck::Pod>(&mut self, value: T) -> anyhow::Result<UniformId<T>> {
+        self.uniforms.add(&self.device, value)
+    }
+
+    pub fn uniform<T: bytemuck::Pod>(&self, id: UniformId<T>) -> &Uniform<T> {
+        self.uniforms.get(id)
+    }
+
+    /// Changes made here are uploaded before the next frame is drawn.
+    pub fn uniform_mut<T: bytemuck::Pod>(&mut self, id: UniformId<T>) -> &mut Uniform<T> {
+        self.uniforms.get_mut(id)
+    }
+
+    /// Write every changed uniform to it's buffer.
+    pub fn upload_uniforms(&mut self) {
+        self.uniforms.upload(&self.queue);
+    }
+
+    /// Reset the per-frame geometry buffers, called before any `ProgramHook::render`.
+    pub fn begin_frame(&mut self) {
+        self.text.begin_frame();
+        self.shapes.begin_frame();
+        self.sprites.begin_frame();
+    }
+
+    /// Load a TrueType/OpenType font and return it's index, for use in a `TextSection`.
+    pub fn load_font(&mut self, bytes: Vec<u8>) -> anyhow::Result<usize> {
+        let font = Font::from_bytes(bytes)?;
+        Ok(self.text.add_font(font))
+    }
+
+    /// Lay out and upload text for the current frame, sized for the target in `self.pass`.
+    /// * Call from `ProgramHook::render` and draw the result with `TextBatch::draw`.
+    /// * The pass's camera must use `Projection::Ndc`, see `TextSystem::prepare`.
+    pub fn prepare_text(&mut self, sections: &[TextSection]) -> TextBatch {
+        self.text.prepare(&self.device, &self.queue, self.pass.target_size, sections)
+    }
+
+    /// A `ShapeBuilder` sized for the target in `self.pass`.
+    /// * The pass's camera must use `Projection::Ndc`, see `ShapeBuilder::for_target`.
+    pub fn shape_builder(&self) -> ShapeBuilder {
+        ShapeBuilder::for_target(self.pass.target_size)
+    }
+
+    /// Upload the shapes for the current frame.
+    /// * Call from `ProgramHook::render` and draw the result with `ShapeBatch::draw`.
+    pub fn prepare_shapes(&mut self, shapes: &ShapeBuilder) -> ShapeBatch {
+        self.shapes.prepare(&self.device, &self.queue, shapes)
+    }
+
+    /// Upload the sprites for the current frame.
+    /// * Call from `ProgramHook::render` and draw the result with `SpriteBatch::draw`.
+    pub fn prepare_sprites(&mut self, sprites: &SpriteBuilder) -> SpriteBatch {
+        self.sprites.prepare(&self.device, &self.queue, sprites)
+    }
+
     /// Get the Surface framerate.
     pub fn get_fps(&self) -> Option<f64> {
         self.loop_fps
@@ -425,6 +1133,63 @@ impl State {
             self.config.width = new_size.width;
             self.config.height = new_size.height;
             self.surface.configure(&self.device, &self.config);
+
+            for c in &mut self.cameras {
+                if c.camera.fit_surface {
+                    c.camera.set_viewport(new_size.width, new_size.height);
+                }
+            }
+        }
+    }
+
+    /// Called on `WindowEvent::ScaleFactorChanged`, the window's physical size changes along with the scale factor.
+    pub fn set_scale_factor(&mut self, scale_factor: f64, new_size: winit::dpi::PhysicalSize<u32>) {
+        self.scale_factor = scale_factor;
+        self.resize(new_size);
+    }
+
+    /// The window size in logical pixels.
+    pub fn logical_size(&self) -> winit::dpi::LogicalSize<f64> {
+        self.size.to_logical(self.scale_factor)
+    }
+
+    /// The whole window as a `ScreenRectangle`.
+    pub fn screen_rect(&self) -> ScreenRectangle {
+        ScreenRectangle::from_size(self.size.width, self.size.height)
+    }
+
+    /// The whole window as a `LogicalRectangle`.
+    pub fn logical_rect(&self) -> LogicalRectangle {
+        LogicalRectangle::from_screen_rect(&self.screen_rect(), self.scale_factor)
+    }
+
+    /// Converts a rectangle given in logical pixels into world units for the current window size and scale factor.
+    pub fn logical_to_world(&self, rect: &LogicalRectangle) -> WorldRectangle {
+        rect.to_world_rect(&self.size, self.scale_factor)
+    }
+
+    /// Maps a mouse position into world units.
+    pub fn mouse_to_world(&self, mouse: &winit::dpi::PhysicalPosition<f64>) -> WorldPoint {
+        WorldPoint::from_mouse(&self.size, mouse)
+    }
+
+    /// Creates a texture for a `TextureRenderer` to draw into, sized in logical pixels, and returns it's index.
+    /// * Use `TextureRenderer::set_logical_size` with the same size, so the texture follows scale factor changes.
+    pub fn create_render_target(&mut self, size: winit::dpi::LogicalSize<f64>) -> usize {
+        let physical = size.to_physical::<u32>(self.scale_factor);
+        let sampler = self.sampler(&SamplerOptions::linear());
+        let tex = Texture::new_render_target(&self.device, physical.width, physical.height, self.config.format, sampler);
+        self.add_texture(tex)
+    }
+
+    /// Recreates a render target texture at a new physical size.
+    /// * Bind groups made with `texture_bind_group` or `texture_bind_group_for_layout` are rebuilt in place,
+    ///   so their indices keep working. Other bind groups made from the old view must be recreated.
+    pub fn resize_render_target(&mut self, index: usize, size: winit::dpi::PhysicalSize<u32>) {
+        let sampler = self.textures[index].sampler.clone();
+        self.textures[index] = Texture::new_render_target(&self.device, size.width, size.height, self.config.format, sampler);
+        for (bind_group, layout) in self.texture_bind_groups.get(&index).into_iter().flatten() {
+            self.bind_groups[*bind_group] = self.textures[index].create_bind_group(&self.device, &self.bindgroup_layouts[*layout]);
         }
     }
 
@@ -524,3 +1289,16 @@ impl State {
     //     Ok(())
     // }
 }
+
+/// Reflect a shader whose pipeline was built without a description. Shaders reflection can't describe yet are only logged.
+fn reflect_source(source: &str) -> Option<Arc<ShaderReflection>> {
+    ShaderReflection::from_wgsl(source)
+        .map_err(|e| log::warn!("Could not reflect shader: {}", e))
+        .ok()
+        .map(Arc::new)
+}
+
+/// `shader.wgsl`, expanded by a `Preprocessor` without defines so it's the same however `State::preprocessor` is set up.
+fn default_shader() -> ProcessedShader {
+    Preprocessor::new().process_str("shader.wgsl", include_str!("shader.wgsl"), None).expect("the default shader expands")
+}
@@ -0,0 +1,1177 @@
+use std::collections::{HashMap, VecDeque};
+use anyhow::*;
+use winit::dpi::PhysicalSize;
+use winit::event::{ElementState, KeyboardInput, ModifiersState, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent};
+use crate::appmgmt::EventLoopAction;
+use crate::rect::{ScreenPoint, ScreenRectangle, WorldPoint, WorldRectangle};
+use crate::shapes::ShapeBuilder;
+use crate::text::{Font, HorizontalAlign, TextSection};
+use crate::texturerenderer::TextureRenderer;
+use crate::wgpustate::State;
+
+/// The index of a `Widget` in it's `WidgetTree`.
+pub type WidgetId = usize;
+
+/// The size of a widget along one axis, in logical pixels.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Length {
+    /// The widget's natural size, from it's content.
+    Auto,
+    Px(f32),
+    /// Shares the space left in the parent by weight.
+    Fill(f32),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Direction {
+    Row,
+    Column,
+    /// Children are placed on top of each other.
+    Stack,
+}
+
+/// Placement of children across the layout direction.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Align {
+    Start,
+    Center,
+    End,
+    Stretch,
+}
+
+/// Placement of children along the layout direction, when none of them `Fill` it.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Justify {
+    Start,
+    Center,
+    End,
+    SpaceBetween,
+}
+
+/// How a container arranges it's children. Sizes are in logical pixels.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FlexLayout {
+    pub direction: Direction,
+    pub gap: f32,
+    pub padding: f32,
+    pub align: Align,
+    pub justify: Justify,
+    pub background: Option<[f32; 4]>,
+}
+
+impl FlexLayout {
+    pub fn new(direction: Direction) -> Self {
+        Self {
+            direction,
+            gap: 8.0,
+            padding: 8.0,
+            align: Align::Stretch,
+            justify: Justify::Start,
+            background: None,
+        }
+    }
+
+    pub fn row() -> Self {
+        Self::new(Direction::Row)
+    }
+
+    pub fn column() -> Self {
+        Self::new(Direction::Column)
+    }
+
+    pub fn stack() -> Self {
+        Self::new(Direction::Stack)
+    }
+}
+
+/// An editable line of text. `caret` and `anchor` are character indices; the selection lies between them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextFieldState {
+    pub text: String,
+    pub caret: usize,
+    pub anchor: usize,
+    pub placeholder: String,
+}
+
+impl TextFieldState {
+    fn byte_index(&self, char_index: usize) -> usize {
+        self.text.char_indices().nth(char_index).map(|(i, _)| i).unwrap_or(self.text.len())
+    }
+
+    fn char_count(&self) -> usize {
+        self.text.chars().count()
+    }
+
+    /// The selected characters, empty when nothing is selected.
+    pub fn selection(&self) -> std::ops::Range<usize> {
+        self.caret.min(self.anchor)..self.caret.max(self.anchor)
+    }
+
+    pub fn selected_text(&self) -> &str {
+        let r = self.selection();
+        &self.text[self.byte_index(r.start)..self.byte_index(r.end)]
+    }
+
+    /// Replaces the selection with `s`, leaving the caret after it.
+    pub fn insert(&mut self, s: &str) {
+        let r = self.selection();
+        let (a, b) = (self.byte_index(r.start), self.byte_index(r.end));
+        self.text.replace_range(a..b, s);
+        self.caret = r.start + s.chars().count();
+        self.anchor = self.caret;
+    }
+
+    /// Deletes the selection, or the character before (`forward == false`) or after the caret.
+    pub fn delete(&mut self, forward: bool) {
+        if self.selection().is_empty() {
+            if forward && self.caret < self.char_count() {
+                self.anchor = self.caret + 1;
+            } else if !forward && self.caret > 0 {
+                self.anchor = self.caret - 1;
+            }
+        }
+        self.insert("");
+    }
+
+    /// Moves the caret, extending the selection when `select` is set.
+    pub fn move_caret(&mut self, to: usize, select: bool) {
+        self.caret = to.min(self.char_count());
+        if !select {
+            self.anchor = self.caret;
+        }
+    }
+}
+
+/// A scrollable list of strings with a single selection.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ListState {
+    pub items: Vec<String>,
+    pub selected: Option<usize>,
+    /// The first visible row.
+    pub scroll: usize,
+    /// The number of rows that fit, set by the layout.
+    pub visible_rows: usize,
+}
+
+impl ListState {
+    fn clamp_scroll(&mut self) {
+        self.scroll = self.scroll.min(self.items.len().saturating_sub(self.visible_rows));
+    }
+
+    /// Scroll so the selected row is visible.
+    fn reveal_selected(&mut self) {
+        if let Some(s) = self.selected {
+            if s < self.scroll {
+                self.scroll = s;
+            } else if s >= self.scroll + self.visible_rows.max(1) {
+                self.scroll = s + 1 - self.visible_rows.max(1);
+            }
+        }
+    }
+}
+
+pub enum WidgetKind {
+    Container { layout: FlexLayout, children: Vec<WidgetId> },
+    Label(String),
+    Button(String),
+    Checkbox { label: String, checked: bool },
+    /// `step` of `0.0` allows any value.
+    Slider { min: f32, max: f32, value: f32, step: f32 },
+    TextField(TextFieldState),
+    List(ListState),
+}
+
+/// A node of a `WidgetTree`.
+pub struct Widget {
+    pub kind: WidgetKind,
+    pub width: Length,
+    pub height: Length,
+    /// Disabled widgets are drawn dimmed and ignore input.
+    pub enabled: bool,
+    /// Set by the layout, in pixels of the render target.
+    pub rect: ScreenRectangle,
+}
+
+impl Widget {
+    pub fn new(kind: WidgetKind) -> Self {
+        Self {
+            kind,
+            width: Length::Auto,
+            height: Length::Auto,
+            enabled: true,
+            rect: ScreenRectangle::new(ScreenPoint::new(0.0, 0.0), 0.0, 0.0),
+        }
+    }
+
+    pub fn container(layout: FlexLayout) -> Self {
+        Self::new(WidgetKind::Container { layout, children: vec![] })
+    }
+
+    pub fn label(text: &str) -> Self {
+        Self::new(WidgetKind::Label(text.to_string()))
+    }
+
+    pub fn button(label: &str) -> Self {
+        Self::new(WidgetKind::Button(label.to_string()))
+    }
+
+    pub fn checkbox(label: &str, checked: bool) -> Self {
+        Self::new(WidgetKind::Checkbox { label: label.to_string(), checked })
+    }
+
+    pub fn slider(min: f32, max: f32, value: f32) -> Self {
+        Self::new(WidgetKind::Slider { min, max, value: value.clamp(min, max), step: 0.0 })
+    }
+
+    pub fn text_field(placeholder: &str) -> Self {
+        Self::new(WidgetKind::TextField(TextFieldState {
+            text: String::new(),
+            caret: 0,
+            anchor: 0,
+            placeholder: placeholder.to_string(),
+        }))
+    }
+
+    pub fn list(items: Vec<String>) -> Self {
+        Self::new(WidgetKind::List(ListState { items, selected: None, scroll: 0, visible_rows: 0 }))
+    }
+
+    pub fn with_size(mut self, width: Length, height: Length) -> Self {
+        self.width = width;
+        self.height = height;
+        self
+    }
+
+    fn focusable(&self) -> bool {
+        self.enabled && !matches!(self.kind, WidgetKind::Container { .. } | WidgetKind::Label(_))
+    }
+}
+
+/// Something the user did to a widget, turned into the conductor's `Message` by the tree.
+#[derive(Clone, Debug, PartialEq)]
+pub enum WidgetEvent {
+    Clicked(WidgetId),
+    Toggled(WidgetId, bool),
+    ValueChanged(WidgetId, f32),
+    TextChanged(WidgetId, String),
+    /// Return was pressed in a text field.
+    Submitted(WidgetId, String),
+    SelectionChanged(WidgetId, Option<usize>),
+}
+
+/// Colors and metrics used to draw widgets. Sizes are in logical pixels.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Theme {
+    /// The index of a font loaded with `State::load_font`.
+    pub font: usize,
+    pub font_size: f32,
+    pub padding: f32,
+    pub corner_radius: f32,
+    pub text: [f32; 4],
+    pub dim_text: [f32; 4],
+    pub surface: [f32; 4],
+    pub hover: [f32; 4],
+    pub pressed: [f32; 4],
+    pub border: [f32; 4],
+    pub accent: [f32; 4],
+}
+
+impl Theme {
+    pub fn dark(font: usize) -> Self {
+        Self {
+            font,
+            font_size: 16.0,
+            padding: 6.0,
+            corner_radius: 4.0,
+            text: [0.92, 0.92, 0.92, 1.0],
+            dim_text: [0.55, 0.55, 0.58, 1.0],
+            surface: [0.16, 0.16, 0.18, 1.0],
+            hover: [0.22, 0.22, 0.25, 1.0],
+            pressed: [0.11, 0.11, 0.13, 1.0],
+            border: [0.35, 0.35, 0.4, 1.0],
+            accent: [0.26, 0.52, 0.96, 1.0],
+        }
+    }
+}
+
+/// A retained tree of widgets, driven from a `ProgramHook`.
+/// * Call `input`, `update` and `render` from the `ProgramHook` functions of the same name.
+/// * `render` can't lay the tree out, since `ProgramHook::render` only has `&self`; `update` does it,
+///   so the renderer's update `Timing` has to run at least as often as it's render `Timing`.
+/// * Widget events become messages through the function given to `new`, returned as `EventLoopAction::MSG`.
+///   One message is returned per call; the rest are returned by following `input`/`update` calls.
+pub struct WidgetTree<M> {
+    pub widgets: Vec<Widget>,
+    pub widget_map: HashMap<String, WidgetId>,
+    pub root: WidgetId,
+    pub theme: Theme,
+    /// Where the renderer's texture is shown in the window, in physical pixels, to map the cursor into it.
+    /// `None` when the renderer draws to the surface.
+    pub viewport: Option<ScreenRectangle>,
+    to_message: Box<dyn Fn(WidgetEvent) -> M>,
+    pending: VecDeque<M>,
+    /// The cursor in pixels of the render target.
+    cursor: Option<ScreenPoint>,
+    hovered: Option<WidgetId>,
+    pressed: Option<WidgetId>,
+    focused: Option<WidgetId>,
+    modifiers: ModifiersState,
+    /// The target size and scale factor of the last layout.
+    laid_out: Option<(PhysicalSize<u32>, f32)>,
+}
+
+impl<M> WidgetTree<M> {
+    /// Creates a tree with a root container that fills the render target.
+    pub fn new(theme: Theme, root_layout: FlexLayout, to_message: impl Fn(WidgetEvent) -> M + 'static) -> Self {
+        Self {
+            widgets: vec![Widget::container(root_layout).with_size(Length::Fill(1.0), Length::Fill(1.0))],
+            widget_map: HashMap::new(),
+            root: 0,
+            theme,
+            viewport: None,
+            to_message: Box::new(to_message),
+            pending: VecDeque::new(),
+            cursor: None,
+            hovered: None,
+            pressed: None,
+            focused: None,
+            modifiers: ModifiersState::empty(),
+            laid_out: None,
+        }
+    }
+
+    /// Adds a widget to a container and returns it's id. An empty `name` isn't registered.
+    /// * Fails if `parent` doesn't exist or isn't a container.
+    pub fn add(&mut self, parent: WidgetId, name: &str, widget: Widget) -> Result<WidgetId> {
+        let id = self.widgets.len();
+        match self.widgets.get_mut(parent).map(|w| &mut w.kind) {
+            Some(WidgetKind::Container { children, .. }) => children.push(id),
+            Some(_) => bail!("Widget {} is not a container", parent),
+            None => bail!("Widget {} does not exist", parent),
+        }
+        self.widgets.push(widget);
+        if !name.is_empty() {
+            self.widget_map.insert(name.to_string(), id);
+        }
+        self.laid_out = None;
+        Ok(id)
+    }
+
+    pub fn id(&self, name: &str) -> Option<WidgetId> {
+        self.widget_map.get(name).copied()
+    }
+
+    pub fn widget(&self, id: WidgetId) -> &Widget {
+        &self.widgets[id]
+    }
+
+    /// The layout is redone after changes made here.
+    pub fn widget_mut(&mut self, id: WidgetId) -> &mut Widget {
+        self.laid_out = None;
+        &mut self.widgets[id]
+    }
+
+    pub fn focused(&self) -> Option<WidgetId> {
+        self.focused
+    }
+
+    fn emit(&mut self, event: WidgetEvent) {
+        self.pending.push_back((self.to_message)(event));
+    }
+
+    fn next_message(&mut self) -> EventLoopAction<M> {
+        match self.pending.pop_front() {
+            Some(m) => EventLoopAction::MSG(m),
+            None => EventLoopAction::None,
+        }
+    }
+
+    fn font<'s>(&self, state: &'s State) -> Option<&'s Font> {
+        state.text.fonts.get(self.theme.font)
+    }
+
+    // ---- layout ----
+
+    /// The size of a length along an axis, or `None` for `Fill`.
+    fn fixed(length: Length, natural: f32, scale: f32) -> Option<f32> {
+        match length {
+            Length::Auto => Some(natural),
+            Length::Px(px) => Some(px * scale),
+            Length::Fill(_) => None,
+        }
+    }
+
+    /// The natural size of a widget's content, in target pixels.
+    fn measure(&self, id: WidgetId, font: Option<&Font>, scale: f32) -> [f32; 2] {
+        let size = self.theme.font_size * scale;
+        let pad = self.theme.padding * scale;
+        let line = font.map(|f| f.line_height(size)).unwrap_or(size);
+        let text_width = |s: &str| font.map(|f| f.measure(size, s)).unwrap_or(0.0);
+
+        let natural = match &self.widgets[id].kind {
+            WidgetKind::Container { layout, children } => {
+                let main = match layout.direction {
+                    Direction::Row => 0,
+                    _ => 1,
+                };
+                let mut sum = [0.0f32; 2];
+                for c in children {
+                    let child = &self.widgets[*c];
+                    let natural = self.measure(*c, font, scale);
+                    let w = Self::fixed(child.width, natural[0], scale).unwrap_or(0.0);
+                    let h = Self::fixed(child.height, natural[1], scale).unwrap_or(0.0);
+                    let s = [w, h];
+                    if layout.direction == Direction::Stack {
+                        sum = [sum[0].max(s[0]), sum[1].max(s[1])];
+                    } else {
+                        sum[main] += s[main];
+                        sum[1 - main] = sum[1 - main].max(s[1 - main]);
+                    }
+                }
+                if layout.direction != Direction::Stack && children.len() > 1 {
+                    sum[main] += layout.gap * scale * (children.len() - 1) as f32;
+                }
+                let p = layout.padding * scale * 2.0;
+                [sum[0] + p, sum[1] + p]
+            }
+            WidgetKind::Label(text) => [text_width(text), line],
+            WidgetKind::Button(label) => [text_width(label) + pad * 4.0, line + pad * 2.0],
+            WidgetKind::Checkbox { label, .. } => [line + pad + text_width(label), line],
+            WidgetKind::Slider { .. } => [160.0 * scale, line],
+            WidgetKind::TextField(_) => [200.0 * scale, line + pad * 2.0],
+            WidgetKind::List(_) => [200.0 * scale, (line + pad) * 6.0],
+        };
+
+        let w = &self.widgets[id];
+        [
+            Self::fixed(w.width, natural[0], scale).unwrap_or(natural[0]),
+            Self::fixed(w.height, natural[1], scale).unwrap_or(natural[1]),
+        ]
+    }
+
+    fn arrange(&mut self, id: WidgetId, rect: ScreenRectangle, font: Option<&Font>, scale: f32) {
+        self.widgets[id].rect = rect;
+
+        let row_height = font.map(|f| f.line_height(self.theme.font_size * scale)).unwrap_or(self.theme.font_size * scale) + self.theme.padding * scale;
+        if let WidgetKind::List(list) = &mut self.widgets[id].kind {
+            list.visible_rows = (rect.height / row_height).floor().max(0.0) as usize;
+            list.clamp_scroll();
+        }
+
+        let (layout, children) = match &self.widgets[id].kind {
+            WidgetKind::Container { layout, children } => (layout.clone(), children.clone()),
+            _ => return,
+        };
+
+        let pad = layout.padding * scale;
+        let inner = ScreenRectangle::new(
+            ScreenPoint::new(rect.pos.x + pad, rect.pos.y + pad),
+            (rect.width - pad * 2.0).max(0.0),
+            (rect.height - pad * 2.0).max(0.0),
+        );
+        let origin = [inner.pos.x, inner.pos.y];
+        let avail = [inner.width, inner.height];
+
+        // size along each axis, `None` for `Fill`, and the fill weights
+        let sizes: Vec<([Option<f32>; 2], [f32; 2])> = children.iter().map(|c| {
+            let natural = self.measure(*c, font, scale);
+            let w = &self.widgets[*c];
+            let weight = |l: Length| if let Length::Fill(f) = l { f } else { 0.0 };
+            (
+                [Self::fixed(w.width, natural[0], scale), Self::fixed(w.height, natural[1], scale)],
+                [weight(w.width), weight(w.height)],
+            )
+        }).collect();
+
+        let place_cross = |size: Option<f32>, start: f32, avail: f32| -> (f32, f32) {
+            match (size, layout.align) {
+                (None, _) | (_, Align::Stretch) => (start, avail),
+                (Some(s), Align::Start) => (start, s),
+                (Some(s), Align::Center) => (start + (avail - s) / 2.0, s),
+                (Some(s), Align::End) => (start + avail - s, s),
+            }
+        };
+
+        if layout.direction == Direction::Stack {
+            for (i, c) in children.iter().enumerate() {
+                let (x, w) = place_cross(sizes[i].0[0], origin[0], avail[0]);
+                let (y, h) = place_cross(sizes[i].0[1], origin[1], avail[1]);
+                self.arrange(*c, ScreenRectangle::new(ScreenPoint::new(x, y), w, h), font, scale);
+            }
+            return;
+        }
+
+        let main = if layout.direction == Direction::Row { 0 } else { 1 };
+        let cross = 1 - main;
+        let n = children.len();
+        let gap = layout.gap * scale;
+        let gaps = gap * n.saturating_sub(1) as f32;
+        let fixed_total: f32 = sizes.iter().filter_map(|s| s.0[main]).sum();
+        let weight_total: f32 = sizes.iter().filter(|s| s.0[main].is_none()).map(|s| s.1[main]).sum();
+        let leftover = (avail[main] - gaps - fixed_total).max(0.0);
+
+        let (mut pen, extra_gap) = if weight_total > 0.0 {
+            (0.0, 0.0)
+        } else {
+            match layout.justify {
+                Justify::Start => (0.0, 0.0),
+                Justify::Center => (leftover / 2.0, 0.0),
+                Justify::End => (leftover, 0.0),
+                Justify::SpaceBetween if n > 1 => (0.0, leftover / (n - 1) as f32),
+                Justify::SpaceBetween => (0.0, 0.0),
+            }
+        };
+
+        for (i, c) in children.iter().enumerate() {
+            let main_size = sizes[i].0[main].unwrap_or_else(|| {
+                if weight_total > 0.0 { leftover * sizes[i].1[main] / weight_total } else { 0.0 }
+            });
+            let (cross_pos, cross_size) = place_cross(sizes[i].0[cross], origin[cross], avail[cross]);
+            let mut pos = [0.0; 2];
+            let mut size = [0.0; 2];
+            pos[main] = origin[main] + pen;
+            size[main] = main_size;
+            pos[cross] = cross_pos;
+            size[cross] = cross_size;
+            self.arrange(*c, ScreenRectangle::new(ScreenPoint::new(pos[0], pos[1]), size[0], size[1]), font, scale);
+            pen += main_size + gap + extra_gap;
+        }
+    }
+
+    /// Positions every widget to fill a target of this size.
+    pub fn layout(&mut self, state: &State, target_size: PhysicalSize<u32>) {
+        let scale = state.scale_factor as f32;
+        let font = self.font(state);
+        let rect = ScreenRectangle::from_size(target_size.width, target_size.height);
+        self.arrange(self.root, rect, font, scale);
+        self.laid_out = Some((target_size, scale));
+    }
+
+    fn layout_if_needed(&mut self, renderer: &TextureRenderer, state: &State) {
+        let size = renderer.target_size(state);
+        if self.laid_out != Some((size, state.scale_factor as f32)) {
+            self.layout(state, size);
+        }
+    }
+
+    // ---- input ----
+
+    /// The deepest interactive widget under the point.
+    fn hit(&self, id: WidgetId, p: &ScreenPoint) -> Option<WidgetId> {
+        let w = &self.widgets[id];
+        if !w.rect.contains(p) {
+            return None;
+        }
+        match &w.kind {
+            // later children are drawn on top
+            WidgetKind::Container { children, .. } => children.iter().rev().find_map(|c| self.hit(*c, p)),
+            _ => Some(id),
+        }
+    }
+
+    /// Every focusable widget in drawing order, for tabbing.
+    fn focus_order(&self, id: WidgetId, out: &mut Vec<WidgetId>) {
+        if let WidgetKind::Container { children, .. } = &self.widgets[id].kind {
+            for c in children {
+                self.focus_order(*c, out);
+            }
+        } else if self.widgets[id].focusable() {
+            out.push(id);
+        }
+    }
+
+    fn to_target(&self, renderer: &TextureRenderer, state: &State, position: &winit::dpi::PhysicalPosition<f64>) -> ScreenPoint {
+        let p = ScreenPoint::from_mouse(position);
+        match &self.viewport {
+            None => p,
+            Some(vp) => {
+                let size = renderer.target_size(state);
+                let uv = vp.to_panel(&p);
+                ScreenPoint::new(uv.u * size.width as f32, uv.v * size.height as f32)
+            }
+        }
+    }
+
+    fn line_height(&self, state: &State) -> f32 {
+        let size = self.theme.font_size * state.scale_factor as f32;
+        self.font(state).map(|f| f.line_height(size)).unwrap_or(size)
+    }
+
+    fn set_slider_from_cursor(&mut self, id: WidgetId, state: &State) {
+        let handle = self.line_height(state) * 0.4;
+        let cursor = match self.cursor {
+            Some(c) => c,
+            None => return,
+        };
+        let rect = self.widgets[id].rect;
+        if let WidgetKind::Slider { min, max, value, step } = &mut self.widgets[id].kind {
+            let t = ((cursor.x - rect.pos.x - handle) / (rect.width - handle * 2.0).max(1.0)).clamp(0.0, 1.0);
+            let mut v = *min + t * (*max - *min);
+            if *step > 0.0 {
+                v = (*min + ((v - *min) / *step).round() * *step).clamp(*min, *max);
+            }
+            if v != *value {
+                *value = v;
+                self.emit(WidgetEvent::ValueChanged(id, v));
+            }
+        }
+    }
+
+    fn caret_from_cursor(&self, id: WidgetId, state: &State) -> usize {
+        let (font, cursor) = match (self.font(state), self.cursor) {
+            (Some(f), Some(c)) => (f, c),
+            _ => return 0,
+        };
+        let w = &self.widgets[id];
+        match &w.kind {
+            WidgetKind::TextField(tf) => {
+                let x = cursor.x - w.rect.pos.x - self.theme.padding * state.scale_factor as f32;
+                font.hit_test(self.theme.font_size * state.scale_factor as f32, &tf.text, x)
+            }
+            _ => 0,
+        }
+    }
+
+    fn press(&mut self, id: WidgetId, state: &State) {
+        self.pressed = Some(id);
+        self.focused = Some(id);
+        let select = self.modifiers.shift();
+        let caret = self.caret_from_cursor(id, state);
+        let row_height = self.line_height(state) + self.theme.padding * state.scale_factor as f32;
+        let rect = self.widgets[id].rect;
+        let cursor_y = self.cursor.map(|c| c.y).unwrap_or(0.0);
+
+        let mut event = None;
+        match &mut self.widgets[id].kind {
+            WidgetKind::TextField(tf) => tf.move_caret(caret, select),
+            WidgetKind::List(list) => {
+                let row = list.scroll + ((cursor_y - rect.pos.y) / row_height).floor().max(0.0) as usize;
+                if row < list.items.len() && list.selected != Some(row) {
+                    list.selected = Some(row);
+                    event = Some(WidgetEvent::SelectionChanged(id, list.selected));
+                }
+            }
+            _ => {}
+        }
+        if let WidgetKind::Slider { .. } = self.widgets[id].kind {
+            self.set_slider_from_cursor(id, state);
+        }
+        if let Some(e) = event {
+            self.emit(e);
+        }
+    }
+
+    /// Clicks a button or toggles a checkbox.
+    fn activate(&mut self, id: WidgetId) {
+        let event = match &mut self.widgets[id].kind {
+            WidgetKind::Button(_) => Some(WidgetEvent::Clicked(id)),
+            WidgetKind::Checkbox { checked, .. } => {
+                *checked = !*checked;
+                Some(WidgetEvent::Toggled(id, *checked))
+            }
+            _ => None,
+        };
+        if let Some(e) = event {
+            self.emit(e);
+        }
+    }
+
+    fn key(&mut self, id: WidgetId, key: VirtualKeyCode) {
+        let select = self.modifiers.shift();
+        let ctrl = self.modifiers.ctrl() || self.modifiers.logo();
+        let mut event = None;
+        match &mut self.widgets[id].kind {
+            WidgetKind::Button(_) | WidgetKind::Checkbox { .. } => {
+                if matches!(key, VirtualKeyCode::Space | VirtualKeyCode::Return) {
+                    self.activate(id);
+                }
+            }
+            WidgetKind::Slider { min, max, value, step } => {
+                let delta = if *step > 0.0 { *step } else { (*max - *min) / 100.0 };
+                let v = match key {
+                    VirtualKeyCode::Left | VirtualKeyCode::Down => *value - delta,
+                    VirtualKeyCode::Right | VirtualKeyCode::Up => *value + delta,
+                    VirtualKeyCode::Home => *min,
+                    VirtualKeyCode::End => *max,
+                    _ => *value,
+                }.clamp(*min, *max);
+                if v != *value {
+                    *value = v;
+                    event = Some(WidgetEvent::ValueChanged(id, v));
+                }
+            }
+            WidgetKind::TextField(tf) => {
+                let before = tf.text.clone();
+                match key {
+                    VirtualKeyCode::Back => tf.delete(false),
+                    VirtualKeyCode::Delete => tf.delete(true),
+                    VirtualKeyCode::Left => {
+                        let to = if !select && !tf.selection().is_empty() { tf.selection().start } else { tf.caret.saturating_sub(1) };
+                        tf.move_caret(to, select);
+                    }
+                    VirtualKeyCode::Right => {
+                        let to = if !select && !tf.selection().is_empty() { tf.selection().end } else { tf.caret + 1 };
+                        tf.move_caret(to, select);
+                    }
+                    VirtualKeyCode::Home => tf.move_caret(0, select),
+                    VirtualKeyCode::End => tf.move_caret(usize::MAX, select),
+                    VirtualKeyCode::A if ctrl => {
+                        tf.anchor = 0;
+                        tf.move_caret(usize::MAX, true);
+                    }
+                    VirtualKeyCode::Return => event = Some(WidgetEvent::Submitted(id, tf.text.clone())),
+                    _ => {}
+                }
+                if tf.text != before {
+                    event = Some(WidgetEvent::TextChanged(id, tf.text.clone()));
+                }
+            }
+            WidgetKind::List(list) if !list.items.is_empty() => {
+                let last = list.items.len() - 1;
+                let selected = match (key, list.selected) {
+                    (VirtualKeyCode::Up, Some(s)) => Some(s.saturating_sub(1)),
+                    (VirtualKeyCode::Down, Some(s)) => Some((s + 1).min(last)),
+                    (VirtualKeyCode::Up | VirtualKeyCode::Down, None) => Some(0),
+                    (VirtualKeyCode::Home, _) => Some(0),
+                    (VirtualKeyCode::End, _) => Some(last),
+                    (_, s) => s,
+                };
+                if selected != list.selected {
+                    list.selected = selected;
+                    list.reveal_selected();
+                    event = Some(WidgetEvent::SelectionChanged(id, selected));
+                }
+            }
+            _ => {}
+        }
+        if let Some(e) = event {
+            self.emit(e);
+        }
+    }
+
+    /// Routes a window event to the widgets. Call from `ProgramHook::input`.
+    pub fn input(&mut self, renderer: &TextureRenderer, state: &State, event: &WindowEvent) -> EventLoopAction<M> {
+        self.layout_if_needed(renderer, state);
+
+        match event {
+            WindowEvent::CursorMoved { position, .. } => {
+                let p = self.to_target(renderer, state, position);
+                self.cursor = Some(p);
+                self.hovered = self.hit(self.root, &p);
+                if let Some(id) = self.pressed {
+                    match self.widgets[id].kind {
+                        WidgetKind::Slider { .. } => self.set_slider_from_cursor(id, state),
+                        WidgetKind::TextField(_) => {
+                            let caret = self.caret_from_cursor(id, state);
+                            if let WidgetKind::TextField(tf) = &mut self.widgets[id].kind {
+                                tf.move_caret(caret, true);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            WindowEvent::CursorLeft { .. } => {
+                self.cursor = None;
+                self.hovered = None;
+            }
+            WindowEvent::ModifiersChanged(m) => self.modifiers = *m,
+            WindowEvent::MouseInput { state: ElementState::Pressed, button: MouseButton::Left, .. } => {
+                match self.hovered.filter(|h| self.widgets[*h].enabled) {
+                    Some(id) => self.press(id, state),
+                    None => self.focused = None,
+                }
+            }
+            WindowEvent::MouseInput { state: ElementState::Released, button: MouseButton::Left, .. } => {
+                if let Some(id) = self.pressed.take() {
+                    if self.hovered == Some(id) {
+                        self.activate(id);
+                    }
+                }
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                let row_height = self.line_height(state) + self.theme.padding * state.scale_factor as f32;
+                if let Some(id) = self.hovered {
+                    if let WidgetKind::List(list) = &mut self.widgets[id].kind {
+                        let rows = match delta {
+                            MouseScrollDelta::LineDelta(_, y) => *y,
+                            MouseScrollDelta::PixelDelta(p) => p.y as f32 / row_height,
+                        };
+                        list.scroll = (list.scroll as f32 - rows).round().max(0.0) as usize;
+                        list.clamp_scroll();
+                    }
+                }
+            }
+            WindowEvent::ReceivedCharacter(c) => {
+                if let Some(id) = self.focused {
+                    if let WidgetKind::TextField(tf) = &mut self.widgets[id].kind {
+                        if !c.is_control() {
+                            tf.insert(&c.to_string());
+                            let text = tf.text.clone();
+                            self.emit(WidgetEvent::TextChanged(id, text));
+                        }
+                    }
+                }
+            }
+            WindowEvent::KeyboardInput { input: KeyboardInput { state: ElementState::Pressed, virtual_keycode: Some(key), .. }, .. } => {
+                if *key == VirtualKeyCode::Tab {
+                    let mut order = vec![];
+                    self.focus_order(self.root, &mut order);
+                    if !order.is_empty() {
+                        let current = self.focused.and_then(|f| order.iter().position(|o| *o == f));
+                        let next = match (current, self.modifiers.shift()) {
+                            (None, _) => 0,
+                            (Some(i), false) => (i + 1) % order.len(),
+                            (Some(i), true) => (i + order.len() - 1) % order.len(),
+                        };
+                        self.focused = Some(order[next]);
+                    }
+                } else if let Some(id) = self.focused {
+                    self.key(id, *key);
+                }
+            }
+            _ => {}
+        }
+
+        self.next_message()
+    }
+
+    /// Lays the tree out again if the target size or scale factor changed, and returns messages queued by `input`.
+    /// Call from `ProgramHook::update`.
+    pub fn update(&mut self, renderer: &TextureRenderer, state: &State) -> EventLoopAction<M> {
+        self.layout_if_needed(renderer, state);
+        self.next_message()
+    }
+
+    // ---- rendering ----
+
+    fn text<'s>(&self, text: &'s str, rect: &ScreenRectangle, line: f32, color: [f32; 4], align: HorizontalAlign, state: &State) -> TextSection<'s> {
+        let size = state.pass.target_size;
+        // vertically centered in the rect
+        let top = rect.pos.y + (rect.height - line) / 2.0;
+        let line_rect = ScreenRectangle::new(ScreenPoint::new(rect.pos.x, top), rect.width, line);
+        let mut section = TextSection::new(text, self.theme.font, self.theme.font_size * state.scale_factor as f32, WorldRectangle::from_screen_rect(&size, &line_rect));
+        section.color = color;
+        section.align = align;
+        section.wrap = false;
+        section
+    }
+
+    fn draw_widget<'s>(&'s self, id: WidgetId, state: &State, layers: &mut Vec<WidgetLayer<'s>>) {
+        let w = &self.widgets[id];
+        let size = state.pass.target_size;
+        let scale = state.scale_factor as f32;
+        let theme = &self.theme;
+        let pad = theme.padding * scale;
+        let radius = theme.corner_radius * scale;
+        let line = self.line_height(state);
+        let font_size = theme.font_size * scale;
+        let font = self.font(state);
+        let rect = w.rect;
+        let world = WorldRectangle::from_screen_rect(&size, &rect);
+        let point = |x: f32, y: f32| WorldPoint::from_screen_point(&size, ScreenPoint::new(x, y));
+        let sub_rect = |x: f32, y: f32, width: f32, height: f32| WorldRectangle::from_screen_rect(&size, &ScreenRectangle::new(ScreenPoint::new(x, y), width, height));
+
+        let hovered = self.hovered == Some(id) && w.enabled;
+        let focused = self.focused == Some(id);
+        let dim = |c: [f32; 4]| if w.enabled { c } else { [c[0], c[1], c[2], c[3] * 0.5] };
+        let fill = if self.pressed == Some(id) && hovered {
+            theme.pressed
+        } else if hovered {
+            theme.hover
+        } else {
+            theme.surface
+        };
+        let outline = if focused { theme.accent } else { theme.border };
+
+        // drawn over text already in the layer, so it needs a layer of it's own
+        if layers.last().is_none_or(|l| l.covers_text(&rect)) {
+            layers.push(WidgetLayer { shapes: state.shape_builder(), sections: vec![], text_rects: vec![] });
+        }
+        let layer = layers.last_mut().unwrap();
+        let (shapes, sections) = (&mut layer.shapes, &mut layer.sections);
+        let text_count = sections.len();
+
+        match &w.kind {
+            WidgetKind::Container { layout, children } => {
+                if let Some(bg) = layout.background {
+                    shapes.fill_rect(&world, bg);
+                }
+                for c in children {
+                    self.draw_widget(*c, state, layers);
+                }
+                return;
+            }
+            WidgetKind::Label(text) => {
+                sections.push(self.text(text, &rect, line, dim(theme.text), HorizontalAlign::Left, state));
+            }
+            WidgetKind::Button(label) => {
+                shapes.fill_rounded_rect(&world, radius, dim(fill));
+                shapes.stroke_rounded_rect(&world, radius, scale, dim(outline));
+                sections.push(self.text(label, &rect, line, dim(theme.text), HorizontalAlign::Center, state));
+            }
+            WidgetKind::Checkbox { label, checked } => {
+                let top = rect.pos.y + (rect.height - line) / 2.0;
+                let check = sub_rect(rect.pos.x, top, line, line);
+                shapes.fill_rounded_rect(&check, radius, dim(fill));
+                shapes.stroke_rounded_rect(&check, radius, scale, dim(outline));
+                if *checked {
+                    let inset = 4.0 * scale;
+                    shapes.fill_rounded_rect(&sub_rect(rect.pos.x + inset, top + inset, line - inset * 2.0, line - inset * 2.0), radius / 2.0, dim(theme.accent));
+                }
+                let text_rect = ScreenRectangle::new(ScreenPoint::new(rect.pos.x + line + pad, rect.pos.y), (rect.width - line - pad).max(0.0), rect.height);
+                sections.push(self.text(label, &text_rect, line, dim(theme.text), HorizontalAlign::Left, state));
+            }
+            WidgetKind::Slider { min, max, value, .. } => {
+                let handle = line * 0.4;
+                let cy = rect.pos.y + rect.height / 2.0;
+                let track = 4.0 * scale;
+                let (x0, x1) = (rect.pos.x + handle, rect.right() - handle);
+                let t = if max > min { (value - min) / (max - min) } else { 0.0 };
+                let hx = x0 + (x1 - x0) * t;
+                shapes.fill_rounded_rect(&sub_rect(x0, cy - track / 2.0, x1 - x0, track), track / 2.0, dim(theme.border));
+                shapes.fill_rounded_rect(&sub_rect(x0, cy - track / 2.0, hx - x0, track), track / 2.0, dim(theme.accent));
+                shapes.fill_circle(point(hx, cy), handle, dim(if hovered { theme.text } else { theme.dim_text }));
+                if focused {
+                    shapes.stroke_circle(point(hx, cy), handle + 2.0 * scale, scale, theme.accent);
+                }
+            }
+            WidgetKind::TextField(tf) => {
+                shapes.fill_rounded_rect(&world, radius, dim(theme.pressed));
+                shapes.stroke_rounded_rect(&world, radius, scale, dim(outline));
+                let text_x = rect.pos.x + pad;
+                let top = rect.pos.y + (rect.height - line) / 2.0;
+                if let (Some(font), true) = (font, focused) {
+                    let sel = tf.selection();
+                    if !sel.is_empty() {
+                        let a = font.caret_x(font_size, &tf.text, sel.start);
+                        let b = font.caret_x(font_size, &tf.text, sel.end);
+                        let mut color = theme.accent;
+                        color[3] = 0.4;
+                        shapes.fill_rect(&sub_rect(text_x + a, top, b - a, line), color);
+                    }
+                    let x = text_x + font.caret_x(font_size, &tf.text, tf.caret);
+                    shapes.line(point(x, top), point(x, top + line), scale.max(1.0), theme.text);
+                }
+                let text_rect = ScreenRectangle::new(ScreenPoint::new(text_x, rect.pos.y), (rect.width - pad * 2.0).max(0.0), rect.height);
+                if tf.text.is_empty() {
+                    sections.push(self.text(&tf.placeholder, &text_rect, line, dim(theme.dim_text), HorizontalAlign::Left, state));
+                } else {
+                    sections.push(self.text(&tf.text, &text_rect, line, dim(theme.text), HorizontalAlign::Left, state));
+                }
+            }
+            WidgetKind::List(list) => {
+                shapes.fill_rounded_rect(&world, radius, dim(theme.pressed));
+                shapes.stroke_rounded_rect(&world, radius, scale, dim(outline));
+                let row_height = line + pad;
+                let hover_row = match (hovered, self.cursor) {
+                    (true, Some(c)) => Some(list.scroll + ((c.y - rect.pos.y) / row_height).floor().max(0.0) as usize),
+                    _ => None,
+                };
+                let end = (list.scroll + list.visible_rows).min(list.items.len());
+                for (i, item) in list.items.iter().enumerate().take(end).skip(list.scroll) {
+                    let y = rect.pos.y + (i - list.scroll) as f32 * row_height;
+                    let row = ScreenRectangle::new(ScreenPoint::new(rect.pos.x, y), rect.width, row_height);
+                    if list.selected == Some(i) {
+                        shapes.fill_rect(&WorldRectangle::from_screen_rect(&size, &row), dim(theme.accent));
+                    } else if hover_row == Some(i) {
+                        shapes.fill_rect(&WorldRectangle::from_screen_rect(&size, &row), theme.hover);
+                    }
+                    let text_rect = ScreenRectangle::new(ScreenPoint::new(rect.pos.x + pad, y), (rect.width - pad * 2.0).max(0.0), row_height);
+                    sections.push(self.text(item, &text_rect, line, dim(theme.text), HorizontalAlign::Left, state));
+                }
+            }
+        }
+        if sections.len() > text_count {
+            layer.text_rects.push(rect);
+        }
+    }
+
+    /// Draws every widget in tree order, so later widgets cover earlier ones. Call from `ProgramHook::render`.
+    /// * Draws the last layout: nothing shows before the first `update`, `input` or `layout`,
+    ///   and a resized target shows the old layout until the next one.
+    /// * Widgets share one batch of shapes and one of text until a widget overlaps text drawn before it.
+    pub fn render<'a>(&self, state: &'a mut State, render_pass: &mut wgpu::RenderPass<'a>) {
+        let mut layers = vec![];
+        self.draw_widget(self.root, state, &mut layers);
+
+        let batches: Vec<_> = layers.iter()
+            .map(|l| (state.prepare_shapes(&l.shapes), state.prepare_text(&l.sections)))
+            .collect();
+        let state: &'a State = state;
+        for (shape_batch, text_batch) in batches {
+            shape_batch.draw(state, render_pass);
+            text_batch.draw(state, render_pass);
+        }
+    }
+}
+
+/// Shapes drawn under text, for the widgets of one `WidgetTree::render` batch.
+struct WidgetLayer<'s> {
+    shapes: ShapeBuilder,
+    sections: Vec<TextSection<'s>>,
+    /// The rects of the widgets with text in `sections`.
+    text_rects: Vec<ScreenRectangle>,
+}
+
+impl WidgetLayer<'_> {
+    /// Whether a widget at `rect` would be drawn over some of the layer's text.
+    fn covers_text(&self, rect: &ScreenRectangle) -> bool {
+        self.text_rects.iter()
+            .filter_map(|r| r.intersection(rect))
+            .any(|r| r.width > 0.0 && r.height > 0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree(layout: FlexLayout) -> WidgetTree<WidgetEvent> {
+        WidgetTree::new(Theme::dark(0), layout, |e| e)
+    }
+
+    fn arrange(tree: &mut WidgetTree<WidgetEvent>, width: f32, height: f32) {
+        tree.arrange(tree.root, ScreenRectangle::new(ScreenPoint::new(0.0, 0.0), width, height), None, 1.0);
+    }
+
+    fn rect(tree: &WidgetTree<WidgetEvent>, id: WidgetId) -> [f32; 4] {
+        let r = tree.widget(id).rect;
+        [r.pos.x, r.pos.y, r.width, r.height]
+    }
+
+    fn boxed(width: f32, height: f32) -> Widget {
+        Widget::label("").with_size(Length::Px(width), Length::Px(height))
+    }
+
+    #[test]
+    fn fill_weights_share_the_space_left() {
+        let mut tree = tree(FlexLayout { padding: 10.0, gap: 10.0, ..FlexLayout::row() });
+        let fixed = tree.add(tree.root, "", boxed(50.0, 20.0)).unwrap();
+        let one = tree.add(tree.root, "", Widget::label("").with_size(Length::Fill(1.0), Length::Auto)).unwrap();
+        let two = tree.add(tree.root, "", Widget::label("").with_size(Length::Fill(2.0), Length::Px(30.0))).unwrap();
+        arrange(&mut tree, 300.0, 100.0);
+        // 280 inside the padding, less two gaps and the fixed child leaves 210 to split 1:2
+        assert_eq!(rect(&tree, fixed), [10.0, 10.0, 50.0, 80.0]);
+        assert_eq!(rect(&tree, one), [70.0, 10.0, 70.0, 80.0]);
+        assert_eq!(rect(&tree, two), [150.0, 10.0, 140.0, 80.0]);
+    }
+
+    #[test]
+    fn justify_and_align() {
+        let place = |justify: Justify, align: Align| {
+            let mut tree = tree(FlexLayout { padding: 0.0, gap: 10.0, justify, align, ..FlexLayout::column() });
+            let a = tree.add(tree.root, "", boxed(40.0, 20.0)).unwrap();
+            let b = tree.add(tree.root, "", boxed(60.0, 20.0)).unwrap();
+            arrange(&mut tree, 200.0, 200.0);
+            (rect(&tree, a), rect(&tree, b))
+        };
+        assert_eq!(place(Justify::Start, Align::Start), ([0.0, 0.0, 40.0, 20.0], [0.0, 30.0, 60.0, 20.0]));
+        assert_eq!(place(Justify::Center, Align::Center), ([80.0, 75.0, 40.0, 20.0], [70.0, 105.0, 60.0, 20.0]));
+        assert_eq!(place(Justify::End, Align::End), ([160.0, 150.0, 40.0, 20.0], [140.0, 180.0, 60.0, 20.0]));
+        assert_eq!(place(Justify::SpaceBetween, Align::Stretch), ([0.0, 0.0, 200.0, 20.0], [0.0, 180.0, 200.0, 20.0]));
+    }
+
+    #[test]
+    fn nested_padding_and_stacks() {
+        let mut tree = tree(FlexLayout { padding: 5.0, ..FlexLayout::stack() });
+        let inner = tree.add(tree.root, "", Widget::container(FlexLayout { padding: 10.0, gap: 4.0, ..FlexLayout::row() })).unwrap();
+        let a = tree.add(inner, "", boxed(20.0, 20.0)).unwrap();
+        let b = tree.add(inner, "", boxed(20.0, 20.0)).unwrap();
+        let over = tree.add(tree.root, "", boxed(30.0, 30.0)).unwrap();
+        arrange(&mut tree, 100.0, 100.0);
+        assert_eq!(rect(&tree, inner), [5.0, 5.0, 90.0, 90.0]);
+        assert_eq!(rect(&tree, a), [15.0, 15.0, 20.0, 70.0]);
+        assert_eq!(rect(&tree, b), [39.0, 15.0, 20.0, 70.0]);
+        // stacked children all start at the padding, and are stretched over each other
+        assert_eq!(rect(&tree, over), [5.0, 5.0, 90.0, 90.0]);
+    }
+
+    #[test]
+    fn text_editing_keeps_to_char_boundaries() {
+        let mut tf = TextFieldState { text: "añb".into(), caret: 0, anchor: 0, placeholder: String::new() };
+        let on_boundary = |tf: &TextFieldState| tf.text.is_char_boundary(tf.byte_index(tf.caret));
+
+        tf.move_caret(2, false);
+        tf.insert("€");
+        assert_eq!((tf.text.as_str(), tf.caret), ("añ€b", 3));
+        assert!(on_boundary(&tf));
+        tf.delete(false);
+        assert_eq!((tf.text.as_str(), tf.caret), ("añb", 2));
+        tf.delete(true);
+        assert_eq!((tf.text.as_str(), tf.caret), ("añ", 2));
+        tf.delete(true);
+        assert_eq!(tf.text, "añ");
+
+        tf.move_caret(usize::MAX, false);
+        assert_eq!(tf.caret, 2);
+        tf.move_caret(1, true);
+        assert_eq!(tf.selected_text(), "ñ");
+        tf.insert("xy");
+        assert_eq!((tf.text.as_str(), tf.caret, tf.anchor), ("axy", 3, 3));
+        tf.move_caret(0, false);
+        tf.delete(false);
+        assert_eq!((tf.text.as_str(), tf.caret), ("axy", 0));
+    }
+
+    #[test]
+    fn list_scroll_and_selection_are_clamped() {
+        let mut tree = tree(FlexLayout { padding: 0.0, ..FlexLayout::column() });
+        let items = (0..10).map(|i| i.to_string()).collect();
+        let list = tree.add(tree.root, "", Widget::list(items).with_size(Length::Fill(1.0), Length::Fill(1.0))).unwrap();
+        let list_state = |tree: &WidgetTree<WidgetEvent>| match &tree.widget(list).kind {
+            WidgetKind::List(l) => (l.selected, l.scroll, l.visible_rows),
+            _ => unreachable!(),
+        };
+        if let WidgetKind::List(l) = &mut tree.widget_mut(list).kind {
+            l.scroll = 9;
+        }
+        // rows are the font size plus padding, 22px without a font
+        arrange(&mut tree, 100.0, 88.0);
+        assert_eq!(list_state(&tree), (None, 6, 4));
+
+        tree.key(list, VirtualKeyCode::Down);
+        assert_eq!(list_state(&tree), (Some(0), 0, 4));
+        tree.key(list, VirtualKeyCode::Up);
+        assert_eq!(list_state(&tree), (Some(0), 0, 4));
+        tree.key(list, VirtualKeyCode::End);
+        assert_eq!(list_state(&tree), (Some(9), 6, 4));
+        tree.key(list, VirtualKeyCode::Down);
+        assert_eq!(list_state(&tree), (Some(9), 6, 4));
+        // one event per change
+        let events: Vec<WidgetEvent> = tree.pending.drain(..).collect();
+        assert_eq!(events, vec![WidgetEvent::SelectionChanged(list, Some(0)), WidgetEvent::SelectionChanged(list, Some(9))]);
+    }
+
+    #[test]
+    fn slider_steps_stay_in_range() {
+        let mut tree = tree(FlexLayout::row());
+        let slider = tree.add(tree.root, "", Widget::new(WidgetKind::Slider { min: 0.0, max: 10.0, value: 5.0, step: 2.0 })).unwrap();
+        let value = |tree: &WidgetTree<WidgetEvent>| match tree.widget(slider).kind {
+            WidgetKind::Slider { value, .. } => value,
+            _ => unreachable!(),
+        };
+        for (key, expected) in [(VirtualKeyCode::Right, 7.0), (VirtualKeyCode::Up, 9.0), (VirtualKeyCode::Right, 10.0), (VirtualKeyCode::Home, 0.0), (VirtualKeyCode::Left, 0.0)] {
+            tree.key(slider, key);
+            assert_eq!(value(&tree), expected, "{:?}", key);
+        }
+        assert_eq!(tree.pending.len(), 4);
+
+        // without a step, keys move by a hundredth of the range
+        let fine = tree.add(tree.root, "", Widget::slider(0.0, 50.0, 50.0)).unwrap();
+        tree.key(fine, VirtualKeyCode::Left);
+        assert!(matches!(tree.widget(fine).kind, WidgetKind::Slider { value, .. } if value == 49.5));
+    }
+
+    #[test]
+    fn add_needs_a_container() {
+        let mut tree = WidgetTree::new(Theme::dark(0), FlexLayout::column(), |_| ());
+        let row = tree.add(tree.root, "row", Widget::container(FlexLayout::row())).unwrap();
+        let label = tree.add(row, "", Widget::label("hi")).unwrap();
+        assert!(tree.add(label, "nope", Widget::button("x")).is_err());
+        assert!(tree.add(99, "", Widget::button("x")).is_err());
+        // failed adds leave the tree as it was
+        assert_eq!(tree.widgets.len(), 3);
+        assert_eq!(tree.id("nope"), None);
+    }
+}